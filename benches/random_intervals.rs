@@ -1,10 +1,35 @@
+use bedder::bedder_bed::BedderBed;
+use bedder::error;
 use bedder::intersection::IntersectionIterator;
 use bedder::position::{Field, FieldError, Positioned, PositionedIterator, Value};
-use bedder::string::String;
+use bedder::sniff;
+use bedder::string::{to_string_type, String};
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use rand::Rng;
+use std::alloc::{GlobalAlloc, Layout, System};
 use std::collections::HashMap;
-use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// counts allocations so we can report allocations per record for file-backed inputs.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
 
 #[derive(Debug)]
 struct Interval {
@@ -25,7 +50,7 @@ impl Positioned for Interval {
     }
 
     fn value(&self, _v: Field) -> Result<Value, FieldError> {
-        Ok(Value::Strings(vec![String::from("foo")]))
+        Ok(Value::Strings(vec![Some(String::from("foo"))]))
     }
 }
 struct Intervals {
@@ -42,11 +67,11 @@ impl Intervals {
     fn new(name: String, n_intervals: usize, interval_len: u64) -> Self {
         Intervals {
             i: 0,
-            name: name,
+            name,
             n_intervals,
             curr_max: 1.0,
             rng: rand::thread_rng(),
-            interval_len: interval_len,
+            interval_len,
             saved_chrom: String::from("chr1"),
         }
     }
@@ -56,10 +81,10 @@ impl PositionedIterator for Intervals {
     type Item = Box<dyn Positioned>;
 
     fn name(&self) -> String {
-        String::from(format!("{}:{}", self.name, self.i))
+        to_string_type(format!("{}:{}", self.name, self.i))
     }
 
-    fn next_position(&mut self, _q: Option<&dyn Positioned>) -> Option<io::Result<Self::Item>> {
+    fn next_position(&mut self, _q: Option<&dyn Positioned>) -> Option<error::Result<Self::Item>> {
         if self.i < self.n_intervals {
             self.i += 1;
             let r: f64 = self.rng.gen();
@@ -67,7 +92,7 @@ impl PositionedIterator for Intervals {
             let start = ((1.0 - self.curr_max) * (MAX_POSITION as f64)) as u64;
             Some(Ok(Box::new(Interval {
                 chrom: self.saved_chrom.clone(),
-                start: start,
+                start,
                 stop: start + self.interval_len,
             })))
         } else {
@@ -96,5 +121,88 @@ pub fn intersection_benchmark(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, intersection_benchmark);
+/// write a sorted bed file with `n` intervals of length `len` spaced by `step`.
+fn write_bed(path: &Path, n: u64, step: u64, len: u64) {
+    let mut f = std::io::BufWriter::new(std::fs::File::create(path).expect("error creating bed"));
+    for i in 0..n {
+        writeln!(
+            f,
+            "chr1\t{}\t{}\tname{}\t{}",
+            i * step,
+            i * step + len,
+            i,
+            i % 7
+        )
+        .expect("error writing bed");
+    }
+}
+
+fn intersect_files(a: &Path, b: &Path, chrom_order: &HashMap<String, usize>) -> usize {
+    let a_ivs = sniff::open_file(a).expect("error opening a");
+    let b_ivs = sniff::open_file(b).expect("error opening b");
+    let iter =
+        IntersectionIterator::new(a_ivs, vec![b_ivs], chrom_order).expect("error getting iterator");
+    let mut n = 0;
+    iter.for_each(|intersection| {
+        let intersection = intersection.expect("error getting intersection");
+        n += intersection.overlapping.len();
+        black_box(intersection.overlapping);
+    });
+    n
+}
+
+/// same as `intersect_files`, but with concrete BedderBed iterators (no dynamic dispatch).
+fn intersect_bed_files(a: &Path, b: &Path, chrom_order: &HashMap<String, usize>) -> usize {
+    let open = |p: &Path| {
+        let f = std::fs::File::open(p).expect("error opening bed");
+        BedderBed::new(std::io::BufReader::new(f), p)
+    };
+    let iter = IntersectionIterator::new(open(a), vec![open(b)], chrom_order)
+        .expect("error getting iterator");
+    let mut n = 0;
+    iter.for_each(|intersection| {
+        let intersection = intersection.expect("error getting intersection");
+        n += intersection.overlapping.len();
+        black_box(intersection.overlapping);
+    });
+    n
+}
+
+pub fn file_intersection_benchmark(c: &mut Criterion) {
+    let chrom_order = HashMap::from([(String::from("chr1"), 0), (String::from("chr2"), 1)]);
+    let dir = std::env::temp_dir();
+    let a: PathBuf = dir.join(format!("bedder-bench-a-{}.bed", std::process::id()));
+    let b: PathBuf = dir.join(format!("bedder-bench-b-{}.bed", std::process::id()));
+    let n_a = 1_000;
+    let n_b = 100_000;
+    write_bed(&a, n_a, 1000, 500);
+    write_bed(&b, n_b, 10, 100);
+
+    // report steady-state allocations per record read.
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    intersect_files(&a, &b, &chrom_order);
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    eprintln!(
+        "file intersection: {} allocations for {} records ({:.3} per record)",
+        allocations,
+        n_a + n_b,
+        allocations as f64 / (n_a + n_b) as f64
+    );
+
+    c.bench_function("file intersection", |bench| {
+        bench.iter(|| black_box(intersect_files(&a, &b, &chrom_order)));
+    });
+    assert_eq!(
+        intersect_files(&a, &b, &chrom_order),
+        intersect_bed_files(&a, &b, &chrom_order)
+    );
+    c.bench_function("file intersection (static dispatch)", |bench| {
+        bench.iter(|| black_box(intersect_bed_files(&a, &b, &chrom_order)));
+    });
+
+    _ = std::fs::remove_file(&a);
+    _ = std::fs::remove_file(&b);
+}
+
+criterion_group!(benches, intersection_benchmark, file_intersection_benchmark);
 criterion_main!(benches);
@@ -46,7 +46,7 @@ fn main() -> io::Result<()> {
         // see issue #15 for more discussion into using an enum.
         // alternatively, we *can* know the file type from the sniff operation when opening.
         // but this places more burden on the user of the API to track file-types.
-        intersection.overlapping.first().map(|p| {
+        if let Some(p) = intersection.overlapping.first() {
             let value = p
                 .interval
                 .value(bedder::position::Field::String(String::from("AD")))
@@ -56,7 +56,7 @@ fn main() -> io::Result<()> {
                 bedder::position::Value::Ints(i) => println!("AD: {:?}", i),
                 _ => panic!("expected integer"),
             }
-        });
+        }
         println!(
             "{}\t{}\t{}\t{}",
             intersection.base_interval.chrom(),
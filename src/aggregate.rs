@@ -0,0 +1,525 @@
+#[cfg(feature = "vcf")]
+use crate::allele::AlleleMatch;
+use crate::error::{Error, Result};
+use crate::intersection::{Intersection, Intersections};
+use crate::position::{Field, FieldError, Positioned, Value};
+use crate::string::{to_string_type, String};
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::result;
+use std::str::FromStr;
+
+/// Operation to summarize the values of a field across all intervals that overlap a base interval.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    /// number of overlapping intervals.
+    Count,
+    /// number of distinct values.
+    CountDistinct,
+    Sum,
+    Mean,
+    Median,
+    Min,
+    Max,
+    /// value from the first overlapping interval.
+    First,
+    /// value from the last overlapping interval.
+    Last,
+    /// all values, comma-separated.
+    Collapse,
+    /// sorted, unique values, comma-separated.
+    Distinct,
+}
+
+impl FromStr for Operation {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        match s {
+            "count" => Ok(Operation::Count),
+            "count_distinct" => Ok(Operation::CountDistinct),
+            "sum" => Ok(Operation::Sum),
+            "mean" => Ok(Operation::Mean),
+            "median" => Ok(Operation::Median),
+            "min" => Ok(Operation::Min),
+            "max" => Ok(Operation::Max),
+            "first" => Ok(Operation::First),
+            "last" => Ok(Operation::Last),
+            "collapse" => Ok(Operation::Collapse),
+            "distinct" => Ok(Operation::Distinct),
+            _ => Err(to_string_type(format!("unknown operation: {}", s))),
+        }
+    }
+}
+
+/// An Aggregation describes how to summarize `field` from the overlapping intervals
+/// of a single source (matching `Intersection::id`) with `op`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aggregation {
+    pub source: u32,
+    pub field: Field,
+    pub op: Operation,
+}
+
+/// Parse an Aggregation from a `source:field:op` string, e.g. `0:4:sum` or `1:INFO.AF:max`.
+/// The source is the 0-based index of the other (-b) file.
+/// An integer field is a 0-based column index, anything else is a field name.
+impl FromStr for Aggregation {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        let (source, rest) = s
+            .split_once(':')
+            .ok_or_else(|| to_string_type(format!("expected source:field:op, got: {}", s)))?;
+        let (field, op) = rest
+            .rsplit_once(':')
+            .ok_or_else(|| to_string_type(format!("expected source:field:op, got: {}", s)))?;
+        let source = source
+            .parse::<u32>()
+            .map_err(|_| to_string_type(format!("invalid source index in: {}", s)))?;
+        if field.is_empty() {
+            return Err(to_string_type(format!("empty field in: {}", s)));
+        }
+        let field = match field.parse::<usize>() {
+            Ok(i) => Field::Int(i),
+            Err(_) => Field::String(String::from(field)),
+        };
+        Ok(Aggregation {
+            source,
+            field,
+            op: op.parse()?,
+        })
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self {
+            Operation::Count => "count",
+            Operation::CountDistinct => "count_distinct",
+            Operation::Sum => "sum",
+            Operation::Mean => "mean",
+            Operation::Median => "median",
+            Operation::Min => "min",
+            Operation::Max => "max",
+            Operation::First => "first",
+            Operation::Last => "last",
+            Operation::Collapse => "collapse",
+            Operation::Distinct => "distinct",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+impl fmt::Display for Aggregation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.source, self.field, self.op)
+    }
+}
+
+/// numeric values collected from overlaps. Ints are kept as long as every value is an integer.
+enum Numbers {
+    Ints(Vec<i64>),
+    Floats(Vec<f64>),
+}
+
+impl Aggregation {
+    /// Summarize the overlapping intervals from `self.source`, which is named `name` in errors.
+    /// Returns `Ok(None)` when the value is missing because there were no overlaps
+    /// (`Count` and `CountDistinct` return 0 instead).
+    pub fn apply<P: Positioned>(
+        &self,
+        intersections: &Intersections<P>,
+        name: &str,
+    ) -> Result<Option<Value>> {
+        let overlapping = intersections
+            .overlapping
+            .iter()
+            .filter(|o| o.id == self.source)
+            .collect::<Vec<_>>();
+        self.aggregate_located(
+            &overlapping,
+            name,
+            |o| o,
+            |o| o.interval.value(self.field.clone()),
+        )
+    }
+
+    /// Like `apply` but using only the overlapping variants that share an allele with the base
+    /// variant (see `allele::allele_matches`). Per-allele fields such as `INFO.AF` are reduced
+    /// to the values of the matching alleles.
+    #[cfg(feature = "vcf")]
+    pub fn apply_allele_matches<P: Positioned>(
+        &self,
+        matches: &[AlleleMatch<P>],
+        name: &str,
+    ) -> Result<Option<Value>> {
+        let matches = matches
+            .iter()
+            .filter(|m| m.intersection.id == self.source)
+            .collect::<Vec<_>>();
+        self.aggregate_located(
+            &matches,
+            name,
+            |m| m.intersection,
+            |m| m.value(self.field.clone()),
+        )
+    }
+
+    /// aggregate the value of each item, reporting errors with the name, line and region of
+    /// the overlapping interval they came from.
+    fn aggregate_located<T, P: Positioned>(
+        &self,
+        items: &[T],
+        name: &str,
+        intersection: impl Fn(&T) -> &Intersection<P>,
+        value: impl Fn(&T) -> result::Result<Value, FieldError>,
+    ) -> Result<Option<Value>> {
+        let error = |item: &T, e: FieldError| {
+            let o = intersection(item);
+            Error::field(name, o.line, o.interval.as_ref(), e)
+        };
+        let values = items
+            .iter()
+            .map(|item| value(item).map_err(|e| error(item, e)))
+            .collect::<Result<Vec<_>>>()?;
+        self.aggregate(values).map_err(|e| {
+            // report the first interval with a value that can not be used.
+            let item = items
+                .iter()
+                .find(|item| match value(item) {
+                    Ok(v) => self.aggregate(vec![v]).is_err(),
+                    Err(_) => true,
+                })
+                .unwrap_or(&items[0]);
+            error(item, e)
+        })
+    }
+
+    /// Summarize a list of values, one per overlapping interval. `Missing` values (e.g. an
+    /// INFO field that a record lacks) are counted by `Count` and skipped otherwise.
+    pub fn aggregate(&self, mut values: Vec<Value>) -> result::Result<Option<Value>, FieldError> {
+        if self.op == Operation::Count {
+            return Ok(Some(Value::Ints(vec![Some(values.len() as i64)])));
+        }
+        values.retain(|v| *v != Value::Missing);
+        if self.op == Operation::CountDistinct {
+            let n = strings(&values)
+                .into_iter()
+                .flatten()
+                .collect::<BTreeSet<_>>()
+                .len();
+            return Ok(Some(Value::Ints(vec![Some(n as i64)])));
+        }
+        if values.is_empty() {
+            return Ok(None);
+        }
+        Ok(match self.op {
+            Operation::First => values.into_iter().next(),
+            Operation::Last => values.into_iter().last(),
+            Operation::Collapse => Some(Value::Strings(strings(&values))),
+            Operation::Distinct => Some(Value::Strings(
+                strings(&values)
+                    .into_iter()
+                    .flatten()
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .map(Some)
+                    .collect(),
+            )),
+            Operation::Sum => self.numbers(values)?.sum(),
+            Operation::Min => self.numbers(values)?.extreme(Ordering::Less),
+            Operation::Max => self.numbers(values)?.extreme(Ordering::Greater),
+            Operation::Mean => {
+                let v = self.numbers(values)?.floats();
+                if v.is_empty() {
+                    None
+                } else {
+                    Some(Value::Floats(vec![Some(
+                        v.iter().sum::<f64>() / v.len() as f64,
+                    )]))
+                }
+            }
+            Operation::Median => {
+                let mut v = self.numbers(values)?.floats();
+                v.sort_by(|a, b| a.total_cmp(b));
+                let n = v.len();
+                match n {
+                    0 => None,
+                    _ if n % 2 == 1 => Some(Value::Floats(vec![Some(v[n / 2])])),
+                    _ => Some(Value::Floats(vec![Some((v[n / 2 - 1] + v[n / 2]) / 2.0)])),
+                }
+            }
+            Operation::Count | Operation::CountDistinct => unreachable!(),
+        })
+    }
+
+    /// convert values to numbers, parsing strings (e.g. extra BED columns) as needed.
+    /// Missing elements are skipped and flags count as 0 or 1.
+    fn numbers(&self, values: Vec<Value>) -> result::Result<Numbers, FieldError> {
+        let mut ints = Vec::new();
+        let mut floats = Vec::new();
+        let mut all_ints = true;
+        for value in values {
+            match value {
+                Value::Ints(v) => ints.extend(v.into_iter().flatten()),
+                Value::Floats(v) => {
+                    all_ints = false;
+                    floats.extend(v.into_iter().flatten())
+                }
+                Value::Strings(v) => {
+                    for s in v.into_iter().flatten() {
+                        if let Ok(i) = s.parse::<i64>() {
+                            ints.push(i)
+                        } else if let Ok(f) = s.parse::<f64>() {
+                            all_ints = false;
+                            floats.push(f)
+                        } else {
+                            return Err(FieldError::TypeMismatch {
+                                field: to_string_type(self.field.to_string()),
+                                value: s,
+                            });
+                        }
+                    }
+                }
+                Value::Flag(b) => ints.push(b as i64),
+                Value::Missing => {}
+            }
+        }
+        Ok(if all_ints {
+            Numbers::Ints(ints)
+        } else {
+            floats.extend(ints.into_iter().map(|i| i as f64));
+            Numbers::Floats(floats)
+        })
+    }
+}
+
+impl Numbers {
+    fn floats(self) -> Vec<f64> {
+        match self {
+            Numbers::Ints(v) => v.into_iter().map(|i| i as f64).collect(),
+            Numbers::Floats(v) => v,
+        }
+    }
+
+    /// sum of the values, or None if there are none. Integer sums that do not fit
+    /// in an i64 are returned as a float.
+    fn sum(self) -> Option<Value> {
+        match self {
+            Numbers::Ints(v) if v.is_empty() => None,
+            Numbers::Floats(v) if v.is_empty() => None,
+            Numbers::Ints(v) => {
+                let s = v.iter().map(|&i| i as i128).sum::<i128>();
+                Some(match i64::try_from(s) {
+                    Ok(s) => Value::Ints(vec![Some(s)]),
+                    Err(_) => Value::Floats(vec![Some(s as f64)]),
+                })
+            }
+            Numbers::Floats(v) => Some(Value::Floats(vec![Some(v.iter().sum())])),
+        }
+    }
+
+    /// return the value that compares as `want` (Less for min, Greater for max) to all others.
+    fn extreme(self, want: Ordering) -> Option<Value> {
+        match self {
+            Numbers::Ints(v) => v
+                .into_iter()
+                .reduce(|a, b| if b.cmp(&a) == want { b } else { a })
+                .map(|i| Value::Ints(vec![Some(i)])),
+            Numbers::Floats(v) => v
+                .into_iter()
+                .reduce(|a, b| if b.total_cmp(&a) == want { b } else { a })
+                .map(|f| Value::Floats(vec![Some(f)])),
+        }
+    }
+}
+
+/// all elements as strings. missing elements are kept as None.
+fn strings(values: &[Value]) -> Vec<Option<String>> {
+    values
+        .iter()
+        .flat_map(|v| match v {
+            Value::Ints(v) => v
+                .iter()
+                .map(|i| i.map(|i| to_string_type(i.to_string())))
+                .collect(),
+            Value::Floats(v) => v
+                .iter()
+                .map(|f| f.map(|f| to_string_type(f.to_string())))
+                .collect(),
+            Value::Strings(v) => v.clone(),
+            Value::Flag(b) => vec![Some(to_string_type(b.to_string()))],
+            Value::Missing => vec![None],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agg(op: Operation) -> Aggregation {
+        Aggregation {
+            source: 0,
+            field: Field::Int(3),
+            op,
+        }
+    }
+
+    #[test]
+    fn test_parse_aggregation() {
+        let a: Aggregation = "1:INFO.AF:max".parse().unwrap();
+        assert_eq!(a.source, 1);
+        assert_eq!(a.field, Field::String(String::from("INFO.AF")));
+        assert_eq!(a.op, Operation::Max);
+        assert_eq!(a.to_string(), "1:INFO.AF:max");
+
+        let a: Aggregation = "0:4:sum".parse().unwrap();
+        assert_eq!(a.field, Field::Int(4));
+
+        assert!("0:4".parse::<Aggregation>().is_err());
+        assert!("0:4:foo".parse::<Aggregation>().is_err());
+        assert!("x:4:sum".parse::<Aggregation>().is_err());
+    }
+
+    #[test]
+    fn test_numeric_ops() {
+        let values = vec![
+            Value::Ints(vec![Some(3)]),
+            Value::Strings(vec![Some(String::from("1"))]),
+            Value::Ints(vec![Some(8), None]),
+        ];
+        let r = |op| agg(op).aggregate(values.clone()).unwrap().unwrap();
+        assert_eq!(r(Operation::Sum), Value::Ints(vec![Some(12)]));
+        assert_eq!(r(Operation::Min), Value::Ints(vec![Some(1)]));
+        assert_eq!(r(Operation::Max), Value::Ints(vec![Some(8)]));
+        assert_eq!(r(Operation::Mean), Value::Floats(vec![Some(4.0)]));
+        assert_eq!(r(Operation::Median), Value::Floats(vec![Some(3.0)]));
+        assert_eq!(r(Operation::First), Value::Ints(vec![Some(3)]));
+        assert_eq!(r(Operation::Last), Value::Ints(vec![Some(8), None]));
+        assert_eq!(r(Operation::Collapse).to_string(), "3,1,8,.");
+
+        let values = vec![Value::Floats(vec![Some(0.5)]), Value::Ints(vec![Some(2)])];
+        let r = agg(Operation::Sum).aggregate(values).unwrap().unwrap();
+        assert_eq!(r, Value::Floats(vec![Some(2.5)]));
+
+        let values = vec![Value::Flag(true), Value::Missing, Value::Flag(true)];
+        let r = agg(Operation::Sum).aggregate(values).unwrap().unwrap();
+        assert_eq!(r, Value::Ints(vec![Some(2)]));
+    }
+
+    #[test]
+    fn test_large_ints() {
+        let big = i64::MAX - 1;
+        let values = vec![
+            Value::Ints(vec![Some(big)]),
+            Value::Ints(vec![Some(big - 1)]),
+        ];
+        let r = |op| agg(op).aggregate(values.clone()).unwrap().unwrap();
+        // compared as integers, these would be equal as f64.
+        assert_eq!(r(Operation::Max), Value::Ints(vec![Some(big)]));
+        assert_eq!(r(Operation::Min), Value::Ints(vec![Some(big - 1)]));
+        assert_eq!(
+            r(Operation::Sum),
+            Value::Floats(vec![Some(2.0 * big as f64)])
+        );
+    }
+
+    #[test]
+    fn test_apply_error_location() {
+        use crate::intersection::Intersection;
+        use noodles::bed;
+        use std::rc::Rc;
+
+        let record = |s: &str| Rc::new(s.parse::<bed::record::Record<3>>().unwrap());
+        let intersections = Intersections {
+            base_interval: record("chr1\t0\t100"),
+            base_line: 1,
+            overlapping: vec![
+                Intersection {
+                    interval: record("chr1\t10\t20\t1"),
+                    id: 0,
+                    line: 1,
+                },
+                Intersection {
+                    interval: record("chr1\t30\t40\tx"),
+                    id: 0,
+                    line: 2,
+                },
+            ],
+        };
+        match agg(Operation::Sum).apply(&intersections, "b.bed") {
+            Err(Error::Field {
+                name,
+                line,
+                region,
+                error: FieldError::TypeMismatch { .. },
+            }) => {
+                assert_eq!(name, "b.bed");
+                assert_eq!(line, 2);
+                assert_eq!(region, "chr1:31-40");
+            }
+            r => panic!("expected a field error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_string_ops() {
+        let values = vec![
+            Value::Strings(vec![Some(String::from("b"))]),
+            Value::Strings(vec![Some(String::from("a"))]),
+            Value::Strings(vec![Some(String::from("b"))]),
+        ];
+        let r = |op| agg(op).aggregate(values.clone()).unwrap().unwrap();
+        assert_eq!(r(Operation::Distinct).to_string(), "a,b");
+        assert_eq!(r(Operation::CountDistinct), Value::Ints(vec![Some(2)]));
+        assert_eq!(r(Operation::Count), Value::Ints(vec![Some(3)]));
+        assert!(matches!(
+            agg(Operation::Sum).aggregate(values),
+            Err(FieldError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_missing() {
+        assert_eq!(agg(Operation::Mean).aggregate(vec![]).unwrap(), None);
+        assert_eq!(agg(Operation::First).aggregate(vec![]).unwrap(), None);
+        assert_eq!(
+            agg(Operation::Count).aggregate(vec![]).unwrap(),
+            Some(Value::Ints(vec![Some(0)]))
+        );
+        // only missing values.
+        assert_eq!(
+            agg(Operation::Max)
+                .aggregate(vec![Value::Ints(vec![None])])
+                .unwrap(),
+            None
+        );
+        // an overlap with no numbers is missing too.
+        let values = vec![Value::Ints(vec![None])];
+        assert_eq!(agg(Operation::Sum).aggregate(values.clone()).unwrap(), None);
+        assert_eq!(agg(Operation::Mean).aggregate(values).unwrap(), None);
+        // a record without the field.
+        let values = vec![Value::Missing, Value::Ints(vec![Some(3)])];
+        assert_eq!(
+            agg(Operation::Max).aggregate(values.clone()).unwrap(),
+            Some(Value::Ints(vec![Some(3)]))
+        );
+        assert_eq!(
+            agg(Operation::First).aggregate(values.clone()).unwrap(),
+            Some(Value::Ints(vec![Some(3)]))
+        );
+        assert_eq!(
+            agg(Operation::Count).aggregate(values).unwrap(),
+            Some(Value::Ints(vec![Some(2)]))
+        );
+        assert_eq!(
+            agg(Operation::Mean)
+                .aggregate(vec![Value::Missing])
+                .unwrap(),
+            None
+        );
+    }
+}
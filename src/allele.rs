@@ -0,0 +1,264 @@
+use crate::bedder_vcf::VcfRecord;
+use crate::error::{Error, Result};
+use crate::intersection::{Intersection, Intersections};
+use crate::position::{Field, FieldError, Positioned, Value};
+use crate::string::{to_string_type, String};
+use noodles::vcf::header::Number;
+use std::result;
+
+/// A single ALT allele of a variant in its minimal representation: bases shared by the end
+/// and then the start of REF and ALT are removed (keeping at least one base of each) and
+/// the position is moved past any removed leading bases. This lets `A>AT` at position 10
+/// match `AC>ATC` at the same position, as happens after splitting multi-allelic records.
+/// Symbolic alleles (e.g. `<DEL>`) and breakends are kept as they are.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Allele {
+    /// 0-based position of the first base of `reference`.
+    pub position: u64,
+    pub reference: String,
+    pub alternate: String,
+}
+
+impl Allele {
+    pub fn new(position: u64, reference: &str, alternate: &str) -> Self {
+        let symbolic = |s: &str| !s.bytes().all(|b| b.is_ascii_alphabetic());
+        if symbolic(reference) || symbolic(alternate) {
+            return Allele {
+                position,
+                reference: String::from(reference),
+                alternate: String::from(alternate),
+            };
+        }
+        let (mut r, mut a) = (reference.as_bytes(), alternate.as_bytes());
+        while r.len() > 1 && a.len() > 1 && r[r.len() - 1].eq_ignore_ascii_case(&a[a.len() - 1]) {
+            r = &r[..r.len() - 1];
+            a = &a[..a.len() - 1];
+        }
+        let mut position = position;
+        while r.len() > 1 && a.len() > 1 && r[0].eq_ignore_ascii_case(&a[0]) {
+            r = &r[1..];
+            a = &a[1..];
+            position += 1;
+        }
+        // the slices are ascii so these are valid utf-8.
+        let upper = |s: &[u8]| {
+            to_string_type(
+                std::str::from_utf8(s)
+                    .unwrap_or_default()
+                    .to_ascii_uppercase(),
+            )
+        };
+        Allele {
+            position,
+            reference: upper(r),
+            alternate: upper(a),
+        }
+    }
+}
+
+/// get the alleles of a variant, one for each ALT, from its `REF` and `ALT` fields.
+/// Missing ALTs (`.`) have no allele but keep their index.
+pub fn alleles<P: Positioned + ?Sized>(p: &P) -> result::Result<Vec<Option<Allele>>, FieldError> {
+    let reference = match p.value(Field::String(String::from("REF")))? {
+        Value::Strings(v) => v.into_iter().next().flatten(),
+        _ => None,
+    };
+    let reference = match reference {
+        Some(r) => r,
+        None => return Ok(Vec::new()),
+    };
+    let alts = match p.value(Field::String(String::from("ALT")))? {
+        Value::Strings(v) => v,
+        _ => Vec::new(),
+    };
+    Ok(alts
+        .into_iter()
+        .map(|alt| match alt {
+            Some(alt) if alt != "." && alt != "*" => Some(Allele::new(p.start(), &reference, &alt)),
+            _ => None,
+        })
+        .collect())
+}
+
+/// An overlapping variant with at least one ALT that matches an ALT of the base variant.
+#[derive(Debug)]
+pub struct AlleleMatch<'a, P: Positioned> {
+    pub intersection: &'a Intersection<P>,
+    /// 0-based indexes of the matching ALTs as (base ALT, overlapping ALT), sorted by base ALT.
+    pub alts: Vec<(usize, usize)>,
+}
+
+impl<'a, P: Positioned> AlleleMatch<'a, P> {
+    /// get a field of the overlapping variant, keeping only the elements of the matching ALTs
+    /// when its header declares one element per ALT (e.g. `INFO.AF`, Number=A) or one for
+    /// REF and each ALT (Number=R). FORMAT fields are reduced sample by sample. Other values
+    /// are returned whole.
+    pub fn value(&self, f: Field) -> result::Result<Value, FieldError> {
+        let interval = self.intersection.interval.as_ref();
+        let record = interval
+            .as_any()
+            .and_then(|a| a.downcast_ref::<VcfRecord>());
+        let number = record.and_then(|r| r.number(&f));
+        let samples = match &f {
+            Field::String(s) if s.starts_with("FORMAT.") => {
+                record.map_or(1, VcfRecord::sample_count)
+            }
+            _ => 1,
+        };
+        let v = interval.value(f)?;
+        let idx: Vec<usize> = match number {
+            Some(Number::A) => self.alts.iter().map(|(_, b)| *b).collect(),
+            Some(Number::R) => self.alts.iter().map(|(_, b)| b + 1).collect(),
+            _ => return Ok(v),
+        };
+        // records with fewer elements than alleles are missing the rest.
+        fn select<T: Clone>(v: Vec<Option<T>>, samples: usize, idx: &[usize]) -> Vec<Option<T>> {
+            if samples <= 1 {
+                return idx.iter().map(|i| v.get(*i).cloned().flatten()).collect();
+            }
+            // FORMAT values are padded so that each sample has the same number of elements.
+            let width = (v.len() / samples).max(1);
+            v.chunks(width)
+                .flat_map(|s| idx.iter().map(|i| s.get(*i).cloned().flatten()))
+                .collect()
+        }
+        Ok(match v {
+            Value::Ints(v) => Value::Ints(select(v, samples, &idx)),
+            Value::Floats(v) => Value::Floats(select(v, samples, &idx)),
+            Value::Strings(v) => Value::Strings(select(v, samples, &idx)),
+            v => v,
+        })
+    }
+}
+
+/// get the overlapping variants that share an allele with the base variant. Overlaps
+/// without a matching allele are left out. All intervals must have `REF` and `ALT` fields
+/// (as VCF and BCF records do). `query_name` and `names`, indexed by source, name the
+/// inputs in errors.
+pub fn allele_matches<'a, P: Positioned, S: AsRef<str>>(
+    intersections: &'a Intersections<P>,
+    query_name: &str,
+    names: &[S],
+) -> Result<Vec<AlleleMatch<'a, P>>> {
+    let base_interval = intersections.base_interval.as_ref();
+    let base = alleles(base_interval)
+        .map_err(|e| Error::field(query_name, intersections.base_line, base_interval, e))?;
+    let mut matches = Vec::new();
+    for o in intersections.overlapping.iter() {
+        let other = alleles(o.interval.as_ref()).map_err(|e| {
+            let name = names.get(o.id as usize).map_or("", |n| n.as_ref());
+            Error::field(name, o.line, o.interval.as_ref(), e)
+        })?;
+        let mut alts = Vec::new();
+        for (i, a) in base.iter().enumerate() {
+            let a = match a {
+                Some(a) => a,
+                None => continue,
+            };
+            for (j, b) in other.iter().enumerate() {
+                if b.as_ref() == Some(a) {
+                    alts.push((i, j));
+                }
+            }
+        }
+        if !alts.is_empty() {
+            matches.push(AlleleMatch {
+                intersection: o,
+                alts,
+            });
+        }
+    }
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bedder_vcf::{vcf_from_str, BedderVCF};
+    use crate::intersection::IntersectionIterator;
+    use std::collections::HashMap;
+
+    const HEADER: &str = "##fileformat=VCFv4.3\n##INFO=<ID=AF,Number=A,Type=Float,Description=\"AF\">\n##INFO=<ID=AD,Number=R,Type=Integer,Description=\"AD\">\n##INFO=<ID=XS,Number=.,Type=Integer,Description=\"XS\">\n#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n";
+
+    fn vcf(records: &str, name: &str) -> BedderVCF<'static> {
+        vcf_from_str(&format!("{}{}", HEADER, records), name)
+    }
+
+    #[test]
+    fn test_minimal_allele() {
+        assert_eq!(Allele::new(9, "AC", "ATC"), Allele::new(9, "A", "AT"));
+        assert_eq!(Allele::new(9, "CAG", "CTG"), Allele::new(10, "A", "T"));
+        assert_eq!(Allele::new(9, "a", "t"), Allele::new(9, "A", "T"));
+        assert_ne!(Allele::new(9, "A", "T"), Allele::new(9, "A", "G"));
+        assert_eq!(Allele::new(9, "A", "<DEL>").alternate, "<DEL>");
+    }
+
+    #[test]
+    fn test_allele_matches() {
+        let a = vcf(
+            "chr1\t10\t.\tA\tT\t.\t.\t.\nchr1\t20\t.\tC\tG,CT\t.\t.\t.\n",
+            "a.vcf",
+        );
+        let b = vcf(
+            "chr1\t10\t.\tA\tG,T\t.\t.\tAF=0.1,0.2;AD=5,6,7;XS=1,2\nchr1\t20\t.\tC\tA\t.\t.\tAF=0.3\nchr1\t20\t.\tCA\tCTA\t.\t.\tAF=0.4\n",
+            "b.vcf",
+        );
+        let order = HashMap::from([(String::from("chr1"), 0)]);
+        let mut it = IntersectionIterator::new(a, vec![b], &order).unwrap();
+
+        let first = it.next().unwrap().unwrap();
+        assert_eq!(first.overlapping.len(), 1);
+        let matches = allele_matches(&first, "a.vcf", &["b.vcf"]).unwrap();
+        assert_eq!(matches.len(), 1);
+        // T is the second ALT of the overlapping variant.
+        assert_eq!(matches[0].alts, vec![(0, 1)]);
+        let af = matches[0]
+            .value(Field::String(String::from("INFO.AF")))
+            .unwrap();
+        assert_eq!(af, Value::Floats(vec![Some(0.2f32 as f64)]));
+        let value = |name: &str| matches[0].value(Field::String(String::from(name))).unwrap();
+        // Number=R has an element for REF before the ALTs.
+        assert_eq!(value("INFO.AD"), Value::Ints(vec![Some(7)]));
+        // Number=. is kept whole even though it has one element per ALT.
+        assert_eq!(value("INFO.XS"), Value::Ints(vec![Some(1), Some(2)]));
+
+        let second = it.next().unwrap().unwrap();
+        assert_eq!(second.overlapping.len(), 2);
+        let matches = allele_matches(&second, "a.vcf", &["b.vcf"]).unwrap();
+        // only the insertion matches (after trimming the shared A).
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].alts, vec![(1, 0)]);
+        assert!(matches[0]
+            .intersection
+            .interval
+            .line()
+            .unwrap()
+            .contains("AF=0.4"));
+    }
+
+    #[test]
+    fn test_allele_match_format() {
+        let a = vcf(
+            "chr1\t10\t.\tA\tT\t.\t.\t.\nchr1\t20\t.\tC\tG\t.\t.\t.\n",
+            "a.vcf",
+        );
+        let data = "##fileformat=VCFv4.3\n##FORMAT=<ID=AD,Number=R,Type=Integer,Description=\"AD\">\n##FORMAT=<ID=DP,Number=1,Type=Integer,Description=\"DP\">\n#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\ts1\ts2\nchr1\t10\t.\tA\tG,T\t.\t.\t.\tAD:DP\t5,6,7:18\t1,2,3:6\nchr1\t20\t.\tC\tA,G\t.\t.\t.\tAD:DP\t.:4\t.:5\n";
+        let b = vcf_from_str(data, "b.vcf");
+        let order = HashMap::from([(String::from("chr1"), 0)]);
+        let mut it = IntersectionIterator::new(a, vec![b], &order).unwrap();
+
+        let intersection = it.next().unwrap().unwrap();
+        let matches = allele_matches(&intersection, "a.vcf", &["b.vcf"]).unwrap();
+        let value = |name: &str| matches[0].value(Field::String(String::from(name))).unwrap();
+        // the T element of each sample.
+        assert_eq!(value("FORMAT.AD"), Value::Ints(vec![Some(7), Some(3)]));
+        assert_eq!(value("FORMAT.DP"), Value::Ints(vec![Some(18), Some(6)]));
+
+        // both samples have fewer AD elements (one) than alleles (three), but each still
+        // gets its own, missing, element for G.
+        let intersection = it.next().unwrap().unwrap();
+        let matches = allele_matches(&intersection, "a.vcf", &["b.vcf"]).unwrap();
+        let value = |name: &str| matches[0].value(Field::String(String::from(name))).unwrap();
+        assert_eq!(value("FORMAT.AD"), Value::Ints(vec![None, None]));
+    }
+}
@@ -0,0 +1,453 @@
+use crate::aggregate::{Aggregation, Operation};
+use crate::allele;
+use crate::error::{Error, Result};
+use crate::intersection::Intersections;
+use crate::position::{Field, Positioned, Value};
+use crate::string::{to_string_type, String};
+use noodles::vcf::{
+    self,
+    header::{
+        record::value::{
+            map::{info::Type, Info},
+            Map,
+        },
+        Number,
+    },
+    record::info::field::Key,
+};
+use std::fmt::Write;
+use std::io::BufRead;
+use std::result;
+use std::str::FromStr;
+
+/// An Annotation adds an INFO field named `key` to each query variant from the summary of
+/// overlapping intervals given by `aggregation`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub aggregation: Aggregation,
+    pub key: String,
+    /// only use overlapping variants that share an allele with the query variant.
+    pub match_alleles: bool,
+    /// the description of the INFO header line. a default is used if this is None.
+    pub description: Option<String>,
+}
+
+/// Parse an annotation from a config line with whitespace-separated columns:
+/// `source:field:op  KEY  [overlap|alleles]  [description]`, e.g.
+/// `0:INFO.AF:max  gnomad_AF  alleles  AF from gnomAD`. The description is the rest of the line.
+impl FromStr for Annotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        let mut fields = s.trim().splitn(2, char::is_whitespace);
+        let aggregation = fields.next().unwrap_or_default().parse::<Aggregation>()?;
+        let rest = fields.next().unwrap_or_default().trim_start();
+        let mut fields = rest.splitn(2, char::is_whitespace);
+        let key = fields.next().unwrap_or_default();
+        if key.is_empty() {
+            return Err(to_string_type(format!("missing INFO key in: {}", s)));
+        }
+        if key.parse::<Key>().is_err() {
+            return Err(to_string_type(format!("invalid INFO key: {}", key)));
+        }
+        let rest = fields.next().unwrap_or_default().trim_start();
+        let mut fields = rest.splitn(2, char::is_whitespace);
+        let match_alleles = match fields.next().unwrap_or_default() {
+            "" | "overlap" => false,
+            "alleles" => true,
+            m => {
+                return Err(to_string_type(format!(
+                    "unknown match type: {}. expected overlap or alleles",
+                    m
+                )))
+            }
+        };
+        let description = fields.next().map(str::trim).filter(|d| !d.is_empty());
+        Ok(Annotation {
+            aggregation,
+            key: String::from(key),
+            match_alleles,
+            description: description.map(String::from),
+        })
+    }
+}
+
+/// read annotations from a config file with one annotation per line (see `Annotation`).
+/// Blank lines and lines starting with `#` are skipped. Annotations must use one of the
+/// `n_sources` overlapping inputs. `name` is used in error messages.
+pub fn read_config<R: BufRead>(reader: R, name: &str, n_sources: usize) -> Result<Vec<Annotation>> {
+    let mut annotations = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parse_error = |message| Error::Parse {
+            name: String::from(name),
+            line: i as u64 + 1,
+            region: None,
+            message,
+        };
+        let a = line.parse::<Annotation>().map_err(parse_error)?;
+        if a.aggregation.source as usize >= n_sources {
+            return Err(parse_error(to_string_type(format!(
+                "annotation {} refers to a missing -b file",
+                a.aggregation
+            ))));
+        }
+        annotations.push(a);
+    }
+    Ok(annotations)
+}
+
+/// A source of overlapping intervals: its name (e.g. the path) for INFO descriptions and
+/// its header if it is a VCF, which is used to choose the Number and Type of new fields.
+pub struct Source {
+    pub name: String,
+    pub header: Option<vcf::Header>,
+}
+
+/// VcfAnnotator adds INFO fields to query VCF records from the intervals that overlap them.
+pub struct VcfAnnotator {
+    annotations: Vec<Annotation>,
+    header: vcf::Header,
+    // the type of each new field.
+    types: Vec<Type>,
+    // the names of the query and sources, used in error messages.
+    query_name: String,
+    names: Vec<String>,
+}
+
+impl VcfAnnotator {
+    /// add INFO definitions for `annotations` to the query `header`. `sources` are the
+    /// overlapping inputs in the order used by `Aggregation::source`. `query_name` (e.g. the
+    /// path of the query) is used in error messages.
+    pub fn new(
+        mut header: vcf::Header,
+        annotations: Vec<Annotation>,
+        sources: &[Source],
+        query_name: &str,
+    ) -> Result<Self> {
+        let mut types = Vec::with_capacity(annotations.len());
+        for a in annotations.iter() {
+            let source =
+                sources
+                    .get(a.aggregation.source as usize)
+                    .ok_or_else(|| Error::Parse {
+                        name: a.key.clone(),
+                        line: 0,
+                        region: None,
+                        message: to_string_type(format!(
+                            "annotation {} refers to a missing -b file",
+                            a.aggregation
+                        )),
+                    })?;
+            let (number, ty) = info_definition(a, source);
+            let description = match &a.description {
+                Some(d) => d.clone(),
+                None => to_string_type(format!(
+                    "{} of {} from {}",
+                    a.aggregation.op, a.aggregation.field, source.name
+                )),
+            };
+            // the key was validated when the annotation was parsed.
+            let key = a.key.parse::<Key>().expect("valid INFO key");
+            header
+                .infos_mut()
+                .insert(key, Map::<Info>::new(number, ty, description));
+            types.push(ty);
+        }
+        Ok(VcfAnnotator {
+            annotations,
+            header,
+            types,
+            query_name: String::from(query_name),
+            names: sources.iter().map(|s| s.name.clone()).collect(),
+        })
+    }
+
+    /// the query header with the new INFO definitions.
+    pub fn header(&self) -> &vcf::Header {
+        &self.header
+    }
+
+    /// write the query record of `intersections` to `line` with the new INFO fields, replacing
+    /// any existing fields with the same keys. The query record must have a `line`
+    /// (see `BedderVCF::with_lines`).
+    pub fn annotate<P: Positioned>(
+        &self,
+        intersections: &Intersections<P>,
+        line: &mut std::string::String,
+    ) -> Result<()> {
+        let base = intersections.base_interval.as_ref();
+        let parse_error =
+            |message| Error::parse(&self.query_name, intersections.base_line, base, message);
+        let text = base.line().ok_or_else(|| {
+            parse_error(String::from(
+                "annotation requires the text of each query record",
+            ))
+        })?;
+        let mut columns = text.splitn(9, '\t');
+        let mut info = None;
+        line.clear();
+        for i in 0..8 {
+            let c = columns.next().ok_or_else(|| {
+                parse_error(to_string_type(format!(
+                    "expected at least 8 columns in: {}",
+                    text
+                )))
+            })?;
+            if i == 7 {
+                info = Some(c);
+            } else {
+                line.push_str(c);
+                line.push('\t');
+            }
+        }
+        let info = info.unwrap_or(".");
+
+        // keep the existing fields except those we replace.
+        let mut n = 0;
+        for field in info.split(';').filter(|f| *f != "." && !f.is_empty()) {
+            let key = field.split('=').next().unwrap_or_default();
+            if self.annotations.iter().any(|a| a.key == key) {
+                continue;
+            }
+            if n > 0 {
+                line.push(';');
+            }
+            line.push_str(field);
+            n += 1;
+        }
+
+        let allele_matches = if self.annotations.iter().any(|a| a.match_alleles) {
+            Some(allele::allele_matches(
+                intersections,
+                &self.query_name,
+                &self.names,
+            )?)
+        } else {
+            None
+        };
+        for (a, ty) in self.annotations.iter().zip(self.types.iter()) {
+            let name = &self.names[a.aggregation.source as usize];
+            let value = match (&allele_matches, a.match_alleles) {
+                (Some(matches), true) => a.aggregation.apply_allele_matches(matches, name)?,
+                _ => a.aggregation.apply(intersections, name)?,
+            };
+            let value = match value {
+                None | Some(Value::Missing) | Some(Value::Flag(false)) => continue,
+                Some(v) => v,
+            };
+            if n > 0 {
+                line.push(';');
+            }
+            n += 1;
+            line.push_str(&a.key);
+            if *ty == Type::Flag {
+                continue;
+            }
+            line.push('=');
+            write_info_value(line, &value);
+        }
+        if n == 0 {
+            line.push('.');
+        }
+        if let Some(rest) = columns.next() {
+            line.push('\t');
+            line.push_str(rest);
+        }
+        Ok(())
+    }
+}
+
+/// the Number and Type of the field summarized by `a` if known from the source header.
+fn source_definition(a: &Annotation, source: &Source) -> Option<(Number, Type)> {
+    match &a.aggregation.field {
+        Field::Int(1) | Field::Int(2) => Some((Number::Count(1), Type::Integer)),
+        Field::Int(_) => None,
+        Field::String(s) => match s.as_str() {
+            "start" | "stop" => Some((Number::Count(1), Type::Integer)),
+            "QUAL" => Some((Number::Count(1), Type::Float)),
+            "chrom" | "REF" => Some((Number::Count(1), Type::String)),
+            "ID" | "FILTER" | "ALT" => Some((Number::Unknown, Type::String)),
+            s => {
+                let key = s.strip_prefix("INFO.")?.parse::<Key>().ok()?;
+                let info = source.header.as_ref()?.infos().get(&key)?;
+                Some((info.number(), info.ty()))
+            }
+        },
+    }
+}
+
+/// choose the Number and Type of the INFO field added by `a`.
+fn info_definition(a: &Annotation, source: &Source) -> (Number, Type) {
+    let def = source_definition(a, source);
+    let ty = def.map(|(_, ty)| ty);
+    match a.aggregation.op {
+        Operation::Count | Operation::CountDistinct => (Number::Count(1), Type::Integer),
+        Operation::Sum | Operation::Min | Operation::Max => match ty {
+            Some(Type::Integer) | Some(Type::Flag) => (Number::Count(1), Type::Integer),
+            _ => (Number::Count(1), Type::Float),
+        },
+        Operation::Mean | Operation::Median => (Number::Count(1), Type::Float),
+        Operation::First | Operation::Last => match def {
+            Some((_, Type::Flag)) => (Number::Count(0), Type::Flag),
+            Some((Number::Count(1), ty)) => (Number::Count(1), ty),
+            // per-allele values may match only some of the query's alleles.
+            Some((_, ty)) => (Number::Unknown, ty),
+            None => (Number::Unknown, Type::String),
+        },
+        Operation::Collapse | Operation::Distinct => match ty {
+            Some(Type::Flag) | None => (Number::Unknown, Type::String),
+            Some(ty) => (Number::Unknown, ty),
+        },
+    }
+}
+
+/// write `v` as an INFO value. Floats are written with single precision as VCF floats are
+/// 32-bit, and characters that are reserved in INFO values are percent-encoded. Spaces are
+/// allowed in INFO values since VCF 4.3 and are kept.
+fn write_info_value(line: &mut std::string::String, v: &Value) {
+    fn join<T, F: Fn(&mut std::string::String, &T)>(
+        line: &mut std::string::String,
+        vals: &[Option<T>],
+        f: F,
+    ) {
+        for (i, v) in vals.iter().enumerate() {
+            if i > 0 {
+                line.push(',');
+            }
+            match v {
+                Some(v) => f(line, v),
+                None => line.push('.'),
+            }
+        }
+    }
+    match v {
+        Value::Ints(v) => join(line, v, |l, i| {
+            let _ = write!(l, "{}", i);
+        }),
+        Value::Floats(v) => join(line, v, |l, f| {
+            let _ = write!(l, "{}", *f as f32);
+        }),
+        Value::Strings(v) => join(line, v, |l, s| {
+            for c in s.chars() {
+                match c {
+                    ';' | '=' | '%' | ',' | '\t' | '\n' | '\r' => {
+                        let _ = write!(l, "%{:02X}", c as u32);
+                    }
+                    c => l.push(c),
+                }
+            }
+        }),
+        Value::Flag(b) => {
+            let _ = write!(line, "{}", *b as u8);
+        }
+        Value::Missing => line.push('.'),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bedder_bed::BedderBed;
+    use crate::bedder_vcf::vcf_from_str;
+    use crate::intersection::IntersectionIterator;
+    use crate::position::{BoxPositioned, DynPositionedIterator};
+    use std::collections::HashMap;
+    use std::io::Cursor;
+
+    const HEADER: &str = "##fileformat=VCFv4.3\n##INFO=<ID=AF,Number=A,Type=Float,Description=\"AF\">\n##INFO=<ID=DP,Number=1,Type=Integer,Description=\"depth\">\n##contig=<ID=chr1>\n#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n";
+
+    #[test]
+    fn test_parse_annotation() {
+        let a: Annotation = "0:INFO.AF:max\tgnomad_AF\talleles\tAF from gnomAD"
+            .parse()
+            .unwrap();
+        assert_eq!(a.key, "gnomad_AF");
+        assert!(a.match_alleles);
+        assert_eq!(a.description.as_deref(), Some("AF from gnomAD"));
+        let a: Annotation = "1:3:collapse genes".parse().unwrap();
+        assert!(!a.match_alleles);
+        assert_eq!(a.description, None);
+        assert!("1:3:collapse".parse::<Annotation>().is_err());
+        assert!("1:3:collapse genes exact".parse::<Annotation>().is_err());
+        assert!("1:3:nope genes".parse::<Annotation>().is_err());
+
+        let config = "# comment\n\n0:INFO.DP:sum\tdp\n1:3:foo\tx\n";
+        let r = read_config(Cursor::new(config), "c.txt", 2);
+        assert!(matches!(r, Err(Error::Parse { line: 4, .. })));
+        // the second annotation uses a -b file that was not given.
+        let config = "0:INFO.DP:sum\tdp\n1:3:collapse\tx\n";
+        match read_config(Cursor::new(config), "c.txt", 1) {
+            Err(Error::Parse { name, line, .. }) => {
+                assert_eq!(name, "c.txt");
+                assert_eq!(line, 2);
+            }
+            r => panic!("expected parse error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_annotate() {
+        // the query has a sample column which must be kept.
+        let data = HEADER.replace("\n#CHROM", "\n##FORMAT=<ID=GT,Number=1,Type=String,Description=\"GT\">\n#CHROM")
+            .replace("INFO\n", "INFO\tFORMAT\ts1\n")
+            + "chr1\t10\t.\tA\tT\t.\t.\tDP=3\tGT\t0/1\nchr1\t20\t.\tC\tG\t.\t.\t.\tGT\t0/1\nchr1\t90\t.\tC\tG\t.\t.\tDP=1\tGT\t1/1\n";
+        let query = vcf_from_str(&data, "q.vcf");
+        let header = query.header().clone();
+        let db = vcf_from_str(
+            &(HEADER.to_string()
+                + "chr1\t10\t.\tA\tG,T\t.\t.\tAF=0.1,0.25;DP=7\nchr1\t20\t.\tC\tA\t.\t.\tAF=0.5;DP=4\n"),
+            "db.vcf",
+        );
+        let bed = BedderBed::new(
+            Cursor::new("chr1\t0\t15\tgene;1\nchr1\t5\t25\tgene 2\n"),
+            "genes.bed",
+        );
+        let sources = vec![
+            Source {
+                name: String::from("db.vcf"),
+                header: Some(db.header().clone()),
+            },
+            Source {
+                name: String::from("genes.bed"),
+                header: None,
+            },
+        ];
+        let config = "0:INFO.AF:first\tdb_AF\talleles\n0:INFO.DP:max\tdb_DP\n1:3:collapse\tgenes\n1:3:count\tDP\n";
+        let annotations = read_config(Cursor::new(config), "c.txt", sources.len()).unwrap();
+        let annotator = VcfAnnotator::new(header, annotations, &sources, "q.vcf").unwrap();
+
+        let infos = annotator.header().infos();
+        let def = |k: &str| infos.get(&k.parse::<Key>().unwrap()).unwrap();
+        assert_eq!(def("db_AF").number(), Number::Unknown);
+        assert_eq!(def("db_AF").ty(), Type::Float);
+        assert_eq!(def("db_DP").ty(), Type::Integer);
+        assert_eq!(def("genes").ty(), Type::String);
+        assert_eq!(def("DP").ty(), Type::Integer);
+
+        let order = HashMap::from([(String::from("chr1"), 0)]);
+        let query: DynPositionedIterator = Box::new(BoxPositioned::new(query));
+        let db: DynPositionedIterator = Box::new(BoxPositioned::new(db));
+        let bed: DynPositionedIterator = Box::new(BoxPositioned::new(bed));
+        let it = IntersectionIterator::new(query, vec![db, bed], &order).unwrap();
+        let mut line = std::string::String::new();
+        let lines: Vec<_> = it
+            .map(|i| {
+                annotator.annotate(&i.unwrap(), &mut line).unwrap();
+                line.clone()
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                // the existing DP is replaced by the count of genes.
+                "chr1\t10\t.\tA\tT\t.\t.\tdb_AF=0.25;db_DP=7;genes=gene%3B1,gene 2;DP=2\tGT\t0/1",
+                "chr1\t20\t.\tC\tG\t.\t.\tdb_DP=4;genes=gene 2;DP=1\tGT\t0/1",
+                "chr1\t90\t.\tC\tG\t.\t.\tDP=0\tGT\t1/1",
+            ]
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use crate::error::Error;
+use crate::position::{Field, FieldError, Positioned, Value};
+use crate::string::{to_string_type, String};
+use noodles::{bam, bgzf, sam};
+use std::any::Any;
+use std::io::{self, BufRead, Read};
+use std::path::Path;
+use std::result;
+
+pub trait AlignmentReader {
+    fn read_record(
+        &mut self,
+        header: &sam::Header,
+        record: &mut sam::alignment::Record,
+    ) -> io::Result<usize>;
+}
+
+impl<R> AlignmentReader for sam::Reader<R>
+where
+    R: BufRead,
+{
+    #[inline]
+    fn read_record(
+        &mut self,
+        header: &sam::Header,
+        record: &mut sam::alignment::Record,
+    ) -> io::Result<usize> {
+        self.read_record(header, record)
+    }
+}
+
+impl<R> AlignmentReader for bam::Reader<bgzf::Reader<R>>
+where
+    R: Read,
+{
+    #[inline]
+    fn read_record(
+        &mut self,
+        header: &sam::Header,
+        record: &mut sam::alignment::Record,
+    ) -> io::Result<usize> {
+        self.read_record(header, record)
+    }
+}
+
+/// A mapped read from a SAM or BAM file, spanning its alignment on the reference.
+#[derive(Debug, Default)]
+pub struct AlignmentRecord {
+    pub record: sam::alignment::Record,
+    chrom: String,
+    start: u64,
+    stop: u64,
+}
+
+impl Positioned for AlignmentRecord {
+    #[inline]
+    fn chrom(&self) -> &str {
+        &self.chrom
+    }
+
+    #[inline]
+    fn start(&self) -> u64 {
+        self.start
+    }
+
+    #[inline]
+    fn stop(&self) -> u64 {
+        self.stop
+    }
+
+    /// columns 0, 1 and 2 are the chromosome, start and stop as for BED. `qname`, `flag`
+    /// and `mapq` give the read name, the flags and the mapping quality.
+    fn value(&self, f: Field) -> result::Result<Value, FieldError> {
+        match f {
+            Field::Int(0) => Ok(Value::Strings(vec![Some(self.chrom.clone())])),
+            Field::Int(1) => Ok(Value::Ints(vec![Some(self.start as i64)])),
+            Field::Int(2) => Ok(Value::Ints(vec![Some(self.stop as i64)])),
+            Field::Int(i) => Err(FieldError::InvalidFieldIndex(i)),
+            Field::String(s) => match s.as_str() {
+                "qname" => Ok(Value::Strings(vec![self
+                    .record
+                    .read_name()
+                    .map(|n| String::from(AsRef::<str>::as_ref(n)))])),
+                "flag" => Ok(Value::Ints(vec![Some(
+                    u16::from(self.record.flags()) as i64
+                )])),
+                "mapq" => Ok(Value::Ints(vec![self
+                    .record
+                    .mapping_quality()
+                    .map(|q| u8::from(q) as i64)])),
+                _ => Err(FieldError::InvalidFieldName(s)),
+            },
+        }
+    }
+
+    fn into_any(self: Box<Self>) -> Option<Box<dyn Any>> {
+        Some(self)
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+/// at most this many records are kept for reuse.
+const MAX_FREE: usize = 1024;
+
+/// Alignments of a SAM or BAM file. Unmapped reads are skipped.
+pub struct BedderAlignments<'a> {
+    reader: Box<dyn AlignmentReader + 'a>,
+    header: sam::Header,
+    record_number: u64,
+    path: String,
+    // records returned via `recycle` that are reused to avoid allocation.
+    free: Vec<AlignmentRecord>,
+}
+
+impl<'a> BedderAlignments<'a> {
+    /// Create a new BedderAlignments. `path` is used only to name the iterator in messages.
+    pub fn new<P: AsRef<Path>>(
+        r: Box<dyn AlignmentReader + 'a>,
+        header: sam::Header,
+        path: P,
+    ) -> BedderAlignments<'a> {
+        BedderAlignments {
+            reader: r,
+            header,
+            record_number: 0,
+            path: String::from(&*path.as_ref().to_string_lossy()),
+            free: Vec::new(),
+        }
+    }
+
+    /// the header of the SAM or BAM file.
+    pub fn header(&self) -> &sam::Header {
+        &self.header
+    }
+
+    fn parse_error(&self, message: &str) -> Error {
+        Error::Parse {
+            name: self.path.clone(),
+            line: self.record_number,
+            region: None,
+            message: String::from(message),
+        }
+    }
+
+    /// set the chromosome, start and stop of a mapped record. Returns false if it is unmapped.
+    fn locate(&self, r: &mut AlignmentRecord) -> crate::error::Result<bool> {
+        if r.record.flags().is_unmapped() {
+            return Ok(false);
+        }
+        let start = match r.record.alignment_start() {
+            Some(p) => usize::from(p) as u64 - 1,
+            None => return Ok(false),
+        };
+        match r.record.reference_sequence(&self.header) {
+            Some(Ok((name, _))) => {
+                if r.chrom.as_str() != &name[..] {
+                    r.chrom = String::from(&name[..]);
+                }
+            }
+            Some(Err(e)) => return Err(self.parse_error(&e.to_string())),
+            None => return Ok(false),
+        }
+        r.start = start;
+        r.stop = start + r.record.alignment_span() as u64;
+        Ok(true)
+    }
+}
+
+impl<'a> crate::position::PositionedIterator for BedderAlignments<'a> {
+    type Item = AlignmentRecord;
+
+    fn next_position(
+        &mut self,
+        _q: Option<&dyn crate::position::Positioned>,
+    ) -> Option<crate::error::Result<Self::Item>> {
+        let mut r = self.free.pop().unwrap_or_default();
+        loop {
+            match self.reader.read_record(&self.header, &mut r.record) {
+                Ok(0) => return None, // EOF
+                Ok(_) => {
+                    self.record_number += 1;
+                    match self.locate(&mut r) {
+                        Ok(true) => return Some(Ok(r)),
+                        Ok(false) => continue,
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+                // noodles reports malformed records as InvalidData.
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                    self.record_number += 1;
+                    return Some(Err(self.parse_error(&e.to_string())));
+                }
+                Err(e) => return Some(Err(Error::Io(e))),
+            }
+        }
+    }
+
+    fn recycle(&mut self, p: Self::Item) {
+        if self.free.len() < MAX_FREE {
+            self.free.push(p);
+        }
+    }
+
+    fn name(&self) -> String {
+        to_string_type(format!("{}:{}", self.path, self.record_number))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::PositionedIterator;
+
+    fn alignments(text: &str) -> BedderAlignments<'static> {
+        let mut reader = sam::Reader::new(io::Cursor::new(text.to_string()));
+        let header = reader.read_header().unwrap();
+        BedderAlignments::new(Box::new(reader), header, "t.sam")
+    }
+
+    #[test]
+    fn test_read_alignments() {
+        let mut it = alignments(
+            "@SQ\tSN:chr1\tLN:1000\n\
+             r1\t0\tchr1\t11\t60\t5M2D3M\t*\t0\t0\tACGTACGT\t*\n\
+             r2\t4\t*\t0\t0\t*\t*\t0\t0\tACGT\t*\n\
+             r3\t16\tchr1\t21\t30\t4M\t*\t0\t0\tACGT\t*\n",
+        );
+        let r = it.next_position(None).unwrap().unwrap();
+        assert_eq!((r.chrom(), r.start(), r.stop()), ("chr1", 10, 20));
+        assert_eq!(
+            r.value(Field::String(String::from("qname"))).unwrap(),
+            Value::Strings(vec![Some(String::from("r1"))])
+        );
+        assert_eq!(
+            r.value(Field::String(String::from("mapq"))).unwrap(),
+            Value::Ints(vec![Some(60)])
+        );
+        it.recycle(r);
+        // the unmapped read is skipped.
+        let r = it.next_position(None).unwrap().unwrap();
+        assert_eq!((r.start(), r.stop()), (20, 24));
+        assert_eq!(
+            r.value(Field::String(String::from("flag"))).unwrap(),
+            Value::Ints(vec![Some(16)])
+        );
+        assert_eq!(it.name(), "t.sam:3");
+        assert!(it.next_position(None).is_none());
+    }
+}
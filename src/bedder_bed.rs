@@ -1,7 +1,11 @@
+use crate::error::Error;
 use crate::position::{Field, FieldError, Positioned, Value};
-use crate::string::String;
+use crate::string::{to_string_type, String};
 pub use noodles::bed;
-use std::io::{self, BufRead};
+use noodles::{bgzf, core::Position, csi};
+use std::any::Any;
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
+use std::path::Path;
 use std::result;
 
 impl crate::position::Positioned for bed::record::Record<3> {
@@ -23,44 +27,302 @@ impl crate::position::Positioned for bed::record::Record<3> {
 
     fn value(&self, v: crate::position::Field) -> result::Result<Value, FieldError> {
         match v {
-            Field::String(s) => Ok(Value::Strings(vec![s])),
+            Field::String(s) => Ok(Value::Strings(vec![Some(s)])),
             Field::Int(i) => match i {
-                0 => Ok(Value::Strings(vec![String::from(self.chrom())])),
-                1 => Ok(Value::Ints(vec![self.start() as i64])),
-                2 => Ok(Value::Ints(vec![self.stop() as i64])),
-                _ => Err(FieldError::InvalidFieldIndex(i)),
+                0 => Ok(Value::Strings(vec![Some(String::from(self.chrom()))])),
+                1 => Ok(Value::Ints(vec![Some(self.start() as i64)])),
+                2 => Ok(Value::Ints(vec![Some(self.stop() as i64)])),
+                // columns after the first 3 are kept as strings with "." as missing.
+                _ => self
+                    .optional_fields()
+                    .get(i - 3)
+                    .map(|s| match s.as_str() {
+                        "." => Value::Strings(vec![None]),
+                        s => Value::Strings(vec![Some(String::from(s))]),
+                    })
+                    .ok_or(FieldError::InvalidFieldIndex(i)),
             },
         }
     }
 }
 
+/// A BED record along with the original line from which it was parsed.
+/// Only the chromosome, start and stop are parsed; other columns are split from the line on demand.
+/// The line buffer is reused when the record is recycled by the reader.
+#[derive(Debug, Default)]
+pub struct BedRecord {
+    line: std::string::String,
+    chrom_end: usize,
+    start: u64,
+    stop: u64,
+}
+
+impl BedRecord {
+    /// Create a BedRecord from a line of text (without the newline).
+    pub fn from_line(line: &str) -> result::Result<BedRecord, std::string::String> {
+        let mut r = BedRecord {
+            line: std::string::String::from(line),
+            ..Default::default()
+        };
+        r.parse()?;
+        Ok(r)
+    }
+
+    /// parse chrom, start and stop from `self.line`.
+    fn parse(&mut self) -> result::Result<(), std::string::String> {
+        let mut fields = self.line.splitn(4, '\t');
+        let chrom = fields.next().unwrap_or_default();
+        self.chrom_end = chrom.len();
+        self.start = fields
+            .next()
+            .ok_or("missing start")?
+            .parse()
+            .map_err(|e| format!("invalid start: {}", e))?;
+        self.stop = fields
+            .next()
+            .ok_or("missing end")?
+            .parse()
+            .map_err(|e| format!("invalid end: {}", e))?;
+        if self.start > self.stop {
+            return Err(format!("start {} is after end {}", self.start, self.stop));
+        }
+        Ok(())
+    }
+}
+
+impl Positioned for BedRecord {
+    #[inline]
+    fn chrom(&self) -> &str {
+        &self.line[..self.chrom_end]
+    }
+
+    #[inline]
+    fn start(&self) -> u64 {
+        self.start
+    }
+
+    #[inline]
+    fn stop(&self) -> u64 {
+        self.stop
+    }
+
+    fn value(&self, v: crate::position::Field) -> result::Result<Value, FieldError> {
+        match v {
+            Field::String(s) => Ok(Value::Strings(vec![Some(s)])),
+            Field::Int(i) => match i {
+                0 => Ok(Value::Strings(vec![Some(String::from(self.chrom()))])),
+                1 => Ok(Value::Ints(vec![Some(self.start as i64)])),
+                2 => Ok(Value::Ints(vec![Some(self.stop as i64)])),
+                // columns after the first 3 are kept as strings with "." as missing.
+                _ => self
+                    .line
+                    .split('\t')
+                    .nth(i)
+                    .map(|s| match s {
+                        "." => Value::Strings(vec![None]),
+                        s => Value::Strings(vec![Some(String::from(s))]),
+                    })
+                    .ok_or(FieldError::InvalidFieldIndex(i)),
+            },
+        }
+    }
+
+    fn line(&self) -> Option<&str> {
+        Some(&self.line)
+    }
+
+    fn into_any(self: Box<Self>) -> Option<Box<dyn Any>> {
+        Some(self)
+    }
+}
+
 struct Last {
     chrom: String,
     start: u64,
     stop: u64,
 }
 
+/// at most this many records are kept for reuse.
+const MAX_FREE: usize = 1024;
+
+/// seekable files only seek when at least this many bytes remain.
+const SEEK_MIN_BYTES: u64 = 1 << 18;
+/// bisection stops when the search window is smaller than this; the rest is read.
+const SEEK_BLOCK: u64 = 1 << 14;
+
+/// skips ahead in a seekable reader; see `BedderBed::seekable`.
+type SeekFn<R> = fn(&mut BedderBed<R>, &dyn Positioned) -> io::Result<()>;
+
 pub struct BedderBed<R>
 where
     R: BufRead,
 {
     reader: bed::Reader<R>,
-    buf: std::string::String,
     last_record: Option<Last>,
     line_number: u64,
+    path: String,
+    // bytes read so far (or the offset after seeking). for indexed files, this is the
+    // compressed offset of the block we last seeked to.
+    offset: u64,
+    // the length of the longest interval in the file, as given to `seekable`.
+    max_len: u64,
+    // set only for seekable readers so that `next_position` does not need R: Seek.
+    seek: Option<SeekFn<R>>,
+    file_len: u64,
+    // line numbers are unknown once we have skipped part of the file.
+    seeked: bool,
+    // the tabix or CSI index of a bgzipped file; see `BedderBed::indexed`.
+    index: Option<Box<csi::Index>>,
+    // records returned via `recycle` that are reused to avoid allocation.
+    free: Vec<BedRecord>,
 }
 
 impl<R> BedderBed<R>
 where
     R: BufRead,
 {
-    pub fn new(r: R) -> BedderBed<R> {
+    /// Create a new BedderBed. `path` is used only to name the iterator in messages.
+    pub fn new<P: AsRef<Path>>(r: R, path: P) -> BedderBed<R> {
         BedderBed {
             reader: bed::Reader::new(r),
-            buf: std::string::String::new(),
             last_record: None,
             line_number: 0,
+            path: String::from(&*path.as_ref().to_string_lossy()),
+            offset: 0,
+            max_len: 0,
+            seek: None,
+            file_len: 0,
+            seeked: false,
+            index: None,
+            free: Vec::new(),
+        }
+    }
+}
+
+impl<R> BedderBed<R>
+where
+    R: BufRead + Seek,
+{
+    /// Create a BedderBed for a sorted, uncompressed file that skips ahead by bisection
+    /// when a query is far past the current position, rather than reading every line.
+    /// To avoid missing intervals that overlap the query, it resumes `max_len` before the
+    /// query, so `max_len` must be at least the length of the longest interval in the file.
+    pub fn seekable<P: AsRef<Path>>(mut r: R, path: P, max_len: u64) -> io::Result<BedderBed<R>> {
+        let offset = r.stream_position()?;
+        let file_len = r.seek(SeekFrom::End(0))?;
+        r.seek(SeekFrom::Start(offset))?;
+        let mut b = BedderBed::new(r, path);
+        b.offset = offset;
+        b.file_len = file_len;
+        b.max_len = max_len;
+        b.seek = Some(Self::seek_to);
+        Ok(b)
+    }
+
+    /// move the reader to shortly before the first line on the same chromosome as the
+    /// last record that could overlap `q`. nothing is done if `q` is on another chromosome
+    /// or is not far enough ahead.
+    fn seek_to(&mut self, q: &dyn Positioned) -> io::Result<()> {
+        let last_start = match &self.last_record {
+            Some(last) if last.chrom == q.chrom() => last.start,
+            _ => return Ok(()),
+        };
+        let target = q.start().saturating_sub(self.max_len);
+        if target <= last_start || self.file_len.saturating_sub(self.offset) < SEEK_MIN_BYTES {
+            return Ok(());
+        }
+
+        // lines after lo start before target; the first line at or after hi does not.
+        let (mut lo, mut hi) = (self.offset, self.file_len);
+        let mut buf = Vec::new();
+        while hi - lo > SEEK_BLOCK {
+            let mid = lo + (hi - lo) / 2;
+            let reader = self.reader.get_mut();
+            reader.seek(SeekFrom::Start(mid))?;
+            // skip the (likely partial) line at mid.
+            reader.read_until(b'\n', &mut buf)?;
+            let before = loop {
+                buf.clear();
+                if reader.read_until(b'\n', &mut buf)? == 0 {
+                    break false; // EOF
+                }
+                if buf.starts_with(b"#") || buf.iter().all(|b| b.is_ascii_whitespace()) {
+                    continue;
+                }
+                let mut fields = buf.splitn(3, |&b| b == b'\t');
+                let chrom = fields.next().unwrap_or_default();
+                let start = fields
+                    .next()
+                    .and_then(|s| std::str::from_utf8(s).ok())
+                    .and_then(|s| s.parse::<u64>().ok());
+                // unparseable lines are left for the normal reader to report.
+                break chrom == q.chrom().as_bytes() && start.is_some_and(|s| s < target);
+            };
+            if before {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+
+        let reader = self.reader.get_mut();
+        reader.seek(SeekFrom::Start(lo))?;
+        if lo != self.offset {
+            buf.clear();
+            self.offset = lo + reader.read_until(b'\n', &mut buf)? as u64;
+            self.seeked = true;
         }
+        Ok(())
+    }
+}
+
+impl<F> BedderBed<bgzf::Reader<F>>
+where
+    F: Read + Seek,
+{
+    /// Create a BedderBed for a sorted, bgzipped file with a tabix or CSI index. When a query
+    /// is on another chromosome or more than one index bin ahead of the last record, the reader
+    /// seeks to the first block that could hold a record overlapping (or after) the query.
+    pub fn indexed<P: AsRef<Path>>(
+        r: bgzf::Reader<F>,
+        index: csi::Index,
+        path: P,
+    ) -> BedderBed<bgzf::Reader<F>> {
+        let mut b = BedderBed::new(r, path);
+        b.index = Some(Box::new(index));
+        b.seek = Some(Self::seek_indexed);
+        b
+    }
+
+    fn seek_indexed(&mut self, q: &dyn Positioned) -> io::Result<()> {
+        let index = match &self.index {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+        if let Some(last) = &self.last_record {
+            // nearby records are found faster by reading.
+            if last.chrom == q.chrom() && q.start() < last.start + (1 << index.min_shift()) {
+                return Ok(());
+            }
+        }
+        let id = match index
+            .header()
+            .and_then(|h| h.reference_sequence_names().get_index_of(q.chrom()))
+        {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+        let start = Position::try_from(q.start() as usize + 1)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let first = index.query(id, start..)?.iter().map(|c| c.start()).min();
+        let reader = self.reader.get_mut();
+        // never go back to records that were already read.
+        if let Some(first) = first.filter(|&f| f > reader.virtual_position()) {
+            reader.seek(first)?;
+            self.offset = first.compressed();
+            self.seeked = true;
+        }
+        Ok(())
     }
 }
 
@@ -68,33 +330,44 @@ impl<R> crate::position::PositionedIterator for BedderBed<R>
 where
     R: BufRead,
 {
-    // type Item = bed::record::Record<3>;
-    type Item = Box<dyn Positioned>;
+    type Item = BedRecord;
 
     fn next_position(
         &mut self,
-        _q: Option<&dyn crate::position::Positioned>,
-    ) -> Option<std::result::Result<Self::Item, std::io::Error>> {
-        self.buf.clear();
+        q: Option<&dyn crate::position::Positioned>,
+    ) -> Option<crate::error::Result<Self::Item>> {
+        if let (Some(seek), Some(q)) = (self.seek, q) {
+            if let Err(e) = seek(self, q) {
+                return Some(Err(Error::Io(e)));
+            }
+        }
+        let mut record = self.free.pop().unwrap_or_default();
         loop {
+            record.line.clear();
             self.line_number += 1;
-            return match self.reader.read_line(&mut self.buf) {
+            return match self.reader.read_line(&mut record.line) {
                 Ok(0) => None,
-                Ok(_) => {
-                    if self.buf.starts_with('#') || self.buf.is_empty() {
+                Ok(n) => {
+                    if self.index.is_none() {
+                        self.offset += n as u64;
+                    }
+                    if record.line.starts_with('#') || record.line.is_empty() {
                         continue;
                     }
-                    let record: bed::record::Record<3> = match self.buf.parse() {
-                        Err(e) => {
-                            let msg = format!(
-                                "line#{:?}:{:?} error: {:?}",
-                                self.line_number, &self.buf, e
-                            );
-                            return Some(Err(io::Error::new(io::ErrorKind::InvalidData, msg)));
-                        }
-                        Ok(r) => r,
-                    };
-
+                    if let Err(e) = record.parse() {
+                        // after seeking, the name holds the byte offset instead of the line.
+                        let (name, line) = if self.seeked {
+                            (self.name(), 0)
+                        } else {
+                            (self.path.clone(), self.line_number)
+                        };
+                        return Some(Err(Error::Parse {
+                            name,
+                            line,
+                            region: None,
+                            message: to_string_type(format!("{:?}: {}", &record.line, e)),
+                        }));
+                    }
                     match &mut self.last_record {
                         None => {
                             self.last_record = Some(Last {
@@ -112,15 +385,27 @@ where
                         }
                     }
 
-                    Some(Ok(Box::new(record)))
+                    Some(Ok(record))
                 }
-                Err(e) => Some(Err(e)),
+                Err(e) => Some(Err(Error::Io(e))),
             };
         }
     }
 
+    fn recycle(&mut self, p: Self::Item) {
+        if self.free.len() < MAX_FREE {
+            self.free.push(p);
+        }
+    }
+
     fn name(&self) -> String {
-        String::from(format!("bed:{}", self.line_number))
+        if self.seeked && self.index.is_some() {
+            to_string_type(format!("{} (block at byte {})", self.path, self.offset))
+        } else if self.seeked {
+            to_string_type(format!("{} (byte {})", self.path, self.offset))
+        } else {
+            to_string_type(format!("{}:{}", self.path, self.line_number))
+        }
     }
 }
 
@@ -128,19 +413,21 @@ where
 mod tests {
     use super::*;
     use crate::intersection::IntersectionIterator;
+    use crate::position::{BoxPositioned, DynPositionedIterator, PositionedIterator};
     use std::collections::HashMap;
     use std::io::Cursor;
 
     #[test]
     fn test_bed_read() {
         // write a test for bed from a string using BufRead
-        let ar = BedderBed::new(Cursor::new("chr1\t20\t30\nchr1\t21\t33"));
-        let br = BedderBed::new(Cursor::new("chr1\t21\t30\nchr1\t22\t33"));
+        let ar = BedderBed::new(Cursor::new("chr1\t20\t30\nchr1\t21\t33"), "a.bed");
+        let br = BedderBed::new(Cursor::new("chr1\t21\t30\nchr1\t22\t33"), "b.bed");
 
         let chrom_order = HashMap::from([(String::from("chr1"), 0), (String::from("chr2"), 1)]);
 
-        let it = IntersectionIterator::new(Box::new(ar), vec![Box::new(br)], &chrom_order)
-            .expect("error creating iterator");
+        // both inputs are BedderBed so this uses the statically dispatched iterator.
+        let it =
+            IntersectionIterator::new(ar, vec![br], &chrom_order).expect("error creating iterator");
 
         let mut n = 0;
         it.for_each(|int| {
@@ -151,4 +438,209 @@ mod tests {
         });
         assert!(n == 2);
     }
+
+    #[test]
+    fn test_bed_line() {
+        let mut br = BedderBed::new(
+            Cursor::new("#header\nchr1\t20\t30\tname\t0.5\nchr1\t22\t33\n"),
+            "b.bed",
+        );
+        let p = br.next_position(None).unwrap().unwrap();
+        assert_eq!(p.line(), Some("chr1\t20\t30\tname\t0.5"));
+        assert_eq!(
+            p.value(Field::Int(3)).unwrap(),
+            Value::Strings(vec![Some(String::from("name"))])
+        );
+        let p = br.next_position(None).unwrap().unwrap();
+        assert_eq!(p.line(), Some("chr1\t22\t33"));
+        assert!(br.next_position(None).is_none());
+    }
+
+    #[test]
+    fn test_bed_recycle() {
+        let mut br = BedderBed::new(
+            Cursor::new("chr1\t20\t30\tlonger line\nchr1\t22\t33\n"),
+            "b.bed",
+        );
+        let p = br.next_position(None).unwrap().unwrap();
+        let ptr = p.line().unwrap().as_ptr();
+        br.recycle(p);
+        // the recycled record (and its line buffer) is reused.
+        let p = br.next_position(None).unwrap().unwrap();
+        assert_eq!(p.line(), Some("chr1\t22\t33"));
+        assert_eq!(p.line().unwrap().as_ptr(), ptr);
+        assert_eq!(p.start(), 22);
+    }
+
+    #[test]
+    fn test_boxed_bed_recycle() {
+        let mut br = BoxPositioned::new(BedderBed::new(
+            Cursor::new("chr1\t20\t30\tlonger line\nchr1\t22\t33\n"),
+            "b.bed",
+        ));
+        let p = br.next_position(None).unwrap().unwrap();
+        let ptr = p.line().unwrap().as_ptr();
+        br.recycle(p);
+        // the box is reused and the record is given back to the BedderBed.
+        let p = br.next_position(None).unwrap().unwrap();
+        assert_eq!(p.line(), Some("chr1\t22\t33"));
+        assert_eq!(p.line().unwrap().as_ptr(), ptr);
+    }
+
+    /// a sorted bed with a long interval at the start followed by `n` short intervals.
+    fn sorted_bed(n: u64) -> Vec<u8> {
+        let mut s = std::string::String::from("chr1\t0\t300000\tlong\n");
+        for i in 0..n {
+            s.push_str(&format!("chr1\t{}\t{}\n", i * 10, i * 10 + 5));
+        }
+        s.push_str("chr2\t0\t5\n");
+        s.into_bytes()
+    }
+
+    #[test]
+    fn test_bed_seek() {
+        let mut br =
+            BedderBed::seekable(Cursor::new(sorted_bed(50_000)), "b.bed", 300_000).unwrap();
+        assert_eq!(br.next_position(None).unwrap().unwrap().start(), 0);
+        let q = BedRecord {
+            line: std::string::String::from("chr1\t450000\t450010"),
+            chrom_end: 4,
+            start: 450_000,
+            stop: 450_010,
+        };
+        let p = br.next_position(Some(&q)).unwrap().unwrap();
+        // we skipped ahead, but not past anything that the long interval says could overlap.
+        assert!(p.start() > 100_000, "did not seek: {}", p.start());
+        assert!(p.start() <= 150_000, "seeked too far: {}", p.start());
+        assert!(br.name().contains("byte"));
+        let mut last = p.start();
+        while let Some(p) = br.next_position(None) {
+            let p = p.unwrap();
+            assert!(p.start() >= last || p.chrom() == "chr2");
+            last = p.start();
+        }
+    }
+
+    #[test]
+    fn test_bed_seek_intersection() {
+        let queries = "chr1\t20\t30\nchr1\t250000\t250010\nchr1\t450000\t450010\nchr2\t1\t2\n";
+        let chrom_order = HashMap::from([(String::from("chr1"), 0), (String::from("chr2"), 1)]);
+        let counts = |seek: bool| -> Vec<usize> {
+            let a = BedderBed::new(Cursor::new(queries.as_bytes().to_vec()), "a.bed");
+            let data = Cursor::new(sorted_bed(50_000));
+            let b = if seek {
+                BedderBed::seekable(data, "b.bed", 300_000).unwrap()
+            } else {
+                BedderBed::new(data, "b.bed")
+            };
+            IntersectionIterator::new(a, vec![b], &chrom_order)
+                .unwrap()
+                .map(|i| i.unwrap().overlapping.len())
+                .collect()
+        };
+        assert_eq!(counts(true), counts(false));
+        assert_eq!(counts(true), vec![2, 2, 1, 1]);
+    }
+
+    #[test]
+    fn test_bed_seek_long_interval() {
+        // a long interval in the middle of the file, in the part that a query skips over.
+        let mut s = std::string::String::new();
+        for i in 0..50_000u64 {
+            s.push_str(&format!("chr1\t{}\t{}\n", i * 10, i * 10 + 5));
+            if i == 30_000 {
+                s.push_str("chr1\t300000\t460000\tlong\n");
+            }
+        }
+        let q = BedRecord {
+            line: std::string::String::from("chr1\t450000\t450010"),
+            chrom_end: 4,
+            start: 450_000,
+            stop: 450_010,
+        };
+        let overlaps = |mut b: BedderBed<Cursor<Vec<u8>>>| -> (Vec<std::string::String>, bool) {
+            let mut found = Vec::new();
+            // seeking starts from a record on the same chromosome.
+            b.next_position(None).unwrap().unwrap();
+            let mut q = Some(&q as &dyn Positioned);
+            while let Some(p) = b.next_position(q.take()) {
+                let p = p.unwrap();
+                if p.start() < 450_010 && p.stop() > 450_000 {
+                    found.push(p.line().unwrap().to_string());
+                }
+            }
+            (found, b.seeked)
+        };
+        let data = || Cursor::new(s.clone().into_bytes());
+        let expected: Vec<_> = ["chr1\t300000\t460000\tlong", "chr1\t450000\t450005"]
+            .map(std::string::String::from)
+            .to_vec();
+        assert_eq!(
+            overlaps(BedderBed::new(data(), "b.bed")),
+            (expected.clone(), false)
+        );
+        let b = BedderBed::seekable(data(), "b.bed", 160_000).unwrap();
+        assert_eq!(overlaps(b), (expected, true));
+    }
+
+    #[test]
+    fn test_bed_indexed_intersection() {
+        use crate::index::{index_bgzf, IndexFormat};
+        use std::io::Write;
+        let mut w = bgzf::Writer::new(Vec::new());
+        w.write_all(&sorted_bed(50_000)).unwrap();
+        let data = w.finish().unwrap();
+        let header = csi::index::header::Builder::bed().build();
+        let index = index_bgzf(&data[..], "b.bed.gz", IndexFormat::Tabix, header).unwrap();
+
+        let queries = "chr1\t20\t30\nchr1\t250000\t250010\nchr1\t450000\t450010\nchr2\t1\t2\n";
+        let chrom_order = HashMap::from([(String::from("chr1"), 0), (String::from("chr2"), 1)]);
+        let mut b = BedderBed::indexed(
+            bgzf::Reader::new(Cursor::new(data.clone())),
+            index.clone(),
+            "b",
+        );
+        let q = BedRecord::from_line("chr1\t450000\t450010").unwrap();
+        let p = b.next_position(Some(&q)).unwrap().unwrap();
+        assert!(p.start() > 400_000 && p.start() <= 450_000, "{}", p.start());
+        assert!(b.name().contains("block"));
+
+        let a = BedderBed::new(Cursor::new(queries.as_bytes().to_vec()), "a.bed");
+        let b = BedderBed::indexed(bgzf::Reader::new(Cursor::new(data)), index, "b.bed.gz");
+        // the query and database readers are different types so box them.
+        let a: DynPositionedIterator = Box::new(BoxPositioned::new(a));
+        let b: DynPositionedIterator = Box::new(BoxPositioned::new(b));
+        let counts: Vec<_> = IntersectionIterator::new(a, vec![b], &chrom_order)
+            .unwrap()
+            .map(|i| i.unwrap().overlapping.len())
+            .collect();
+        assert_eq!(counts, vec![2, 2, 1, 1]);
+    }
+
+    #[test]
+    fn test_bed_parse_error() {
+        let mut br = BedderBed::new(Cursor::new("chr1\t20\t30\nchr1\tx\t33\n"), "b.bed");
+        assert!(br.next_position(None).unwrap().is_ok());
+        assert_eq!(br.name(), "b.bed:1");
+        match br.next_position(None).unwrap() {
+            Err(Error::Parse { name, line, .. }) => {
+                assert_eq!(name, "b.bed");
+                assert_eq!(line, 2)
+            }
+            r => panic!("expected parse error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_bed_start_after_end() {
+        let mut br = BedderBed::new(Cursor::new("chr1\t20\t30\nchr1\t40\t33\n"), "b.bed");
+        assert!(br.next_position(None).unwrap().is_ok());
+        match br.next_position(None).unwrap() {
+            Err(Error::Parse { name, line, .. }) => {
+                assert_eq!(name, "b.bed");
+                assert_eq!(line, 2)
+            }
+            r => panic!("expected parse error, got {:?}", r),
+        }
+    }
 }
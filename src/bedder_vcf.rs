@@ -1,14 +1,30 @@
+use crate::error::Error;
 use crate::position::{Field, FieldError, Positioned, Value};
-use crate::string::String;
+use crate::string::{to_string_type, String};
 use noodles::bcf;
-use noodles::vcf::{self, record::Chromosome};
+use noodles::vcf::{self, header::Number, record::Chromosome};
+use std::any::Any;
 use std::io::{self, BufRead};
+use std::path::Path;
+use std::rc::Rc;
 use std::result;
+use vcf::header::record::value::map::info::Type as InfoType;
 use vcf::record::info::field;
-use vcf::record::QualityScore;
 
 pub trait VCFReader {
     fn read_record(&mut self, header: &vcf::Header, v: &mut vcf::Record) -> io::Result<usize>;
+
+    /// Read a record and keep its original text (without the newline) in `line`.
+    /// Readers of binary formats leave `line` empty.
+    fn read_record_line(
+        &mut self,
+        header: &vcf::Header,
+        v: &mut vcf::Record,
+        line: &mut std::string::String,
+    ) -> io::Result<usize> {
+        line.clear();
+        self.read_record(header, v)
+    }
     // fn queryable
 }
 
@@ -22,6 +38,83 @@ where
     }
 }
 
+/// A BufRead wrapper that keeps a copy of the bytes consumed since the last clear.
+/// This lets noodles parse into a reused record while we keep the original line.
+pub struct LineCapture<R> {
+    inner: R,
+    captured: Vec<u8>,
+}
+
+impl<R: BufRead> io::Read for LineCapture<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.captured.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for LineCapture<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        // the buffer was already filled so this does not read.
+        if let Ok(buf) = self.inner.fill_buf() {
+            self.captured.extend_from_slice(&buf[..amt.min(buf.len())]);
+        }
+        self.inner.consume(amt)
+    }
+}
+
+/// A text VCF reader that keeps the original line of each record.
+pub struct LineVCFReader<R> {
+    reader: vcf::Reader<LineCapture<R>>,
+}
+
+impl<R: BufRead> LineVCFReader<R> {
+    pub fn new(inner: R) -> Self {
+        LineVCFReader {
+            reader: vcf::Reader::new(LineCapture {
+                inner,
+                captured: Vec::new(),
+            }),
+        }
+    }
+
+    pub fn read_header(&mut self) -> io::Result<vcf::Header> {
+        let header = self.reader.read_header();
+        self.reader.get_mut().captured.clear();
+        header
+    }
+}
+
+impl<R: BufRead> VCFReader for LineVCFReader<R> {
+    #[inline]
+    fn read_record(&mut self, header: &vcf::Header, v: &mut vcf::Record) -> io::Result<usize> {
+        // the line is not needed, but what is captured must not grow with every record.
+        self.reader.get_mut().captured.clear();
+        self.reader.read_record(header, v)
+    }
+
+    fn read_record_line(
+        &mut self,
+        header: &vcf::Header,
+        v: &mut vcf::Record,
+        line: &mut std::string::String,
+    ) -> io::Result<usize> {
+        line.clear();
+        self.reader.get_mut().captured.clear();
+        let n = self.reader.read_record(header, v)?;
+        let captured = &self.reader.get_ref().captured;
+        let captured = captured.strip_suffix(b"\n").unwrap_or(captured);
+        let captured = captured.strip_suffix(b"\r").unwrap_or(captured);
+        // the record parsed so the line is valid utf-8.
+        line.push_str(&std::string::String::from_utf8_lossy(captured));
+        Ok(n)
+    }
+}
+
 impl<R> VCFReader for vcf::indexed_reader::IndexedReader<R>
 where
     R: BufRead,
@@ -42,91 +135,343 @@ where
     }
 }
 
+/// A VCF record along with the original line from text VCF files.
+/// The record and line are reused when the record is recycled by the reader.
+#[derive(Debug, Default)]
+pub struct VcfRecord {
+    pub record: vcf::Record,
+    // empty for binary formats.
+    line: std::string::String,
+    // the header of the file, to tell INFO keys that are absent from a record from
+    // keys that do not exist.
+    header: Option<Rc<vcf::Header>>,
+}
+
+impl VcfRecord {
+    /// the number of samples in the record. FORMAT values hold the elements of each sample
+    /// in turn (see `Positioned::value`).
+    pub fn sample_count(&self) -> usize {
+        self.record.genotypes().values().count()
+    }
+
+    /// parse a VCF line (without the newline), keeping it as the record's line.
+    pub fn parse(line: std::string::String, header: &Rc<vcf::Header>) -> io::Result<Self> {
+        let record = vcf::Record::try_from((header.as_ref(), line.as_str()))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(VcfRecord {
+            record,
+            line,
+            header: Some(header.clone()),
+        })
+    }
+
+    /// the Number that the header declares for a field such as `INFO.AF` or `FORMAT.AD`.
+    pub fn number(&self, f: &Field) -> Option<Number> {
+        let header = self.header.as_deref()?;
+        match f {
+            Field::String(s) => match s.split_once('.') {
+                Some(("INFO", key)) => info_number(header, key),
+                Some(("FORMAT", key)) => format_number(header, key),
+                _ => None,
+            },
+            Field::Int(_) => None,
+        }
+    }
+}
+
+/// the Number of INFO field `key` declared in `header`.
+pub(crate) fn info_number(header: &vcf::Header, key: &str) -> Option<Number> {
+    let key: vcf::record::info::field::Key = key.parse().ok()?;
+    header.infos().get(&key).map(|i| i.number())
+}
+
+/// the Number of FORMAT field `key` declared in `header`.
+pub(crate) fn format_number(header: &vcf::Header, key: &str) -> Option<Number> {
+    let key: vcf::record::genotypes::keys::Key = key.parse().ok()?;
+    header.formats().get(&key).map(|f| f.number())
+}
+
+impl Positioned for VcfRecord {
+    #[inline]
+    fn chrom(&self) -> &str {
+        Positioned::chrom(&self.record)
+    }
+
+    #[inline]
+    fn start(&self) -> u64 {
+        Positioned::start(&self.record)
+    }
+
+    #[inline]
+    fn stop(&self) -> u64 {
+        Positioned::stop(&self.record)
+    }
+
+    fn value(&self, f: crate::position::Field) -> result::Result<Value, FieldError> {
+        match_value(&self.record, self.header.as_deref(), f)
+    }
+
+    fn line(&self) -> Option<&str> {
+        if self.line.is_empty() {
+            None
+        } else {
+            Some(&self.line)
+        }
+    }
+
+    fn into_any(self: Box<Self>) -> Option<Box<dyn Any>> {
+        Some(self)
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        Some(self)
+    }
+}
+
+/// at most this many records are kept for reuse.
+const MAX_FREE: usize = 1024;
+
 pub struct BedderVCF<'a> {
     reader: Box<dyn VCFReader + 'a>,
-    header: vcf::Header,
+    header: Rc<vcf::Header>,
     record_number: u64,
+    path: String,
+    // records returned via `recycle` that are reused to avoid allocation.
+    free: Vec<VcfRecord>,
+    // format records from binary files as text so that every record has a line.
+    lines: bool,
 }
 
 impl<'a> BedderVCF<'a> {
-    pub fn new(r: Box<dyn VCFReader + 'a>, header: vcf::Header) -> io::Result<BedderVCF<'a>> {
+    /// Create a new BedderVCF. `path` is used only to name the iterator in messages.
+    pub fn new<P: AsRef<Path>>(
+        r: Box<dyn VCFReader + 'a>,
+        header: vcf::Header,
+        path: P,
+    ) -> crate::error::Result<BedderVCF<'a>> {
         let v = BedderVCF {
             reader: r,
-            header,
+            header: Rc::new(header),
             record_number: 0,
+            path: String::from(&*path.as_ref().to_string_lossy()),
+            free: Vec::new(),
+            lines: false,
         };
         Ok(v)
     }
+
+    /// the header of the VCF or BCF file.
+    pub fn header(&self) -> &vcf::Header {
+        &self.header
+    }
+
+    /// the path of the file, as used in error messages.
+    pub fn path(&self) -> &String {
+        &self.path
+    }
+
+    /// the 1-based number of the last record read.
+    pub fn record_number(&self) -> u64 {
+        self.record_number
+    }
+
+    /// the header shared with the records of this reader; see `VcfRecord::parse`.
+    pub fn shared_header(&self) -> &Rc<vcf::Header> {
+        &self.header
+    }
+
+    /// Give every record a `line`, formatting records from BCF files as VCF text.
+    /// Text VCF records always keep their original line.
+    pub fn with_lines(mut self) -> Self {
+        self.lines = true;
+        self
+    }
 }
 
-fn match_info_value(info: &vcf::record::Info, name: &str) -> result::Result<Value, FieldError> {
-    //let info = record.info();
+/// get an INFO field. A key that is absent from `info` but declared in `header` is
+/// `Missing`, or `Flag(false)` for flags; without a header, absent keys are an error.
+fn match_info_value(
+    info: &vcf::record::Info,
+    header: Option<&vcf::Header>,
+    name: &str,
+) -> result::Result<Value, FieldError> {
     let key: vcf::record::info::field::Key = name
         .parse()
         .map_err(|_| FieldError::InvalidFieldName(String::from(name)))?;
 
     match info.get(&key) {
         Some(value) => match value {
-            Some(field::Value::Integer(i)) => Ok(Value::Ints(vec![*i as i64])),
-            Some(field::Value::Float(f)) => Ok(Value::Floats(vec![*f as f64])),
-            Some(field::Value::String(s)) => Ok(Value::Strings(vec![String::from(s)])),
+            Some(field::Value::Integer(i)) => Ok(Value::Ints(vec![Some(*i as i64)])),
+            Some(field::Value::Float(f)) => Ok(Value::Floats(vec![Some(*f as f64)])),
+            Some(field::Value::String(s)) => Ok(Value::Strings(vec![Some(String::from(s))])),
             Some(field::Value::Character(c)) => {
-                Ok(Value::Strings(vec![String::from(c.to_string())]))
-            }
-            //Some(field::Value::Flag) => Ok(Value::Strings(vec![String::from("true")])),
-            Some(field::Value::Array(arr)) => {
-                match arr {
-                    field::value::Array::Integer(arr) => Ok(Value::Ints(
-                        arr.iter().flatten().map(|&v| v as i64).collect(),
-                    )),
-                    field::value::Array::Float(arr) => Ok(Value::Floats(
-                        arr.iter().flatten().map(|&v| v as f64).collect(),
-                    )),
-                    field::value::Array::String(arr) => Ok(Value::Strings(
-                        arr.iter().flatten().map(String::from).collect(),
-                    )),
-                    field::value::Array::Character(arr) => Ok(Value::Strings(
-                        arr.iter().flatten().map(|v| v.to_string().into()).collect(),
-                    )),
-                    //field::Value::Flag => Ok(Value::Strings(vec![String::from("true")])),
-                }
+                Ok(Value::Strings(vec![Some(to_string_type(c.to_string()))]))
             }
-
-            _ => Err(FieldError::InvalidFieldName(String::from(name))),
+            Some(field::Value::Flag) => Ok(Value::Flag(true)),
+            // missing elements are kept so values stay aligned with alleles.
+            Some(field::Value::Array(arr)) => match arr {
+                field::value::Array::Integer(arr) => Ok(Value::Ints(
+                    arr.iter().map(|v| v.map(|v| v as i64)).collect(),
+                )),
+                field::value::Array::Float(arr) => Ok(Value::Floats(
+                    arr.iter().map(|v| v.map(|v| v as f64)).collect(),
+                )),
+                field::value::Array::String(arr) => Ok(Value::Strings(
+                    arr.iter().map(|v| v.as_ref().map(String::from)).collect(),
+                )),
+                field::value::Array::Character(arr) => Ok(Value::Strings(
+                    arr.iter()
+                        .map(|v| v.map(|v| to_string_type(v.to_string())))
+                        .collect(),
+                )),
+            },
+            // the key is present without a value.
+            None => Ok(Value::Missing),
+        },
+        None => match header.and_then(|h| h.infos().get(&key)) {
+            Some(info) if info.ty() == InfoType::Flag => Ok(Value::Flag(false)),
+            Some(_) => Ok(Value::Missing),
+            None => Err(FieldError::InvalidFieldName(String::from(name))),
         },
-        None => Err(FieldError::InvalidFieldName(String::from(name))),
     }
 }
 
-fn match_value(record: &vcf::record::Record, f: Field) -> result::Result<Value, FieldError> {
+/// get a FORMAT field with one element per sample. Arrays (e.g. Number=A or R) are
+/// flattened sample by sample, each padded with missing elements to the length of the
+/// longest, so `AD` of two biallelic samples is `[ref1, alt1, ref2, alt2]`. Integers and
+/// floats keep their type; other values are returned as strings.
+fn match_format_value(
+    genotypes: &vcf::record::Genotypes,
+    name: &str,
+) -> result::Result<Value, FieldError> {
+    use vcf::record::genotypes::sample::value::Array;
+    use vcf::record::genotypes::sample::Value as SampleValue;
+    let key: vcf::record::genotypes::keys::Key = name
+        .parse()
+        .map_err(|_| FieldError::InvalidFieldName(String::from(name)))?;
+    if !genotypes.keys().contains(&key) {
+        return Err(FieldError::InvalidFieldName(String::from(name)));
+    }
+    // samples may end early, leaving trailing keys missing.
+    let values: Vec<Option<&SampleValue>> = genotypes
+        .values()
+        .map(|sample| sample.get(&key).flatten())
+        .collect();
+    let width = values
+        .iter()
+        .map(|v| match v {
+            Some(SampleValue::Array(Array::Integer(a))) => a.len(),
+            Some(SampleValue::Array(Array::Float(a))) => a.len(),
+            Some(SampleValue::Array(Array::Character(a))) => a.len(),
+            Some(SampleValue::Array(Array::String(a))) => a.len(),
+            _ => 1,
+        })
+        .max()
+        .unwrap_or(1);
+    fn flatten<T, F>(values: &[Option<&SampleValue>], width: usize, f: F) -> Vec<Option<T>>
+    where
+        F: Fn(&SampleValue) -> Vec<Option<T>>,
+    {
+        let mut out = Vec::with_capacity(values.len() * width);
+        for v in values {
+            let start = out.len();
+            out.extend(v.map(&f).unwrap_or_default());
+            out.resize_with(start + width, || None);
+        }
+        out
+    }
+    if values.iter().flatten().all(|v| {
+        matches!(
+            v,
+            SampleValue::Integer(_) | SampleValue::Array(Array::Integer(_))
+        )
+    }) {
+        return Ok(Value::Ints(flatten(&values, width, |v| match v {
+            SampleValue::Integer(i) => vec![Some(*i as i64)],
+            SampleValue::Array(Array::Integer(a)) => {
+                a.iter().map(|i| i.map(|i| i as i64)).collect()
+            }
+            _ => unreachable!(),
+        })));
+    }
+    if values.iter().flatten().all(|v| {
+        matches!(
+            v,
+            SampleValue::Float(_) | SampleValue::Array(Array::Float(_))
+        )
+    }) {
+        return Ok(Value::Floats(flatten(&values, width, |v| match v {
+            SampleValue::Float(f) => vec![Some(*f as f64)],
+            SampleValue::Array(Array::Float(a)) => a.iter().map(|f| f.map(|f| f as f64)).collect(),
+            _ => unreachable!(),
+        })));
+    }
+    Ok(Value::Strings(flatten(&values, width, |v| match v {
+        SampleValue::Array(Array::Integer(a)) => a
+            .iter()
+            .map(|i| i.map(|i| to_string_type(i.to_string())))
+            .collect(),
+        SampleValue::Array(Array::Float(a)) => a
+            .iter()
+            .map(|f| f.map(|f| to_string_type(f.to_string())))
+            .collect(),
+        SampleValue::Array(Array::Character(a)) => a
+            .iter()
+            .map(|c| c.map(|c| to_string_type(c.to_string())))
+            .collect(),
+        SampleValue::Array(Array::String(a)) => {
+            a.iter().map(|s| s.as_deref().map(String::from)).collect()
+        }
+        v => vec![Some(to_string_type(v.to_string()))],
+    })))
+}
+
+fn match_value(
+    record: &vcf::record::Record,
+    header: Option<&vcf::Header>,
+    f: Field,
+) -> result::Result<Value, FieldError> {
     match f {
         Field::String(s) => match s.as_str() {
-            "chrom" => Ok(Value::Strings(vec![String::from(Positioned::chrom(
+            "chrom" => Ok(Value::Strings(vec![Some(String::from(Positioned::chrom(
                 record,
-            ))])),
-            "start" => Ok(Value::Ints(vec![Positioned::start(record) as i64])),
-            "stop" => Ok(Value::Ints(vec![Positioned::stop(record) as i64])),
+            )))])),
+            "start" => Ok(Value::Ints(vec![Some(Positioned::start(record) as i64)])),
+            "stop" => Ok(Value::Ints(vec![Some(Positioned::stop(record) as i64)])),
             "ID" => Ok(Value::Strings(
-                record.ids().iter().map(|s| s.to_string().into()).collect(),
+                record
+                    .ids()
+                    .iter()
+                    .map(|s| Some(to_string_type(s.to_string())))
+                    .collect(),
             )),
             "FILTER" => Ok(Value::Strings(
                 record
                     .filters()
                     .iter()
-                    .map(|s| String::from(s.to_string()))
+                    .map(|s| Some(to_string_type(s.to_string())))
                     .collect(),
             )),
-            "QUAL" => Ok(Value::Floats(vec![f32::from(
+            "REF" => Ok(Value::Strings(vec![Some(to_string_type(
+                record.reference_bases().to_string(),
+            ))])),
+            "ALT" => Ok(Value::Strings(
                 record
-                    .quality_score()
-                    .unwrap_or(QualityScore::try_from(0f32).expect("error getting quality score")),
-            ) as f64])),
+                    .alternate_bases()
+                    .iter()
+                    .map(|a| Some(to_string_type(a.to_string())))
+                    .collect(),
+            )),
+            "QUAL" => Ok(Value::Floats(vec![record
+                .quality_score()
+                .map(|q| f32::from(q) as f64)])),
             _ => {
                 if s.len() > 5 && &s[0..5] == "INFO." {
-                    match_info_value(record.info(), &s[5..])
+                    match_info_value(record.info(), header, &s[5..])
+                } else if s.len() > 7 && &s[0..7] == "FORMAT." {
+                    match_format_value(record.genotypes(), &s[7..])
                 } else {
-                    // TODO: format
-                    unimplemented!();
+                    Err(FieldError::InvalidFieldName(s))
                 }
             }
         },
@@ -146,7 +491,8 @@ impl Positioned for vcf::record::Record {
 
     #[inline]
     fn start(&self) -> u64 {
-        usize::from(self.position()) as u64
+        // noodles position is 1-based.
+        usize::from(self.position()) as u64 - 1
     }
 
     #[inline]
@@ -155,38 +501,70 @@ impl Positioned for vcf::record::Record {
     }
 
     fn value(&self, f: crate::position::Field) -> result::Result<Value, FieldError> {
-        // TODO: implement this!
-        match_value(self, f)
+        match_value(self, None, f)
     }
 }
 
 impl<'a> crate::position::PositionedIterator for BedderVCF<'a> {
-    type Item = Box<dyn Positioned>;
+    type Item = VcfRecord;
 
     fn next_position(
         &mut self,
         _q: Option<&dyn crate::position::Positioned>,
-    ) -> Option<std::result::Result<Self::Item, std::io::Error>> {
-        let mut v = vcf::Record::default();
+    ) -> Option<crate::error::Result<Self::Item>> {
+        let mut v = self.free.pop().unwrap_or_default();
+        if v.header.is_none() {
+            v.header = Some(Rc::clone(&self.header));
+        }
 
-        match self.reader.read_record(&self.header, &mut v) {
+        match self
+            .reader
+            .read_record_line(&self.header, &mut v.record, &mut v.line)
+        {
             Ok(0) => None, // EOF
             Ok(_) => {
                 self.record_number += 1;
-                Some(Ok(Box::new(v)))
+                if self.lines && v.line.is_empty() {
+                    use std::fmt::Write;
+                    // writing to a String can not fail.
+                    let _ = write!(v.line, "{}", v.record);
+                }
+                Some(Ok(v))
             }
-            Err(e) => Some(Err(e)),
+            // noodles reports malformed records as InvalidData.
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => Some(Err(Error::Parse {
+                name: self.path.clone(),
+                line: self.record_number + 1,
+                region: None,
+                message: to_string_type(e.to_string()),
+            })),
+            Err(e) => Some(Err(Error::Io(e))),
+        }
+    }
+    fn recycle(&mut self, p: Self::Item) {
+        if self.free.len() < MAX_FREE {
+            self.free.push(p);
         }
     }
+
     fn name(&self) -> String {
-        String::from("vcf")
+        to_string_type(format!("{}:{}", self.path, self.record_number))
     }
 }
 
+/// read a text VCF (header and records) from `text` for tests. `name` names the iterator.
+#[cfg(test)]
+pub(crate) fn vcf_from_str(text: &str, name: &str) -> BedderVCF<'static> {
+    let mut reader = LineVCFReader::new(std::io::Cursor::new(text.to_string()));
+    let header = reader.read_header().expect("error reading header");
+    BedderVCF::new(Box::new(reader), header, name).expect("error creating BedderVCF")
+}
+
 // tests
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::position::PositionedIterator;
 
     #[test]
     fn test_match_info() {
@@ -197,10 +575,134 @@ mod tests {
             .collect();
 
         // write a test to extract the value using match_info_value
-        let value = match_info_value(&info, "AAA").unwrap();
+        let value = match_info_value(&info, None, "AAA").unwrap();
         assert!(matches!(value, Value::Ints(_)));
     }
 
+    #[test]
+    fn test_match_info_flag_and_missing() {
+        let flag: field::Key = "DB".parse().expect("error parsing key");
+        let missing: field::Key = "AF".parse().expect("error parsing key");
+
+        let info: vcf::record::Info = [(flag, Some(field::Value::Flag)), (missing, None)]
+            .into_iter()
+            .collect();
+
+        assert_eq!(
+            match_info_value(&info, None, "DB").unwrap(),
+            Value::Flag(true)
+        );
+        assert_eq!(match_info_value(&info, None, "AF").unwrap(), Value::Missing);
+        assert!(match_info_value(&info, None, "XX").is_err());
+    }
+
+    #[test]
+    fn test_match_info_absent() {
+        let data = "##fileformat=VCFv4.3\n##INFO=<ID=AF,Number=A,Type=Float,Description=\"AF\">\n##INFO=<ID=DB,Number=0,Type=Flag,Description=\"DB\">\n#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\nchr1\t10\t.\tA\tT\t.\t.\t.\nchr1\t20\t.\tA\tT\t.\t.\tAF=0.5;DB\n";
+        let mut reader = vcf::Reader::new(std::io::Cursor::new(data));
+        let header = reader.read_header().expect("error reading header");
+        let mut v = BedderVCF::new(Box::new(reader), header, "t.vcf").unwrap();
+        let value = |p: &VcfRecord, name: &str| p.value(Field::String(String::from(name)));
+        let p = v.next_position(None).unwrap().unwrap();
+        // declared in the header but absent from the record.
+        assert_eq!(value(&p, "INFO.AF").unwrap(), Value::Missing);
+        assert_eq!(value(&p, "INFO.DB").unwrap(), Value::Flag(false));
+        assert!(value(&p, "INFO.XX").is_err());
+        let p = v.next_position(None).unwrap().unwrap();
+        assert_eq!(
+            value(&p, "INFO.AF").unwrap(),
+            Value::Floats(vec![Some(0.5)])
+        );
+        assert_eq!(value(&p, "INFO.DB").unwrap(), Value::Flag(true));
+    }
+
+    #[test]
+    fn test_vcf_line() {
+        let data = "##fileformat=VCFv4.3\n#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\nchr1\t10\trs1\tA\tT\t30\tPASS\t.\n";
+        let mut v = vcf_from_str(data, "t.vcf");
+        let p = v.next_position(None).unwrap().unwrap();
+        assert_eq!(p.line(), Some("chr1\t10\trs1\tA\tT\t30\tPASS\t."));
+        assert_eq!(p.start(), 9);
+        assert_eq!(v.name(), "t.vcf:1");
+        assert!(v.next_position(None).is_none());
+    }
+
+    #[test]
+    fn test_line_capture_cleared() {
+        let data = "##fileformat=VCFv4.3\n#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\nchr1\t10\trs1\tA\tT\t30\tPASS\t.\nchr1\t20\trs2\tA\tT\t30\tPASS\t.\n";
+        let mut reader = LineVCFReader::new(std::io::Cursor::new(data));
+        let header = reader.read_header().expect("error reading header");
+        assert!(reader.reader.get_ref().captured.is_empty());
+        let mut record = vcf::Record::default();
+        reader.read_record(&header, &mut record).unwrap();
+        reader.read_record(&header, &mut record).unwrap();
+        // only the last record is kept.
+        assert_eq!(
+            reader.reader.get_ref().captured,
+            b"chr1\t20\trs2\tA\tT\t30\tPASS\t.\n"
+        );
+    }
+
+    #[test]
+    fn test_vcf_bed_coordinates() {
+        use crate::bedder_bed::BedderBed;
+        use crate::intersection::IntersectionIterator;
+        use crate::position::{BoxPositioned, DynPositionedIterator};
+        use std::collections::HashMap;
+
+        // POS 10 is the 0-based, half-open interval 9-10, like the BED line chr1 9 10.
+        let data = "##fileformat=VCFv4.3\n#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\nchr1\t10\t.\tA\tT\t.\t.\t.\n";
+        let mut reader = vcf::Reader::new(std::io::Cursor::new(data));
+        let header = reader.read_header().expect("error reading header");
+        let vcf = BedderVCF::new(Box::new(reader), header, "t.vcf").unwrap();
+        let bed = BedderBed::new(
+            std::io::Cursor::new("chr1\t8\t9\nchr1\t9\t10\nchr1\t10\t11\n"),
+            "t.bed",
+        );
+        let order = HashMap::from([(String::from("chr1"), 0)]);
+        // the query and other intervals differ in type so both are boxed.
+        let bed: DynPositionedIterator = Box::new(BoxPositioned::new(bed));
+        let vcf: DynPositionedIterator = Box::new(BoxPositioned::new(vcf));
+        let it = IntersectionIterator::new(bed, vec![vcf], &order).unwrap();
+        let overlaps: Vec<_> = it
+            .map(|i| {
+                let i = i.unwrap();
+                let overlapping: Vec<_> = i
+                    .overlapping
+                    .iter()
+                    .map(|o| (o.interval.start(), o.interval.stop()))
+                    .collect();
+                (i.base_interval.start(), overlapping)
+            })
+            .collect();
+        assert_eq!(
+            overlaps,
+            vec![(8, vec![]), (9, vec![(9, 10)]), (10, vec![])]
+        );
+    }
+
+    #[test]
+    fn test_match_format() {
+        let data = "##fileformat=VCFv4.3\n##FORMAT=<ID=GT,Number=1,Type=String,Description=\"GT\">\n##FORMAT=<ID=DP,Number=1,Type=Integer,Description=\"DP\">\n##FORMAT=<ID=AD,Number=R,Type=Integer,Description=\"AD\">\n#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\ts1\ts2\nchr1\t10\t.\tA\tT\t.\t.\t.\tGT:DP:AD\t0/1:12:6,7\t1|1:.\n";
+        let mut v = vcf_from_str(data, "t.vcf");
+        let p = v.next_position(None).unwrap().unwrap();
+        let value = |name: &str| p.value(Field::String(String::from(name)));
+        assert_eq!(
+            value("FORMAT.GT").unwrap(),
+            Value::Strings(vec![Some("0/1".into()), Some("1|1".into())])
+        );
+        assert_eq!(
+            value("FORMAT.DP").unwrap(),
+            Value::Ints(vec![Some(12), None])
+        );
+        // arrays are flattened with each sample padded to the same length.
+        assert_eq!(
+            value("FORMAT.AD").unwrap(),
+            Value::Ints(vec![Some(6), Some(7), None, None])
+        );
+        assert!(value("FORMAT.GQ").is_err());
+    }
+
     #[test]
     fn test_match_info_vector() {
         let key: field::Key = "AAA".parse().expect("error parsing key");
@@ -219,15 +721,11 @@ mod tests {
         .collect();
 
         // write a test to extract the value using match_info_value
-        let value = match_info_value(&info, "AAA").unwrap();
+        let value = match_info_value(&info, None, "AAA").unwrap();
         assert!(matches!(value, Value::Ints(_)));
 
         if let Value::Ints(v) = value {
-            assert_eq!(v.len(), 4);
-            assert_eq!(v[0], -1);
-            assert_eq!(v[1], 2);
-            assert_eq!(v[2], 3);
-            assert_eq!(v[3], 496);
+            assert_eq!(v, vec![Some(-1), Some(2), Some(3), None, Some(496)]);
         } else {
             panic!("error getting value");
         }
@@ -0,0 +1,204 @@
+use crate::error::{Error, Result};
+use crate::order::ViolationKind;
+use crate::position::{Positioned, PositionedIterator};
+use crate::region::Region;
+use crate::string::{to_string_type, String};
+use std::collections::HashMap;
+
+/// An iterator over the parts of a genome not covered by the intervals of a sorted
+/// iterator (like bedtools complement). Chromosomes are visited in the order of the genome,
+/// so chromosomes without any intervals are returned whole.
+pub struct Complement<I: PositionedIterator> {
+    inner: I,
+    genome: Vec<(String, u64)>,
+    chrom_order: HashMap<String, usize>,
+    // index of the current chromosome in `genome`.
+    chrom: usize,
+    // the current chromosome is covered up to here.
+    covered: u64,
+    // start of the last interval, to check the order.
+    last_start: u64,
+    // the number of intervals read, to locate errors.
+    lines: u64,
+    // an interval that was read but not yet used.
+    next: Option<I::Item>,
+    done: bool,
+}
+
+impl<I: PositionedIterator> Complement<I> {
+    /// `genome` holds the chromosomes and their lengths in the order of the intervals.
+    pub fn new(inner: I, genome: Vec<(String, u64)>) -> Self {
+        let chrom_order = genome
+            .iter()
+            .enumerate()
+            .map(|(i, (chrom, _))| (chrom.clone(), i))
+            .collect();
+        Complement {
+            inner,
+            genome,
+            chrom_order,
+            chrom: 0,
+            covered: 0,
+            last_start: 0,
+            lines: 0,
+            next: None,
+            done: false,
+        }
+    }
+
+    /// return an error if `p`, which is not on the current chromosome, is on an unknown or
+    /// earlier chromosome.
+    fn check_chrom(&self, p: &I::Item) -> Result<()> {
+        match self.chrom_order.get(p.chrom()) {
+            None => Err(Error::UnknownChromosome {
+                name: self.inner.name(),
+                chrom: String::from(p.chrom()),
+            }),
+            Some(i) if *i < self.chrom => Err(Error::OutOfOrder {
+                name: self.inner.name(),
+                line: self.lines,
+                kind: ViolationKind::ChromosomeOrder,
+                previous: String::from(self.genome[self.chrom].0.as_str()),
+                current: String::from(p.chrom()),
+            }),
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+impl<I: PositionedIterator> PositionedIterator for Complement<I> {
+    type Item = Region;
+
+    fn next_position(&mut self, _q: Option<&dyn Positioned>) -> Option<Result<Self::Item>> {
+        loop {
+            let (chrom, length) = self.genome.get(self.chrom)?;
+            if self.next.is_none() && !self.done {
+                match self.inner.next_position(None) {
+                    None => self.done = true,
+                    Some(Err(e)) => return Some(Err(e)),
+                    Some(Ok(p)) => {
+                        self.lines += 1;
+                        self.next = Some(p);
+                    }
+                }
+            }
+            let gap = match self.next.take() {
+                Some(p) if p.chrom() == chrom.as_str() => {
+                    let (start, stop) = (p.start(), p.stop());
+                    if start < self.last_start {
+                        let e = Error::OutOfOrder {
+                            name: self.inner.name(),
+                            line: self.lines,
+                            kind: ViolationKind::Start,
+                            previous: to_string_type(format!("{}:{}", chrom, self.last_start + 1)),
+                            current: to_string_type(format!("{}:{}", chrom, start + 1)),
+                        };
+                        return Some(Err(e));
+                    }
+                    self.inner.recycle(p);
+                    self.last_start = start;
+                    let gap = Region {
+                        chrom: chrom.clone(),
+                        start: self.covered,
+                        stop: start.min(*length),
+                    };
+                    self.covered = self.covered.max(stop);
+                    gap
+                }
+                // the current chromosome has no more intervals.
+                next => {
+                    if let Some(p) = &next {
+                        if let Err(e) = self.check_chrom(p) {
+                            return Some(Err(e));
+                        }
+                    }
+                    self.next = next;
+                    let gap = Region {
+                        chrom: chrom.clone(),
+                        start: self.covered,
+                        stop: *length,
+                    };
+                    self.chrom += 1;
+                    self.covered = 0;
+                    self.last_start = 0;
+                    gap
+                }
+            };
+            if gap.start < gap.stop {
+                return Some(Ok(gap));
+            }
+        }
+    }
+
+    fn name(&self) -> String {
+        to_string_type(format!("complement of {}", self.inner.name()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bedder_bed::BedderBed;
+    use std::io::Cursor;
+
+    fn genome() -> Vec<(String, u64)> {
+        vec![
+            (String::from("chr1"), 1000),
+            (String::from("chr2"), 500),
+            (String::from("chr3"), 300),
+        ]
+    }
+
+    fn complement(bed: &str) -> Result<Vec<(std::string::String, u64, u64)>> {
+        let bed = BedderBed::new(Cursor::new(bed.to_string()), "t.bed");
+        let mut c = Complement::new(bed, genome());
+        let mut gaps = Vec::new();
+        while let Some(r) = c.next_position(None) {
+            let r = r?;
+            gaps.push((r.chrom.to_string(), r.start, r.stop));
+        }
+        Ok(gaps)
+    }
+
+    #[test]
+    fn test_complement() {
+        let gaps = complement(
+            "chr1\t0\t100\nchr1\t50\t200\nchr1\t150\t180\nchr1\t300\t400\nchr3\t100\t400\n",
+        )
+        .unwrap();
+        assert_eq!(
+            gaps,
+            vec![
+                (std::string::String::from("chr1"), 200, 300),
+                (std::string::String::from("chr1"), 400, 1000),
+                (std::string::String::from("chr2"), 0, 500),
+                (std::string::String::from("chr3"), 0, 100),
+            ]
+        );
+        assert_eq!(complement("").unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_complement_errors() {
+        let e = complement("chr1\t100\t200\nchr1\t50\t60\n").unwrap_err();
+        assert!(matches!(
+            e,
+            Error::OutOfOrder {
+                line: 2,
+                kind: ViolationKind::Start,
+                ..
+            }
+        ));
+        let e = complement("chr2\t100\t200\nchr1\t50\t60\n").unwrap_err();
+        assert!(matches!(
+            e,
+            Error::OutOfOrder {
+                line: 2,
+                kind: ViolationKind::ChromosomeOrder,
+                ..
+            }
+        ));
+        let e = complement("chr1\t100\t200\nchrX\t50\t60\n").unwrap_err();
+        assert!(matches!(e, Error::UnknownChromosome { .. }));
+    }
+}
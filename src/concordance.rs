@@ -0,0 +1,570 @@
+use crate::allele::{alleles, Allele};
+use crate::error::{Error, Result};
+use crate::order::ViolationKind;
+use crate::position::{
+    DynPositionedIterator, Field, FieldError, Positioned, PositionedIterator, Value,
+};
+use crate::string::{to_string_type, String};
+use noodles::vcf;
+use std::collections::HashMap;
+use std::fmt;
+use std::result;
+
+/// The class of a genotype used to compare calls between two VCFs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenotypeClass {
+    HomRef,
+    Het,
+    HomAlt,
+    /// no call or a partial call (e.g. `./.` or `./1`).
+    Missing,
+}
+
+impl GenotypeClass {
+    pub const ALL: [GenotypeClass; 4] = [
+        GenotypeClass::HomRef,
+        GenotypeClass::Het,
+        GenotypeClass::HomAlt,
+        GenotypeClass::Missing,
+    ];
+
+    /// classify a `GT` value such as `0/1`, `1|1` or `0` (haploid).
+    /// Genotypes with different ALTs (`1/2`) are Het.
+    pub fn parse(gt: &str) -> Self {
+        let mut alleles = gt.split(['/', '|']).map(|a| a.parse::<usize>().ok());
+        let first = match alleles.next().flatten() {
+            Some(a) => a,
+            None => return GenotypeClass::Missing,
+        };
+        let mut het = false;
+        for a in alleles {
+            match a {
+                Some(a) => het |= a != first,
+                None => return GenotypeClass::Missing,
+            }
+        }
+        match (het, first) {
+            (true, _) => GenotypeClass::Het,
+            (false, 0) => GenotypeClass::HomRef,
+            (false, _) => GenotypeClass::HomAlt,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for GenotypeClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            GenotypeClass::HomRef => "HOM_REF",
+            GenotypeClass::Het => "HET",
+            GenotypeClass::HomAlt => "HOM_ALT",
+            GenotypeClass::Missing => "MISSING",
+        })
+    }
+}
+
+/// Counts of truth genotype class by query genotype class.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConcordanceTable {
+    counts: [[u64; 4]; 4],
+}
+
+impl ConcordanceTable {
+    pub fn add(&mut self, truth: GenotypeClass, query: GenotypeClass) {
+        self.counts[truth.index()][query.index()] += 1;
+    }
+
+    pub fn get(&self, truth: GenotypeClass, query: GenotypeClass) -> u64 {
+        self.counts[truth.index()][query.index()]
+    }
+
+    /// add all counts from `other`.
+    pub fn merge(&mut self, other: &ConcordanceTable) {
+        for (row, other) in self.counts.iter_mut().zip(other.counts.iter()) {
+            for (c, o) in row.iter_mut().zip(other.iter()) {
+                *c += o;
+            }
+        }
+    }
+
+    /// the number of genotypes called in both truth and query.
+    pub fn called(&self) -> u64 {
+        self.called_pairs().map(|(_, _, n)| n).sum()
+    }
+
+    /// the fraction of genotypes called in both that agree.
+    pub fn concordance(&self) -> f64 {
+        let agree: u64 = self
+            .called_pairs()
+            .filter(|(t, q, _)| t == q)
+            .map(|(_, _, n)| n)
+            .sum();
+        ratio(agree, self.called())
+    }
+
+    /// the fraction of genotypes called in both and non-reference in either that disagree.
+    /// Unlike concordance, this is not inflated by the many sites that are hom-ref in both.
+    pub fn non_reference_discordance(&self) -> f64 {
+        let non_ref = self
+            .called_pairs()
+            .filter(|(t, q, _)| !(*t == GenotypeClass::HomRef && *q == GenotypeClass::HomRef));
+        let (mut disagree, mut total) = (0, 0);
+        for (t, q, n) in non_ref {
+            total += n;
+            if t != q {
+                disagree += n;
+            }
+        }
+        ratio(disagree, total)
+    }
+
+    fn called_pairs(&self) -> impl Iterator<Item = (GenotypeClass, GenotypeClass, u64)> + '_ {
+        let called = &GenotypeClass::ALL[..3];
+        called
+            .iter()
+            .flat_map(move |t| called.iter().map(move |q| (*t, *q, self.get(*t, *q))))
+    }
+}
+
+fn ratio(n: u64, d: u64) -> f64 {
+    if d == 0 {
+        0.0
+    } else {
+        n as f64 / d as f64
+    }
+}
+
+/// A sample whose genotype class differs between truth and query at a site.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub sample: String,
+    /// the `GT` values, `.` if the variant or the value is absent.
+    pub truth: String,
+    pub query: String,
+}
+
+/// Genotype concordance of the samples shared by a truth and a query VCF.
+#[derive(Debug)]
+pub struct Concordance {
+    samples: Vec<String>,
+    // the index of each shared sample in the truth and query headers.
+    columns: Vec<(usize, usize)>,
+    tables: Vec<ConcordanceTable>,
+}
+
+impl Concordance {
+    /// compare the samples found in both headers, in the order of the truth header.
+    pub fn new(truth: &vcf::Header, query: &vcf::Header) -> Self {
+        let mut samples = Vec::new();
+        let mut columns = Vec::new();
+        for (i, name) in truth.sample_names().iter().enumerate() {
+            if let Some(j) = query.sample_names().get_index_of(name) {
+                samples.push(String::from(name.as_str()));
+                columns.push((i, j));
+            }
+        }
+        let tables = vec![ConcordanceTable::default(); samples.len()];
+        Concordance {
+            samples,
+            columns,
+            tables,
+        }
+    }
+
+    /// the names of the shared samples.
+    pub fn samples(&self) -> &[String] {
+        &self.samples
+    }
+
+    /// one table per shared sample.
+    pub fn tables(&self) -> &[ConcordanceTable] {
+        &self.tables
+    }
+
+    /// the counts summed over all samples.
+    pub fn total(&self) -> ConcordanceTable {
+        let mut total = ConcordanceTable::default();
+        for t in self.tables.iter() {
+            total.merge(t);
+        }
+        total
+    }
+
+    /// count the genotypes of a site. A variant found in only one VCF is compared to
+    /// missing genotypes in the other. Returns the samples whose classes differ.
+    pub fn add_site(
+        &mut self,
+        truth: Option<&dyn Positioned>,
+        query: Option<&dyn Positioned>,
+    ) -> result::Result<Vec<Mismatch>, FieldError> {
+        let truth = genotypes(truth)?;
+        let query = genotypes(query)?;
+        Ok(self.add_genotypes(truth, query))
+    }
+
+    fn add_genotypes(
+        &mut self,
+        truth: Vec<Option<String>>,
+        query: Vec<Option<String>>,
+    ) -> Vec<Mismatch> {
+        let mut mismatches = Vec::new();
+        for (k, (i, j)) in self.columns.iter().enumerate() {
+            let t = truth.get(*i).cloned().flatten();
+            let q = query.get(*j).cloned().flatten();
+            let class = |gt: &Option<String>| {
+                gt.as_deref()
+                    .map(GenotypeClass::parse)
+                    .unwrap_or(GenotypeClass::Missing)
+            };
+            let (tc, qc) = (class(&t), class(&q));
+            self.tables[k].add(tc, qc);
+            if tc != qc {
+                mismatches.push(Mismatch {
+                    sample: self.samples[k].clone(),
+                    truth: t.unwrap_or_else(|| String::from(".")),
+                    query: q.unwrap_or_else(|| String::from(".")),
+                });
+            }
+        }
+        mismatches
+    }
+
+    /// count the genotypes of every variant in `truth` and `query`, which must be sorted by
+    /// `chromosome_order`. Variants at the same position that share an ALT allele are
+    /// compared with each other and the others with missing genotypes, so a variant with a
+    /// different ALT at the same position counts as a separate site. `found` is called with
+    /// each variant and the samples whose classes differ at it.
+    pub fn compare<F>(
+        &mut self,
+        truth: DynPositionedIterator,
+        query: DynPositionedIterator,
+        chromosome_order: &HashMap<String, usize>,
+        mut found: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&dyn Positioned, Vec<Mismatch>) -> Result<()>,
+    {
+        let mut inputs = [Input::new(truth, 0), Input::new(query, 1)];
+        for input in inputs.iter_mut() {
+            input.advance(chromosome_order)?;
+        }
+        let mut site: Vec<Variant> = Vec::new();
+        loop {
+            // the input with the earlier next variant; the truth on ties.
+            let id = match (&inputs[0].next, &inputs[1].next) {
+                (None, None) => break,
+                (Some(_), None) => 0,
+                (None, Some(_)) => 1,
+                (Some(t), Some(q)) => usize::from(q.key() < t.key()),
+            };
+            let variant = inputs[id].next.take().expect("input has a next variant");
+            inputs[id].advance(chromosome_order)?;
+            if site.first().is_some_and(|s| s.key() != variant.key()) {
+                self.add_position(&site, &inputs, &mut found)?;
+                for v in site.drain(..) {
+                    inputs[v.id].iter.recycle(v.position);
+                }
+            }
+            site.push(variant);
+        }
+        if !site.is_empty() {
+            self.add_position(&site, &inputs, &mut found)?;
+        }
+        Ok(())
+    }
+
+    /// count the variants of `truth` and `query` that start at the same position.
+    fn add_position<F>(
+        &mut self,
+        variants: &[Variant],
+        inputs: &[Input; 2],
+        found: &mut F,
+    ) -> Result<()>
+    where
+        F: FnMut(&dyn Positioned, Vec<Mismatch>) -> Result<()>,
+    {
+        let located = |v: &Variant, e: FieldError| {
+            Error::field(&inputs[v.id].iter.name(), v.line, v.position.as_ref(), e)
+        };
+        let mut query = Vec::new();
+        for v in variants.iter().filter(|v| v.id == 1) {
+            let a = alleles(v.position.as_ref()).map_err(|e| located(v, e))?;
+            query.push(Some((v, a)));
+        }
+        for v in variants.iter().filter(|v| v.id == 0) {
+            let truth_alleles = alleles(v.position.as_ref()).map_err(|e| located(v, e))?;
+            // each query variant is compared with at most one truth variant.
+            let matched = query
+                .iter_mut()
+                .find(|q| matches!(q, Some((_, a)) if share_allele(a, &truth_alleles)))
+                .and_then(|q| q.take())
+                .map(|(q, _)| q);
+            let truth = genotypes(Some(v.position.as_ref())).map_err(|e| located(v, e))?;
+            let query = match matched {
+                Some(q) => genotypes(Some(q.position.as_ref())).map_err(|e| located(q, e))?,
+                None => Vec::new(),
+            };
+            let mismatches = self.add_genotypes(truth, query);
+            found(v.position.as_ref(), mismatches)?;
+        }
+        for (v, _) in query.into_iter().flatten() {
+            let query = genotypes(Some(v.position.as_ref())).map_err(|e| located(v, e))?;
+            let mismatches = self.add_genotypes(Vec::new(), query);
+            found(v.position.as_ref(), mismatches)?;
+        }
+        Ok(())
+    }
+}
+
+/// A variant read by `Concordance::compare`; `id` is 0 for truth and 1 for query.
+struct Variant {
+    position: Box<dyn Positioned>,
+    id: usize,
+    chrom_id: usize,
+    // the record number in its input, to locate errors.
+    line: u64,
+}
+
+impl Variant {
+    fn key(&self) -> (usize, u64) {
+        (self.chrom_id, self.position.start())
+    }
+}
+
+/// One of the inputs of `Concordance::compare` and the next variant read from it.
+struct Input {
+    iter: DynPositionedIterator,
+    id: usize,
+    next: Option<Variant>,
+    lines: u64,
+    // the chromosome and start of the last variant read, to check the order.
+    previous: Option<(usize, u64, String)>,
+}
+
+impl Input {
+    fn new(iter: DynPositionedIterator, id: usize) -> Self {
+        Input {
+            iter,
+            id,
+            next: None,
+            lines: 0,
+            previous: None,
+        }
+    }
+
+    /// read the next variant, checking that the input is sorted.
+    fn advance(&mut self, chromosome_order: &HashMap<String, usize>) -> Result<()> {
+        let position = match self.iter.next_position(None) {
+            Some(p) => p?,
+            None => return Ok(()),
+        };
+        self.lines += 1;
+        let chrom_id =
+            *chromosome_order
+                .get(position.chrom())
+                .ok_or_else(|| Error::UnknownChromosome {
+                    name: self.iter.name(),
+                    chrom: String::from(position.chrom()),
+                })?;
+        if let Some((previous_chrom, previous_start, chrom)) = &self.previous {
+            if (chrom_id, position.start()) < (*previous_chrom, *previous_start) {
+                return Err(Error::OutOfOrder {
+                    name: self.iter.name(),
+                    line: self.lines,
+                    kind: if chrom_id == *previous_chrom {
+                        ViolationKind::Start
+                    } else {
+                        ViolationKind::ChromosomeOrder
+                    },
+                    previous: to_string_type(format!("{}:{}", chrom, previous_start + 1)),
+                    current: to_string_type(format!(
+                        "{}:{}",
+                        position.chrom(),
+                        position.start() + 1
+                    )),
+                });
+            }
+        }
+        match &mut self.previous {
+            Some((c, s, _)) if *c == chrom_id => *s = position.start(),
+            _ => self.previous = Some((chrom_id, position.start(), String::from(position.chrom()))),
+        }
+        self.next = Some(Variant {
+            position,
+            id: self.id,
+            chrom_id,
+            line: self.lines,
+        });
+        Ok(())
+    }
+}
+
+/// true if any ALT allele is in both `a` and `b`.
+fn share_allele(a: &[Option<Allele>], b: &[Option<Allele>]) -> bool {
+    a.iter()
+        .flatten()
+        .any(|x| b.iter().flatten().any(|y| x == y))
+}
+
+/// the `GT` of each sample; empty if there is no variant or no `GT` field.
+fn genotypes(p: Option<&dyn Positioned>) -> result::Result<Vec<Option<String>>, FieldError> {
+    let p = match p {
+        Some(p) => p,
+        None => return Ok(Vec::new()),
+    };
+    match p.value(Field::String(String::from("FORMAT.GT"))) {
+        Ok(Value::Strings(v)) => Ok(v),
+        Ok(_) | Err(FieldError::InvalidFieldName(_)) => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bedder_vcf::{vcf_from_str, BedderVCF};
+    use crate::position::PositionedIterator;
+
+    fn vcf(samples: &str, records: &str) -> BedderVCF<'static> {
+        let text = format!("##fileformat=VCFv4.3\n##FORMAT=<ID=GT,Number=1,Type=String,Description=\"GT\">\n#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\t{}\n{}", samples, records);
+        vcf_from_str(&text, "t.vcf")
+    }
+
+    #[test]
+    fn test_genotype_class() {
+        use GenotypeClass::*;
+        assert_eq!(GenotypeClass::parse("0/0"), HomRef);
+        assert_eq!(GenotypeClass::parse("0|1"), Het);
+        assert_eq!(GenotypeClass::parse("1/2"), Het);
+        assert_eq!(GenotypeClass::parse("2/2"), HomAlt);
+        assert_eq!(GenotypeClass::parse("1"), HomAlt);
+        assert_eq!(GenotypeClass::parse("./."), Missing);
+        assert_eq!(GenotypeClass::parse("./1"), Missing);
+        assert_eq!(GenotypeClass::parse("."), Missing);
+    }
+
+    #[test]
+    fn test_table() {
+        use GenotypeClass::*;
+        let mut t = ConcordanceTable::default();
+        for _ in 0..6 {
+            t.add(HomRef, HomRef);
+        }
+        t.add(Het, Het);
+        t.add(HomAlt, HomAlt);
+        t.add(Het, HomAlt);
+        t.add(HomAlt, Missing);
+        assert_eq!(t.called(), 9);
+        assert!((t.concordance() - 8.0 / 9.0).abs() < 1e-9);
+        // the 6 hom-ref pairs are left out.
+        assert!((t.non_reference_discordance() - 1.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_add_site() {
+        let mut truth = vcf(
+            "s1\ts2\ts3",
+            "chr1\t10\t.\tA\tT\t.\t.\t.\tGT\t0/1\t1/1\t0/0\n",
+        );
+        let mut query = vcf("s3\ts1", "chr1\t10\t.\tA\tT\t.\t.\t.\tGT\t0/1\t0|1\n");
+        let mut c = Concordance::new(truth.header(), query.header());
+        assert_eq!(c.samples(), &["s1", "s3"]);
+
+        let t = truth.next_position(None).unwrap().unwrap();
+        let q = query.next_position(None).unwrap().unwrap();
+        let mismatches = c.add_site(Some(&t), Some(&q)).unwrap();
+        assert_eq!(
+            mismatches,
+            vec![Mismatch {
+                sample: String::from("s3"),
+                truth: String::from("0/0"),
+                query: String::from("0/1"),
+            }]
+        );
+        // the variant is missing from the query.
+        let mismatches = c.add_site(Some(&t), None).unwrap();
+        assert_eq!(mismatches.len(), 2);
+
+        use GenotypeClass::*;
+        assert_eq!(c.tables()[0].get(Het, Het), 1);
+        assert_eq!(c.tables()[0].get(Het, Missing), 1);
+        assert_eq!(c.tables()[1].get(HomRef, Het), 1);
+        assert_eq!(c.total().called(), 2);
+    }
+
+    #[test]
+    fn test_compare() {
+        use crate::position::BoxPositioned;
+        use GenotypeClass::*;
+        let truth = vcf(
+            "s1",
+            "chr1\t10\t.\tA\tT\t.\t.\t.\tGT\t0/1\nchr1\t20\t.\tC\tG\t.\t.\t.\tGT\t1/1\n",
+        );
+        // a different ALT at 10, a multi-allelic record sharing G at 20 and a query-only 30.
+        let query = vcf(
+            "s1",
+            "chr1\t10\t.\tA\tG\t.\t.\t.\tGT\t0/1\nchr1\t20\t.\tC\tA,G\t.\t.\t.\tGT\t2/2\nchr1\t30\t.\tT\tC\t.\t.\t.\tGT\t0/1\n",
+        );
+        let mut c = Concordance::new(truth.header(), query.header());
+        let order = HashMap::from([(String::from("chr1"), 0)]);
+        let mut sites = Vec::new();
+        c.compare(
+            Box::new(BoxPositioned::new(truth)),
+            Box::new(BoxPositioned::new(query)),
+            &order,
+            |site, found| {
+                let m: Vec<_> = found
+                    .iter()
+                    .map(|m| (m.truth.clone(), m.query.clone()))
+                    .collect();
+                sites.push((
+                    site.start(),
+                    site.line().unwrap().split('\t').nth(4).unwrap().to_string(),
+                    m,
+                ));
+                Ok(())
+            },
+        )
+        .unwrap();
+        let dot = || String::from(".");
+        let gt = |s: &str| String::from(s);
+        assert_eq!(
+            sites,
+            vec![
+                (9, "T".to_string(), vec![(gt("0/1"), dot())]),
+                (9, "G".to_string(), vec![(dot(), gt("0/1"))]),
+                (19, "G".to_string(), vec![]),
+                (29, "C".to_string(), vec![(dot(), gt("0/1"))]),
+            ]
+        );
+        let t = &c.tables()[0];
+        assert_eq!(t.get(Het, Het), 0);
+        assert_eq!(t.get(Het, Missing), 1);
+        assert_eq!(t.get(Missing, Het), 2);
+        assert_eq!(t.get(HomAlt, HomAlt), 1);
+    }
+
+    #[test]
+    fn test_compare_out_of_order() {
+        use crate::position::BoxPositioned;
+        let truth = vcf("s1", "chr1\t10\t.\tA\tT\t.\t.\t.\tGT\t0/1\n");
+        let query = vcf(
+            "s1",
+            "chr1\t20\t.\tA\tG\t.\t.\t.\tGT\t0/1\nchr1\t10\t.\tA\tT\t.\t.\t.\tGT\t0/1\n",
+        );
+        let mut c = Concordance::new(truth.header(), query.header());
+        let order = HashMap::from([(String::from("chr1"), 0)]);
+        let e = c
+            .compare(
+                Box::new(BoxPositioned::new(truth)),
+                Box::new(BoxPositioned::new(query)),
+                &order,
+                |_, _| Ok(()),
+            )
+            .unwrap_err();
+        assert!(matches!(e, Error::OutOfOrder { line: 2, .. }));
+    }
+}
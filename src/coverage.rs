@@ -0,0 +1,420 @@
+use crate::error::{Error, Result};
+use crate::order::ViolationKind;
+use crate::position::{Field, FieldError, Positioned, PositionedIterator, Value};
+use crate::string::{to_string_type, String};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::io;
+use std::result;
+
+/// A run of bases with the same depth, as in a bedGraph line.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Segment {
+    pub chrom: String,
+    pub start: u64,
+    pub stop: u64,
+    pub depth: u64,
+}
+
+impl Positioned for Segment {
+    fn chrom(&self) -> &str {
+        &self.chrom
+    }
+
+    fn start(&self) -> u64 {
+        self.start
+    }
+
+    fn stop(&self) -> u64 {
+        self.stop
+    }
+
+    fn value(&self, f: Field) -> result::Result<Value, FieldError> {
+        match f {
+            Field::Int(0) => Ok(Value::Strings(vec![Some(self.chrom.clone())])),
+            Field::Int(1) => Ok(Value::Ints(vec![Some(self.start as i64)])),
+            Field::Int(2) => Ok(Value::Ints(vec![Some(self.stop as i64)])),
+            Field::Int(3) => Ok(Value::Ints(vec![Some(self.depth as i64)])),
+            Field::Int(i) => Err(FieldError::InvalidFieldIndex(i)),
+            Field::String(s) if s == "depth" => Ok(Value::Ints(vec![Some(self.depth as i64)])),
+            Field::String(s) => Err(FieldError::InvalidFieldName(s)),
+        }
+    }
+}
+
+/// An iterator over the depth of a sorted iterator of intervals (like bedtools genomecov
+/// -bg). Adjacent bases with the same depth are returned as one segment. Without a genome
+/// only covered bases are returned; with one, uncovered bases are returned with depth 0
+/// (like -bga), including chromosomes without any intervals.
+pub struct Coverage<I: PositionedIterator> {
+    inner: I,
+    // chromosomes and lengths, if uncovered bases are reported.
+    genome: Option<Vec<(String, u64)>>,
+    // the genome order, or without a genome, the order in which chromosomes were seen.
+    chrom_order: HashMap<String, usize>,
+    // index of the current chromosome in `genome`.
+    chrom_id: usize,
+    chrom: Option<String>,
+    // the last finished chromosome, to report errors.
+    previous: Option<String>,
+    // the number of intervals read, to locate errors.
+    lines: u64,
+    length: u64,
+    // depth is known up to here.
+    pos: u64,
+    last_start: u64,
+    // ends of the intervals that cover `pos`.
+    ends: BinaryHeap<Reverse<u64>>,
+    // an interval that was read but not yet used.
+    next: Option<I::Item>,
+    done: bool,
+    // a segment that may be extended by the next one.
+    pending: Option<Segment>,
+}
+
+impl<I: PositionedIterator> Coverage<I> {
+    pub fn new(inner: I) -> Self {
+        Coverage {
+            inner,
+            genome: None,
+            chrom_order: HashMap::new(),
+            chrom_id: 0,
+            chrom: None,
+            previous: None,
+            lines: 0,
+            length: u64::MAX,
+            pos: 0,
+            last_start: 0,
+            ends: BinaryHeap::new(),
+            next: None,
+            done: false,
+            pending: None,
+        }
+    }
+
+    /// report uncovered bases of the chromosomes in `genome`, which gives the chromosome
+    /// order and lengths. Intervals past the end of a chromosome are clipped.
+    pub fn with_genome(inner: I, genome: Vec<(String, u64)>) -> Self {
+        let mut c = Coverage::new(inner);
+        c.chrom_order = genome
+            .iter()
+            .enumerate()
+            .map(|(i, (chrom, _))| (chrom.clone(), i))
+            .collect();
+        c.genome = Some(genome);
+        c
+    }
+
+    fn fill(&mut self) -> Result<()> {
+        if self.next.is_none() && !self.done {
+            match self.inner.next_position(None) {
+                None => self.done = true,
+                Some(p) => {
+                    self.next = Some(p?);
+                    self.lines += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// start the next chromosome. Returns false if there are none left.
+    fn start_chrom(&mut self) -> Result<bool> {
+        if let Some(p) = &self.next {
+            if self.genome.is_none() && !self.chrom_order.contains_key(p.chrom()) {
+                self.chrom_order
+                    .insert(String::from(p.chrom()), self.chrom_id);
+            }
+            match self.chrom_order.get(p.chrom()) {
+                None => {
+                    return Err(Error::UnknownChromosome {
+                        name: self.inner.name(),
+                        chrom: String::from(p.chrom()),
+                    })
+                }
+                Some(i) if *i < self.chrom_id => {
+                    return Err(Error::OutOfOrder {
+                        name: self.inner.name(),
+                        line: self.lines,
+                        kind: match self.genome {
+                            Some(_) => ViolationKind::ChromosomeOrder,
+                            None => ViolationKind::ChromosomeRevisited,
+                        },
+                        previous: self.previous.clone().unwrap_or_default(),
+                        current: String::from(p.chrom()),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        let (chrom, length) = match &self.genome {
+            Some(genome) => match genome.get(self.chrom_id) {
+                Some((chrom, length)) => (chrom.clone(), *length),
+                None => return Ok(false),
+            },
+            None => match &self.next {
+                Some(p) => (String::from(p.chrom()), u64::MAX),
+                None => return Ok(false),
+            },
+        };
+        self.chrom = Some(chrom);
+        self.length = length;
+        self.pos = 0;
+        self.last_start = 0;
+        Ok(true)
+    }
+
+    /// the next segment, which may have the same depth as the last.
+    fn next_segment(&mut self) -> Result<Option<Segment>> {
+        loop {
+            self.fill()?;
+            if self.chrom.is_none() && !self.start_chrom()? {
+                return Ok(None);
+            }
+            let chrom = self.chrom.as_ref().expect("a chromosome was started");
+            let next_start = match &self.next {
+                Some(p) if p.chrom() == chrom.as_str() => Some(p.start()),
+                _ => None,
+            };
+            if let Some(start) = next_start {
+                if start < self.last_start {
+                    return Err(Error::OutOfOrder {
+                        name: self.inner.name(),
+                        line: self.lines,
+                        kind: ViolationKind::Start,
+                        previous: to_string_type(format!("{}:{}", chrom, self.last_start + 1)),
+                        current: to_string_type(format!("{}:{}", chrom, start + 1)),
+                    });
+                }
+                if start <= self.pos || self.pos >= self.length {
+                    let p = self.next.take().expect("next has a start");
+                    self.last_start = start;
+                    if p.stop() > start && start < self.length {
+                        self.ends.push(Reverse(p.stop().min(self.length)));
+                    }
+                    self.inner.recycle(p);
+                    continue;
+                }
+            }
+            match self.ends.peek() {
+                Some(Reverse(end)) if *end <= self.pos => {
+                    self.ends.pop();
+                    continue;
+                }
+                _ => {}
+            }
+            let end = self.ends.peek().map(|Reverse(end)| *end);
+            let event = match (next_start, end) {
+                (Some(s), Some(e)) => s.min(e),
+                (Some(s), None) => s,
+                (None, Some(e)) => e,
+                // nothing more on this chromosome, except uncovered bases if there is a genome.
+                (None, None) if self.genome.is_some() => self.length,
+                (None, None) => self.pos,
+            }
+            .min(self.length);
+            if event <= self.pos {
+                // the chromosome is finished.
+                self.previous = self.chrom.take();
+                self.chrom_id += 1;
+                continue;
+            }
+            let segment = Segment {
+                chrom: chrom.clone(),
+                start: self.pos,
+                stop: event,
+                depth: self.ends.len() as u64,
+            };
+            self.pos = event;
+            if segment.depth > 0 || self.genome.is_some() {
+                return Ok(Some(segment));
+            }
+        }
+    }
+}
+
+impl<I: PositionedIterator> PositionedIterator for Coverage<I> {
+    type Item = Segment;
+
+    fn next_position(&mut self, _q: Option<&dyn Positioned>) -> Option<Result<Self::Item>> {
+        loop {
+            match self.next_segment() {
+                Err(e) => return Some(Err(e)),
+                Ok(None) => return self.pending.take().map(Ok),
+                Ok(Some(s)) => match &mut self.pending {
+                    Some(p) if p.chrom == s.chrom && p.stop == s.start && p.depth == s.depth => {
+                        p.stop = s.stop
+                    }
+                    _ => {
+                        if let Some(p) = self.pending.replace(s) {
+                            return Some(Ok(p));
+                        }
+                    }
+                },
+            }
+        }
+    }
+
+    fn name(&self) -> String {
+        to_string_type(format!("coverage of {}", self.inner.name()))
+    }
+}
+
+/// The number of bases at each depth per chromosome and across the genome.
+#[derive(Debug, Default)]
+pub struct Histogram {
+    chroms: Vec<(String, BTreeMap<u64, u64>)>,
+}
+
+impl Histogram {
+    pub fn add(&mut self, s: &Segment) {
+        match self.chroms.last_mut() {
+            Some((chrom, _)) if *chrom == s.chrom => {}
+            _ => self.chroms.push((s.chrom.clone(), BTreeMap::new())),
+        }
+        let (_, depths) = self.chroms.last_mut().expect("a chromosome was added");
+        *depths.entry(s.depth).or_default() += s.stop - s.start;
+    }
+
+    /// the bases at each depth for each chromosome, in the order they were added.
+    pub fn chroms(&self) -> &[(String, BTreeMap<u64, u64>)] {
+        &self.chroms
+    }
+
+    /// the bases at each depth across all chromosomes.
+    pub fn genome(&self) -> BTreeMap<u64, u64> {
+        let mut total = BTreeMap::new();
+        for (_, depths) in self.chroms.iter() {
+            for (depth, bases) in depths.iter() {
+                *total.entry(*depth).or_default() += bases;
+            }
+        }
+        total
+    }
+
+    /// write `chrom, depth, bases, total bases, fraction` lines as bedtools genomecov does,
+    /// with `genome` as the chromosome of the genome-wide lines.
+    pub fn write<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        let genome = self.genome();
+        let rows = self
+            .chroms
+            .iter()
+            .map(|(chrom, depths)| (chrom.as_str(), depths))
+            .chain(std::iter::once(("genome", &genome)));
+        for (chrom, depths) in rows {
+            let total: u64 = depths.values().sum();
+            for (depth, bases) in depths.iter() {
+                writeln!(
+                    w,
+                    "{}\t{}\t{}\t{}\t{:.6}",
+                    chrom,
+                    depth,
+                    bases,
+                    total,
+                    *bases as f64 / total as f64
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bedder_bed::BedderBed;
+    use std::io::Cursor;
+
+    const BED: &str = "chr1\t10\t20\nchr1\t15\t30\nchr1\t20\t25\nchr1\t40\t50\nchr3\t0\t5\n";
+
+    fn segments<I: PositionedIterator<Item = Segment>>(mut it: I) -> Vec<(String, u64, u64, u64)> {
+        let mut v = Vec::new();
+        while let Some(s) = it.next_position(None) {
+            let s = s.unwrap();
+            v.push((s.chrom, s.start, s.stop, s.depth));
+        }
+        v
+    }
+
+    fn seg(chrom: &str, start: u64, stop: u64, depth: u64) -> (String, u64, u64, u64) {
+        (String::from(chrom), start, stop, depth)
+    }
+
+    #[test]
+    fn test_coverage() {
+        let bed = BedderBed::new(Cursor::new(BED), "t.bed");
+        // depth is 2 at 15-20 and (after one ends and one starts at 20) 20-25.
+        assert_eq!(
+            segments(Coverage::new(bed)),
+            vec![
+                seg("chr1", 10, 15, 1),
+                seg("chr1", 15, 25, 2),
+                seg("chr1", 25, 30, 1),
+                seg("chr1", 40, 50, 1),
+                seg("chr3", 0, 5, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_coverage_with_genome() {
+        let genome = vec![
+            (String::from("chr1"), 45),
+            (String::from("chr2"), 10),
+            (String::from("chr3"), 5),
+        ];
+        let bed = BedderBed::new(Cursor::new(BED), "t.bed");
+        let v = segments(Coverage::with_genome(bed, genome.clone()));
+        assert_eq!(
+            v,
+            vec![
+                seg("chr1", 0, 10, 0),
+                seg("chr1", 10, 15, 1),
+                seg("chr1", 15, 25, 2),
+                seg("chr1", 25, 30, 1),
+                seg("chr1", 30, 40, 0),
+                seg("chr1", 40, 45, 1),
+                seg("chr2", 0, 10, 0),
+                seg("chr3", 0, 5, 1),
+            ]
+        );
+
+        let mut h = Histogram::default();
+        let bed = BedderBed::new(Cursor::new(BED), "t.bed");
+        let mut c = Coverage::with_genome(bed, genome);
+        while let Some(s) = c.next_position(None) {
+            h.add(&s.unwrap());
+        }
+        assert_eq!(h.chroms()[0].1, BTreeMap::from([(0, 20), (1, 15), (2, 10)]));
+        assert_eq!(h.genome(), BTreeMap::from([(0, 30), (1, 20), (2, 10)]));
+        let mut out = Vec::new();
+        h.write(&mut out).unwrap();
+        let out = std::string::String::from_utf8(out).unwrap();
+        assert!(out.contains("chr2\t0\t10\t10\t1.000000\n"));
+        assert!(out.contains("genome\t2\t10\t60\t0.166667\n"));
+    }
+
+    #[test]
+    fn test_coverage_unsorted_chrom() {
+        let bed = BedderBed::new(Cursor::new("chr1\t0\t5\nchr2\t0\t5\nchr1\t8\t9\n"), "t.bed");
+        let mut c = Coverage::new(bed);
+        let e = loop {
+            match c.next_position(None).unwrap() {
+                Ok(_) => continue,
+                Err(e) => break e,
+            }
+        };
+        assert!(matches!(e, Error::OutOfOrder { line: 3, .. }), "{:?}", e);
+    }
+
+    #[test]
+    fn test_coverage_unknown_chrom() {
+        let bed = BedderBed::new(Cursor::new("chr1\t0\t5\nchrX\t0\t5\n"), "t.bed");
+        let mut c = Coverage::with_genome(bed, vec![(String::from("chr1"), 10)]);
+        assert!(c.next_position(None).unwrap().is_ok());
+        assert!(matches!(
+            c.next_position(None).unwrap(),
+            Err(Error::UnknownChromosome { .. })
+        ));
+    }
+}
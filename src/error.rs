@@ -0,0 +1,148 @@
+use crate::order::ViolationKind;
+use crate::position::{FieldError, Positioned};
+use crate::string::{to_string_type, String};
+use std::fmt;
+use std::io;
+use std::result;
+
+/// Errors returned by bedder readers and iterators.
+/// `name` is the name of the iterator (see `PositionedIterator::name`) that produced the error
+/// and regions are formatted as `chrom:start-stop` with 1-based start.
+#[derive(Debug)]
+pub enum Error {
+    /// An underlying I/O error.
+    Io(io::Error),
+    /// Intervals were not sorted. `previous` should have come after `current`, which is
+    /// the `line`th interval read from the iterator. `kind` is the rule that was broken.
+    OutOfOrder {
+        name: String,
+        line: u64,
+        kind: ViolationKind,
+        previous: String,
+        current: String,
+    },
+    /// An interval was on a chromosome that is not in the chromosome order (genome file).
+    UnknownChromosome { name: String, chrom: String },
+    /// A record could not be parsed. `line` is the line (or record) number, or 0 if unknown,
+    /// and `region` is the region of the record, when it is known.
+    Parse {
+        name: String,
+        line: u64,
+        region: Option<String>,
+        message: String,
+    },
+    /// A field of the `line`th interval from `name`, at `region`, could not be extracted
+    /// or had the wrong type.
+    Field {
+        name: String,
+        line: u64,
+        region: String,
+        error: FieldError,
+    },
+    /// The file format is not (yet) supported.
+    UnsupportedFormat { name: String, format: String },
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+impl Error {
+    /// wrap `error`, from the `line`th interval `p` read from `name`.
+    pub fn field<P: Positioned + ?Sized>(name: &str, line: u64, p: &P, error: FieldError) -> Self {
+        Error::Field {
+            name: String::from(name),
+            line,
+            region: to_string_type(format!("{}:{}-{}", p.chrom(), p.start() + 1, p.stop())),
+            error,
+        }
+    }
+
+    /// An `Error::Parse` for the record `p`, which is number `line` from `name`.
+    pub fn parse<P: Positioned + ?Sized>(name: &str, line: u64, p: &P, message: String) -> Self {
+        Error::Parse {
+            name: String::from(name),
+            line,
+            region: Some(to_string_type(format!(
+                "{}:{}-{}",
+                p.chrom(),
+                p.start() + 1,
+                p.stop()
+            ))),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::OutOfOrder {
+                name,
+                line,
+                kind,
+                previous,
+                current,
+            } => write!(
+                f,
+                "intervals from {} out of order at line#{} ({}): {} came before {}",
+                name, line, kind, previous, current
+            ),
+            Error::UnknownChromosome { name, chrom } => write!(
+                f,
+                "invalid chromosome: \"{}\" from {} not found in chromosome order",
+                chrom, name
+            ),
+            Error::Parse {
+                name,
+                line,
+                region,
+                message,
+            } => {
+                write!(f, "{}", name)?;
+                if *line > 0 {
+                    write!(f, " line#{}", line)?;
+                }
+                if let Some(region) = region {
+                    write!(f, " ({})", region)?;
+                }
+                write!(f, " error: {}", message)
+            }
+            Error::Field {
+                name,
+                line,
+                region,
+                error,
+            } => write!(f, "{} line#{} ({}) error: {}", name, line, region, error),
+            Error::UnsupportedFormat { name, format } => {
+                write!(f, "{}: {} not yet supported", name, format)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Field { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// Errors are converted to io::Error for callers that only deal with I/O.
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(e) => e,
+            Error::Parse { .. } => io::Error::new(io::ErrorKind::InvalidData, e),
+            e => io::Error::other(e),
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use crate::error::{Error, Result};
+use crate::string::String;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// A sequence in a FASTA index (`.fai`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FaiRecord {
+    pub length: u64,
+    /// byte offset of the first base.
+    pub offset: u64,
+    pub line_bases: u64,
+    /// bytes per line including the line ending.
+    pub line_width: u64,
+}
+
+/// read a FASTA index as made by `samtools faidx`. `name` is used in error messages.
+pub fn read_fai<R: BufRead>(reader: R, name: &str) -> Result<HashMap<String, FaiRecord>> {
+    let mut index = HashMap::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let parse_error = |message: &str| Error::Parse {
+            name: String::from(name),
+            line: i as u64 + 1,
+            region: None,
+            message: String::from(message),
+        };
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 5 {
+            return Err(parse_error("expected 5 columns"));
+        }
+        let mut numbers = [0u64; 4];
+        for (n, f) in numbers.iter_mut().zip(fields[1..5].iter()) {
+            *n = f
+                .parse()
+                .map_err(|_| parse_error(&format!("invalid number: {}", f)))?;
+        }
+        let [length, offset, line_bases, line_width] = numbers;
+        if line_bases == 0 || line_width < line_bases {
+            return Err(parse_error("invalid line length"));
+        }
+        index.insert(
+            String::from(fields[0]),
+            FaiRecord {
+                length,
+                offset,
+                line_bases,
+                line_width,
+            },
+        );
+    }
+    Ok(index)
+}
+
+/// A FASTA file with a `.fai` index, read by region.
+pub struct IndexedFasta<R> {
+    reader: R,
+    index: HashMap<String, FaiRecord>,
+    name: String,
+    buf: Vec<u8>,
+}
+
+impl IndexedFasta<io::BufReader<fs::File>> {
+    /// open `path` and its index at `path.fai`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut fai_path = path.as_os_str().to_owned();
+        fai_path.push(".fai");
+        let fai = fs::File::open(&fai_path).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("{}: {}", Path::new(&fai_path).display(), e),
+            )
+        })?;
+        let index = read_fai(
+            io::BufReader::new(fai),
+            &Path::new(&fai_path).to_string_lossy(),
+        )?;
+        let reader = io::BufReader::new(fs::File::open(path)?);
+        Ok(IndexedFasta::new(reader, index, &path.to_string_lossy()))
+    }
+}
+
+impl<R: Read + Seek> IndexedFasta<R> {
+    pub fn new(reader: R, index: HashMap<String, FaiRecord>, name: &str) -> Self {
+        IndexedFasta {
+            reader,
+            index,
+            name: String::from(name),
+            buf: Vec::new(),
+        }
+    }
+
+    /// the length of `chrom`, if it is in the index.
+    pub fn length(&self, chrom: &str) -> Option<u64> {
+        self.index.get(chrom).map(|r| r.length)
+    }
+
+    /// get the bases of `chrom` from 0-based `start` up to `stop`, which is clamped to the
+    /// length of the sequence.
+    pub fn fetch(&mut self, chrom: &str, start: u64, stop: u64) -> Result<Vec<u8>> {
+        let r = self.index.get(chrom).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} not found in {}", chrom, self.name),
+            )
+        })?;
+        let stop = stop.min(r.length);
+        if start >= stop {
+            return Ok(Vec::new());
+        }
+        let byte = |i: u64| r.offset + (i / r.line_bases) * r.line_width + i % r.line_bases;
+        let (first, last) = (byte(start), byte(stop - 1) + 1);
+        self.reader.seek(SeekFrom::Start(first))?;
+        self.buf.resize((last - first) as usize, 0);
+        self.reader.read_exact(&mut self.buf)?;
+        Ok(self
+            .buf
+            .iter()
+            .copied()
+            .filter(|b| *b != b'\n' && *b != b'\r')
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_fetch() {
+        let fasta = ">chr1\nACGTA\nCGTAC\nGT\n>chr2 description\r\nTTTT\r\nGG\r\n";
+        let fai = "chr1\t12\t6\t5\t6\nchr2\t6\t40\t4\t6\n";
+        let index = read_fai(fai.as_bytes(), "t.fa.fai").unwrap();
+        let mut f = IndexedFasta::new(Cursor::new(fasta), index, "t.fa");
+        assert_eq!(f.length("chr1"), Some(12));
+        assert_eq!(f.fetch("chr1", 0, 12).unwrap(), b"ACGTACGTACGT");
+        assert_eq!(f.fetch("chr1", 3, 7).unwrap(), b"TACG");
+        assert_eq!(f.fetch("chr1", 10, 100).unwrap(), b"GT");
+        assert_eq!(f.fetch("chr2", 2, 6).unwrap(), b"TTGG");
+        assert!(f.fetch("chr1", 12, 20).unwrap().is_empty());
+        assert!(f.fetch("chr3", 0, 1).is_err());
+        assert!(read_fai("chr1\t12\n".as_bytes(), "t.fa.fai").is_err());
+    }
+}
@@ -24,6 +24,34 @@ where
     Ok(genome)
 }
 
+/// read the chromosomes and their lengths from a genome file (or `.fai`), in file order.
+pub fn parse_genome_lengths<R>(reader: R) -> io::Result<Vec<(String, u64)>>
+where
+    R: Read,
+{
+    let reader = io::BufReader::new(reader);
+    let mut genome = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let chrom = fields.next().unwrap_or_default();
+        let length = fields
+            .next()
+            .and_then(|l| l.parse::<u64>().ok())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("genome file line {}: expected a chromosome length", i + 1),
+                )
+            })?;
+        genome.push((String::from(chrom), length));
+    }
+    Ok(genome)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -37,4 +65,15 @@ mod tests {
         assert_eq!(genome.get("chr2"), Some(&1));
         assert_eq!(genome.get("chr3"), Some(&2));
     }
+
+    #[test]
+    fn test_parse_genome_lengths() {
+        let genome =
+            parse_genome_lengths("chr2\t100\n#comment\nchr1 50\textra\n".as_bytes()).unwrap();
+        assert_eq!(
+            genome,
+            vec![(String::from("chr2"), 100), (String::from("chr1"), 50)]
+        );
+        assert!(parse_genome_lengths("chr1\n".as_bytes()).is_err());
+    }
 }
@@ -0,0 +1,539 @@
+use crate::error::{Error, Result};
+use crate::order::ViolationKind;
+use crate::string::{to_string_type, String};
+use noodles::bgzf;
+use noodles::core::Position;
+use noodles::csi::{
+    self,
+    index::{
+        header::{format::CoordinateSystem, Format, ReferenceSequenceNames},
+        reference_sequence::bin::Chunk,
+        Header,
+    },
+};
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The type of index to build for BGZF files.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IndexFormat {
+    /// A tabix (.tbi) index. Positions are limited to 2^29.
+    Tabix,
+    /// A CSI (.csi) index with the given minimum interval size (as a bit shift) in
+    /// `MIN_SHIFT_RANGE`.
+    Csi { min_shift: u8 },
+}
+
+/// the default min_shift for CSI indexes (as used by htslib).
+pub const DEFAULT_MIN_SHIFT: u8 = 14;
+
+/// CSI indexes are deep enough to cover positions up to 2^MAX_POSITION_BITS.
+const MAX_POSITION_BITS: u8 = 32;
+
+/// the min_shift values that can be written to a CSI index. Smaller values need a depth over 9
+/// to reach 2^MAX_POSITION_BITS, which overflows the (i32) bin ids.
+pub const MIN_SHIFT_RANGE: std::ops::RangeInclusive<u8> = 5..=31;
+
+const TABIX_MIN_SHIFT: u8 = 14;
+const TABIX_DEPTH: u8 = 5;
+
+impl IndexFormat {
+    /// the file extension (without the dot) of this index.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            IndexFormat::Tabix => "tbi",
+            IndexFormat::Csi { .. } => "csi",
+        }
+    }
+
+    /// the path of the index for the data file at `path`, e.g. `x.bed.gz.tbi`.
+    pub fn index_path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        let mut p = path.as_ref().as_os_str().to_owned();
+        p.push(".");
+        p.push(self.extension());
+        PathBuf::from(p)
+    }
+
+    /// the min_shift and depth of the binning index.
+    fn shape(&self) -> (u8, u8) {
+        match *self {
+            IndexFormat::Tabix => (TABIX_MIN_SHIFT, TABIX_DEPTH),
+            IndexFormat::Csi { min_shift } => {
+                // each level covers 8 times the bins of the level below it.
+                let mut depth = 1;
+                while min_shift + 3 * depth < MAX_POSITION_BITS {
+                    depth += 1;
+                }
+                (min_shift, depth)
+            }
+        }
+    }
+
+    /// write `index` to `path` in this format.
+    pub fn write<P: AsRef<Path>>(&self, path: P, index: &csi::Index) -> io::Result<()> {
+        let f = std::fs::File::create(&path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.as_ref().display(), e)))?;
+        self.write_to(f, index)
+    }
+
+    /// write `index` to `w` in this format.
+    pub fn write_to<W: Write>(&self, w: W, index: &csi::Index) -> io::Result<()> {
+        match self {
+            IndexFormat::Tabix => {
+                let mut w = noodles::tabix::Writer::new(w);
+                w.write_index(index)?;
+                w.try_finish()
+            }
+            IndexFormat::Csi { .. } => {
+                let mut w = bgzf::Writer::new(w);
+                write_csi(&mut w, index)?;
+                w.finish().map(|_| ())
+            }
+        }
+    }
+
+    /// read an index in this format from `path`.
+    pub fn read<P: AsRef<Path>>(&self, path: P) -> io::Result<csi::Index> {
+        let f = std::fs::File::open(&path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.as_ref().display(), e)))?;
+        self.read_from(f)
+    }
+
+    /// the largest position (exclusive) that can be indexed in this format.
+    pub fn max_position(&self) -> u64 {
+        let (min_shift, depth) = self.shape();
+        1 << (min_shift + 3 * depth)
+    }
+
+    /// read an index in this format from `r`.
+    pub fn read_from<R: Read>(&self, r: R) -> io::Result<csi::Index> {
+        match self {
+            IndexFormat::Tabix => noodles::tabix::Reader::new(r).read_index(),
+            IndexFormat::Csi { .. } => {
+                let index = csi::Reader::new(r).read_index()?;
+                // noodles-csi 0.20 reads the (1-based) columns of the header as 0-based.
+                let header = index.header().map(|h| {
+                    Header::builder()
+                        .set_format(h.format())
+                        .set_reference_sequence_name_index(
+                            h.reference_sequence_name_index().saturating_sub(1),
+                        )
+                        .set_start_position_index(h.start_position_index().saturating_sub(1))
+                        .set_end_position_index(h.end_position_index().map(|i| i - 1))
+                        .set_line_comment_prefix(h.line_comment_prefix())
+                        .set_line_skip_count(h.line_skip_count())
+                        .set_reference_sequence_names(h.reference_sequence_names().clone())
+                        .build()
+                });
+                Ok(rebuild(&index, index.min_shift(), index.depth(), header))
+            }
+        }
+    }
+}
+
+/// Parse "tbi" (or "tabix") and "csi".
+impl FromStr for IndexFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "tbi" | "tabix" => Ok(IndexFormat::Tabix),
+            "csi" => Ok(IndexFormat::Csi {
+                min_shift: DEFAULT_MIN_SHIFT,
+            }),
+            _ => Err(to_string_type(format!(
+                "unknown index format: {}. expected tbi or csi",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for IndexFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+/// Indexer builds a tabix or CSI index from sorted records and their locations in a BGZF file.
+pub struct Indexer {
+    format: IndexFormat,
+    indexer: csi::index::Indexer,
+    header: Header,
+    names: ReferenceSequenceNames,
+    // chromosome index and start of the last record.
+    last: Option<(usize, u64)>,
+    // used in error messages.
+    name: String,
+    // the number of records added, to locate out of order records.
+    records: u64,
+}
+
+impl Indexer {
+    /// `header` describes the columns of the indexed file
+    /// (e.g. `csi::index::header::Builder::bed().build()`); chromosome names are filled in as
+    /// records are added. `name` (e.g. the file path) is used in error messages.
+    pub fn new(format: IndexFormat, header: Header, name: &str) -> Self {
+        let (min_shift, depth) = format.shape();
+        Indexer {
+            format,
+            indexer: csi::index::Indexer::new(min_shift, depth),
+            header,
+            names: ReferenceSequenceNames::new(),
+            last: None,
+            name: String::from(name),
+            records: 0,
+        }
+    }
+
+    pub fn format(&self) -> IndexFormat {
+        self.format
+    }
+
+    /// add a record with 0-based, half-open coordinates stored between the virtual positions
+    /// of `chunk`. Records must be sorted with each chromosome in a single block.
+    pub fn add_record(&mut self, chrom: &str, start: u64, stop: u64, chunk: Chunk) -> Result<()> {
+        self.records += 1;
+        let (id, new) = match self.names.get_index_of(chrom) {
+            Some(id) => (id, false),
+            None => (self.names.insert_full(chrom.into()).0, true),
+        };
+        if let Some((last_id, last_start)) = self.last {
+            if (id != last_id && !new) || (id == last_id && start < last_start) {
+                return Err(Error::OutOfOrder {
+                    name: self.name.clone(),
+                    line: self.records,
+                    kind: if id == last_id {
+                        ViolationKind::Start
+                    } else {
+                        ViolationKind::ChromosomeRevisited
+                    },
+                    previous: to_string_type(format!("{}:{}", self.names[last_id], last_start + 1)),
+                    current: to_string_type(format!("{}:{}", chrom, start + 1)),
+                });
+            }
+        }
+        self.last = Some((id, start));
+
+        if stop > self.format.max_position() {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{}: {}:{} is past the largest position ({}) of a {} index. use a CSI index",
+                    self.name,
+                    chrom,
+                    stop,
+                    self.format.max_position(),
+                    self.format
+                ),
+            )));
+        }
+        // index positions are 1-based and inclusive. empty intervals cover one base.
+        let invalid = |e| io::Error::new(io::ErrorKind::InvalidInput, e);
+        let first = Position::try_from(start as usize + 1).map_err(invalid)?;
+        let last = Position::try_from(stop.max(start + 1) as usize).map_err(invalid)?;
+        self.indexer
+            .add_record(Some((id, first, last, true)), chunk)?;
+        Ok(())
+    }
+
+    pub fn build(mut self) -> csi::Index {
+        let n = self.names.len();
+        *self.header.reference_sequence_names_mut() = self.names;
+        // noodles-csi only adds the reference sequences *before* the last id given to
+        // `build`, which would drop the last chromosome, so we ask for one more.
+        let index = self.indexer.set_header(self.header).build(n + 1);
+        // it also leaves min_shift and depth at the tabix defaults, so set them here.
+        let (min_shift, depth) = self.format.shape();
+        let header = index.header().cloned();
+        rebuild(&index, min_shift, depth, header)
+    }
+}
+
+/// copy `index` with the given shape and header.
+fn rebuild(index: &csi::Index, min_shift: u8, depth: u8, header: Option<Header>) -> csi::Index {
+    let mut builder = csi::Index::builder()
+        .set_min_shift(min_shift)
+        .set_depth(depth)
+        .set_reference_sequences(index.reference_sequences().to_vec());
+    if let Some(header) = header {
+        builder = builder.set_header(header);
+    }
+    if let Some(n) = index.unplaced_unmapped_record_count() {
+        builder = builder.set_unplaced_unmapped_record_count(n);
+    }
+    builder.build()
+}
+
+/// write a CSI index (uncompressed; the caller wraps `w` in BGZF).
+/// noodles-csi 0.20 writes the start of each chunk in place of its end and writes the header
+/// columns 0-based, so we write the format ourselves.
+fn write_csi<W: Write>(w: &mut W, index: &csi::Index) -> io::Result<()> {
+    let invalid = |e| io::Error::new(io::ErrorKind::InvalidInput, e);
+    let i32_bytes = |n: usize| i32::try_from(n).map(i32::to_le_bytes).map_err(invalid);
+
+    w.write_all(b"CSI\x01")?;
+    w.write_all(&i32::from(index.min_shift()).to_le_bytes())?;
+    w.write_all(&i32::from(index.depth()).to_le_bytes())?;
+
+    // the aux data is a tabix-style header (with 1-based columns) so text files can be queried.
+    let mut aux = Vec::new();
+    if let Some(h) = index.header() {
+        aux.extend_from_slice(&i32::from(h.format()).to_le_bytes());
+        aux.extend_from_slice(&i32_bytes(h.reference_sequence_name_index() + 1)?);
+        aux.extend_from_slice(&i32_bytes(h.start_position_index() + 1)?);
+        aux.extend_from_slice(&i32_bytes(h.end_position_index().map_or(0, |i| i + 1))?);
+        aux.extend_from_slice(&i32::from(h.line_comment_prefix()).to_le_bytes());
+        aux.extend_from_slice(&i32_bytes(h.line_skip_count() as usize)?);
+        let names = h.reference_sequence_names();
+        aux.extend_from_slice(&i32_bytes(names.iter().map(|n| n.len() + 1).sum())?);
+        for name in names {
+            aux.extend_from_slice(name.as_bytes());
+            aux.push(0);
+        }
+    }
+    w.write_all(&i32_bytes(aux.len())?)?;
+    w.write_all(&aux)?;
+
+    let metadata_id = csi::index::reference_sequence::Bin::metadata_id(index.depth());
+    w.write_all(&i32_bytes(index.reference_sequences().len())?)?;
+    for r in index.reference_sequences() {
+        let metadata = r.metadata();
+        w.write_all(&i32_bytes(r.bins().len() + metadata.map_or(0, |_| 1))?)?;
+        let mut bins: Vec<_> = r.bins().iter().collect();
+        bins.sort_by_key(|(id, _)| **id);
+        for (&id, bin) in bins {
+            w.write_all(&u32::try_from(id).map_err(invalid)?.to_le_bytes())?;
+            w.write_all(&u64::from(bin.loffset()).to_le_bytes())?;
+            w.write_all(&i32_bytes(bin.chunks().len())?)?;
+            for chunk in bin.chunks() {
+                w.write_all(&u64::from(chunk.start()).to_le_bytes())?;
+                w.write_all(&u64::from(chunk.end()).to_le_bytes())?;
+            }
+        }
+        if let Some(m) = metadata {
+            // the pseudo-bin holds the file offsets and record counts of this chromosome.
+            w.write_all(&u32::try_from(metadata_id).map_err(invalid)?.to_le_bytes())?;
+            w.write_all(&0u64.to_le_bytes())?;
+            w.write_all(&2i32.to_le_bytes())?;
+            w.write_all(&u64::from(m.start_position()).to_le_bytes())?;
+            w.write_all(&u64::from(m.end_position()).to_le_bytes())?;
+            w.write_all(&m.mapped_record_count().to_le_bytes())?;
+            w.write_all(&m.unmapped_record_count().to_le_bytes())?;
+        }
+    }
+    if let Some(n) = index.unplaced_unmapped_record_count() {
+        w.write_all(&n.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// get the 0-based, half-open interval of a tab-delimited `line` using the columns in `header`.
+fn parse_columns<'a>(
+    line: &'a str,
+    header: &Header,
+) -> std::result::Result<(&'a str, u64, u64), String> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    let column = |i: usize| {
+        fields
+            .get(i)
+            .copied()
+            .ok_or_else(|| to_string_type(format!("missing column {}", i + 1)))
+    };
+    let number = |i: usize| -> std::result::Result<u64, String> {
+        column(i)?
+            .parse::<u64>()
+            .map_err(|e| to_string_type(format!("invalid position in column {}: {}", i + 1, e)))
+    };
+    let chrom = column(header.reference_sequence_name_index())?;
+    let start = number(header.start_position_index())?;
+    let end = match (header.format(), header.end_position_index()) {
+        // VCF records end at POS + len(REF) - 1.
+        (Format::Vcf, _) => start + column(3)?.len().max(1) as u64 - 1,
+        (_, Some(i)) => number(i)?,
+        (_, None) => match header.format().coordinate_system() {
+            CoordinateSystem::Bed => start + 1,
+            CoordinateSystem::Gff => start,
+        },
+    };
+    match header.format().coordinate_system() {
+        CoordinateSystem::Bed => Ok((chrom, start, end)),
+        CoordinateSystem::Gff => {
+            if start == 0 {
+                return Err(String::from("1-based position can not be 0"));
+            }
+            Ok((chrom, start - 1, end))
+        }
+    }
+}
+
+/// Build an index for the sorted BGZF file read by `reader`.
+/// `header` gives the columns (0-based), coordinate system, comment prefix and the number of
+/// lines to skip. `name` (e.g. the path) is used in error messages.
+pub fn index_bgzf<R: Read>(
+    reader: R,
+    name: &str,
+    format: IndexFormat,
+    header: Header,
+) -> Result<csi::Index> {
+    let mut reader = bgzf::Reader::new(reader);
+    let mut indexer = Indexer::new(format, header.clone(), name);
+    let mut line = std::string::String::new();
+    let mut line_number = 0;
+    loop {
+        line.clear();
+        let start = reader.virtual_position();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let end = reader.virtual_position();
+        line_number += 1;
+        if line_number <= header.line_skip_count() as u64
+            || line.as_bytes().first() == Some(&header.line_comment_prefix())
+        {
+            continue;
+        }
+        let text = line.trim_end_matches(['\n', '\r']);
+        if text.is_empty() {
+            continue;
+        }
+        let (chrom, first, last) =
+            parse_columns(text, &header).map_err(|message| Error::Parse {
+                name: String::from(name),
+                line: line_number,
+                region: None,
+                message,
+            })?;
+        indexer.add_record(chrom, first, last, Chunk::new(start, end))?;
+    }
+    Ok(indexer.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use noodles::csi::index::header::Builder;
+
+    fn bgzip(text: &str) -> Vec<u8> {
+        let mut w = bgzf::Writer::new(Vec::new());
+        w.write_all(text.as_bytes()).unwrap();
+        w.finish().unwrap()
+    }
+
+    fn query(data: Vec<u8>, index: csi::Index, region: &str) -> Vec<std::string::String> {
+        let region = region.parse().unwrap();
+        let mut reader = csi::io::IndexedReader::new(io::Cursor::new(data), index);
+        reader
+            .query(&region)
+            .unwrap()
+            .map(|r| r.unwrap().as_ref().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_columns() {
+        let bed = Builder::bed().build();
+        assert_eq!(parse_columns("chr1\t10\t20", &bed), Ok(("chr1", 10, 20)));
+        let gff = Builder::gff().build();
+        assert_eq!(
+            parse_columns("chr1\tsrc\tgene\t11\t20\t.\t+\t.\tID=x", &gff),
+            Ok(("chr1", 10, 20))
+        );
+        let vcf = Builder::vcf().build();
+        assert_eq!(
+            parse_columns("chr1\t11\t.\tACG\tA\t.\t.\t.", &vcf),
+            Ok(("chr1", 10, 13))
+        );
+        assert!(parse_columns("chr1\tx\t20", &bed).is_err());
+        assert!(parse_columns("chr1", &bed).is_err());
+    }
+
+    #[test]
+    fn test_index_gff() {
+        let mut text = std::string::String::from("##gff-version 3\n");
+        for i in 0..500 {
+            text.push_str(&format!(
+                "chr1\tsrc\tgene\t{}\t{}\t.\t+\t.\tID=g{}\n",
+                i * 100 + 1,
+                i * 100 + 50,
+                i
+            ));
+        }
+        let data = bgzip(&text);
+        for format in [
+            IndexFormat::Tabix,
+            IndexFormat::Csi {
+                min_shift: DEFAULT_MIN_SHIFT,
+            },
+        ] {
+            let index = index_bgzf(&data[..], "t.gff.gz", format, Builder::gff().build()).unwrap();
+            // round-trip the index through its file format.
+            let mut buf = Vec::new();
+            format.write_to(&mut buf, &index).unwrap();
+            let index = format.read_from(&buf[..]).unwrap();
+            let header = index.header().unwrap();
+            assert_eq!(header.start_position_index(), 3, "{}", format);
+            let lines = query(data.clone(), index, "chr1:20001-20010");
+            assert_eq!(lines.len(), 1, "{}", format);
+            assert!(lines[0].ends_with("ID=g200"));
+        }
+    }
+
+    #[test]
+    fn test_index_unsorted() {
+        let data = bgzip("chr1\t10\t20\nchr1\t5\t20\n");
+        let r = index_bgzf(
+            &data[..],
+            "t.bed.gz",
+            IndexFormat::Tabix,
+            Builder::bed().build(),
+        );
+        assert!(matches!(r, Err(Error::OutOfOrder { .. })));
+        let data = bgzip("chr1\t10\t20\nchr1\tx\t20\n");
+        let r = index_bgzf(
+            &data[..],
+            "t.bed.gz",
+            IndexFormat::Tabix,
+            Builder::bed().build(),
+        );
+        assert!(matches!(r, Err(Error::Parse { line: 2, .. })));
+    }
+
+    #[test]
+    fn test_index_long_chromosome() {
+        // tabix can not index past 2^29 (~536Mbp) but CSI can.
+        let data = bgzip("chr1\t10\t20\nchr1\t600000000\t600000010\n");
+        let r = index_bgzf(
+            &data[..],
+            "t.bed.gz",
+            IndexFormat::Tabix,
+            Builder::bed().build(),
+        );
+        assert!(matches!(r, Err(Error::Io(_))));
+        let format = IndexFormat::Csi {
+            min_shift: DEFAULT_MIN_SHIFT,
+        };
+        let index = index_bgzf(&data[..], "t.bed.gz", format, Builder::bed().build()).unwrap();
+        let mut buf = Vec::new();
+        format.write_to(&mut buf, &index).unwrap();
+        let index = format.read_from(&buf[..]).unwrap();
+        let lines = query(data, index, "chr1:600000001-600000001");
+        assert_eq!(lines, vec!["chr1\t600000000\t600000010"]);
+    }
+
+    #[test]
+    fn test_index_min_shift_range() {
+        let data = bgzip("chr1\t10\t20\nchr1\t600000000\t600000010\n");
+        for min_shift in [*MIN_SHIFT_RANGE.start(), *MIN_SHIFT_RANGE.end()] {
+            let format = IndexFormat::Csi { min_shift };
+            let index = index_bgzf(&data[..], "t.bed.gz", format, Builder::bed().build()).unwrap();
+            let mut buf = Vec::new();
+            format.write_to(&mut buf, &index).unwrap();
+            let index = format.read_from(&buf[..]).unwrap();
+            let lines = query(data.clone(), index, "chr1:600000001-600000001");
+            assert_eq!(lines, vec!["chr1\t600000000\t600000010"]);
+        }
+    }
+}
@@ -1,27 +1,42 @@
-use crate::string::String;
+use crate::string::{to_string_type, String};
 use std::cmp::Ordering;
 use std::collections::{vec_deque::VecDeque, BinaryHeap, HashMap};
-use std::io;
-use std::io::{Error, ErrorKind};
 use std::rc::Rc;
 //use std::sync::Arc as Rc;
 
-use crate::position::{Positioned, PositionedIterator};
+use crate::error::{Error, Result};
+use crate::order::OrderValidator;
+use crate::position::{DynPositionedIterator, Positioned, PositionedIterator};
 
 /// An iterator that returns the intersection of multiple iterators.
-pub struct IntersectionIterator<'a> {
-    base_iterator: Box<dyn PositionedIterator<Item = Box<dyn Positioned>>>,
-    other_iterators: Vec<Box<dyn PositionedIterator<Item = Box<dyn Positioned>>>>,
-    min_heap: BinaryHeap<ReverseOrderPosition<'a, Box<dyn Positioned>>>,
+/// By default, iterators and intervals are trait objects so that different file types can
+/// be intersected. When all inputs are the same type (e.g. all `BedderBed`), `I` can be that
+/// type so that calls on iterators and intervals are statically dispatched.
+pub struct IntersectionIterator<'a, I = DynPositionedIterator>
+where
+    I: PositionedIterator,
+{
+    base_iterator: I,
+    other_iterators: Vec<I>,
+    min_heap: BinaryHeap<ReverseOrderPosition<I::Item>>,
     chromosome_order: &'a HashMap<String, usize>,
+    // the last chromosome seen from the base iterator and from each of the other iterators.
+    // chromosome names are resolved to their index in `chromosome_order` once per interval
+    // so that all comparisons below are integer comparisons.
+    base_chrom: LastChrom,
+    other_chroms: Vec<LastChrom>,
     // because multiple intervals from each stream can overlap a single base interval
     // and each interval from others may overlap many base intervals, we must keep a cache (Q)
     // we always add intervals in order with push_back and therefore remove with pop_front.
     // As soon as the front interval in cache is stricly less than the query interval, then we can pop it.
-    dequeue: VecDeque<Intersection<Box<dyn Positioned>>>,
+    dequeue: VecDeque<Queued<I::Item>>,
 
-    // this is only kept for error checking so we can track if intervals are out of order.
-    previous_interval: Option<Rc<Box<dyn Positioned>>>,
+    // the previous base interval, which is kept until the next one so that its Rc is reused.
+    previous_interval: Option<Rc<I::Item>>,
+
+    // check that the intervals of the base and of each other iterator are sorted.
+    base_order: OrderValidator,
+    other_orders: Vec<OrderValidator>,
 
     // this tracks which iterators have been called with Some(Positioned) for a given interval
     // so that calls after the first are called with None.
@@ -29,8 +44,23 @@ pub struct IntersectionIterator<'a> {
 
     // we call this on the first iteration of pull_through_heap
     heap_initialized: bool,
+
+    // the number of intervals read from the base iterator and from each of the others,
+    // so that errors can point to a record.
+    base_line: u64,
+    lines: Vec<u64>,
+    // Rc's that are no longer referenced by the caller, along with the index of the iterator
+    // (None for the base iterator) that produced the Positioned they hold.
+    // They are reused for new intervals so that steady-state iteration does not allocate;
+    // the Positioned they held is then recycled by its iterator.
+    rc_pool: Vec<PooledRc<I::Item>>,
 }
 
+type PooledRc<P> = (Rc<P>, Option<usize>);
+
+/// at most this many unused Rc's are kept for reuse.
+const MAX_RC_POOL: usize = 1024;
+
 /// An Intersection wraps the Positioned that was intersected with a unique identifier.
 /// The u32 identifier matches the index of the database that was intersected.
 #[derive(Debug)]
@@ -39,71 +69,112 @@ pub struct Intersection<P: Positioned> {
     pub interval: Rc<P>,
     /// a unique identifier indicating the source of this interval.
     pub id: u32,
+    /// the 1-based number of this interval among those read from its source.
+    pub line: u64,
 }
 
 /// An Intersections wraps the base interval and a vector of overlapping intervals.
 #[derive(Debug)]
 pub struct Intersections<P: Positioned> {
     pub base_interval: Rc<P>,
+    /// the 1-based number of the base interval among those read from the query.
+    pub base_line: u64,
     pub overlapping: Vec<Intersection<P>>,
 }
 
-struct ReverseOrderPosition<'a, P: Positioned> {
-    position: P,
-    chromosome_order: &'a HashMap<String, usize>,
-    id: usize, // file_index
+/// An interval in the dequeue along with its chromosome index.
+struct Queued<P> {
+    interval: Rc<P>,
+    chrom_id: usize,
+    id: u32,
+    line: u64,
+}
+
+/// The chromosome of the most recent interval from an iterator.
+/// Intervals are sorted so most share the chromosome of the previous interval
+/// and can be resolved with a string comparison instead of a hash lookup.
+#[derive(Default)]
+pub(crate) struct LastChrom {
+    name: Option<String>,
+    id: usize,
 }
 
-impl<'a, P: Positioned> PartialEq for ReverseOrderPosition<'a, P> {
+impl LastChrom {
+    /// return the index of the chromosome of `p` in `chromosome_order`
+    /// or an error if it is not present.
+    #[inline]
+    pub(crate) fn resolve<P: Positioned, N: FnOnce() -> String>(
+        &mut self,
+        p: &P,
+        chromosome_order: &HashMap<String, usize>,
+        name: N,
+    ) -> Result<usize> {
+        if let Some(last) = &self.name {
+            if last == p.chrom() {
+                return Ok(self.id);
+            }
+        }
+        match chromosome_order.get(p.chrom()) {
+            Some(&id) => {
+                self.name = Some(String::from(p.chrom()));
+                self.id = id;
+                Ok(id)
+            }
+            None => Err(Error::UnknownChromosome {
+                name: name(),
+                chrom: String::from(p.chrom()),
+            }),
+        }
+    }
+}
+
+/// An interval ordered so that a `BinaryHeap` returns the first interval in the genome.
+pub(crate) struct ReverseOrderPosition<P: Positioned> {
+    pub(crate) position: P,
+    pub(crate) chrom_id: usize,
+    pub(crate) id: usize, // file_index
+    pub(crate) line: u64,
+}
+
+impl<P: Positioned> PartialEq for ReverseOrderPosition<P> {
     fn eq(&self, other: &Self) -> bool {
         self.position.start() == other.position.start()
             && self.position.stop() == other.position.stop()
-            && self.position.chrom() == other.position.chrom()
+            && self.chrom_id == other.chrom_id
+            && self.id == other.id
     }
 }
 
-impl<'a, P: Positioned> Eq for ReverseOrderPosition<'a, P> {}
+impl<P: Positioned> Eq for ReverseOrderPosition<P> {}
 
-impl<'a, P: Positioned> PartialOrd for ReverseOrderPosition<'a, P> {
+impl<P: Positioned> PartialOrd for ReverseOrderPosition<P> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl<'a, P: Positioned> Ord for ReverseOrderPosition<'a, P> {
+impl<P: Positioned> Ord for ReverseOrderPosition<P> {
     fn cmp(&self, other: &Self) -> Ordering {
-        if self.position.chrom() != other.position.chrom() {
-            return self
-                .chromosome_order
-                .get(self.position.chrom())
-                .unwrap_or_else(|| {
-                    panic!(
-                        "Invalid chromosome: \"{}\" from file {} not found in chromosome order.",
-                        self.position.chrom(),
-                        self.id + 1
-                    )
-                })
-                .cmp(self.chromosome_order.get(other.position.chrom()).unwrap())
-                .reverse();
+        if self.chrom_id != other.chrom_id {
+            return self.chrom_id.cmp(&other.chrom_id).reverse();
         }
 
-        let so = self.position.start().cmp(&other.position.start()).reverse();
-        match so {
-            Ordering::Equal => self.position.stop().cmp(&other.position.stop()).reverse(),
-            _ => so,
-        }
+        // identical intervals are returned in the order of their iterators.
+        self.position
+            .start()
+            .cmp(&other.position.start())
+            .then_with(|| self.position.stop().cmp(&other.position.stop()))
+            .then_with(|| self.id.cmp(&other.id))
+            .reverse()
     }
 }
 
 /// cmp will return Less if a is before b, Greater if a is after b, Equal if they overlap.
+/// `a_chrom` and `b_chrom` are the chromosome indexes of `a` and `b`.
 #[inline(always)]
-fn cmp(
-    a: &dyn Positioned,
-    b: &dyn Positioned,
-    chromosome_order: &HashMap<String, usize>,
-) -> Ordering {
-    if a.chrom() != b.chrom() {
-        return chromosome_order[a.chrom()].cmp(&chromosome_order[b.chrom()]);
+fn cmp<A: Positioned, B: Positioned>(a_chrom: usize, a: &A, b_chrom: usize, b: &B) -> Ordering {
+    if a_chrom != b_chrom {
+        return a_chrom.cmp(&b_chrom);
     }
     // same chrom.
     if a.stop() <= b.start() {
@@ -116,13 +187,13 @@ fn cmp(
     Ordering::Equal
 }
 
-fn region_str<P: Positioned>(p: &P) -> std::string::String {
-    format!("{}:{}-{}", p.chrom(), p.start() + 1, p.stop())
+pub(crate) fn region_str<P: Positioned>(p: &P) -> String {
+    to_string_type(format!("{}:{}-{}", p.chrom(), p.start() + 1, p.stop()))
 }
 
 /// An iterator that returns the intersection of multiple iterators for each query interval
-impl<'a> Iterator for IntersectionIterator<'a> {
-    type Item = io::Result<Intersections<Box<dyn Positioned>>>;
+impl<'a, I: PositionedIterator> Iterator for IntersectionIterator<'a, I> {
+    type Item = Result<Intersections<I::Item>>;
 
     fn next(&mut self) -> Option<Self::Item> {
         let bi = self.base_iterator.next_position(None)?;
@@ -130,44 +201,50 @@ impl<'a> Iterator for IntersectionIterator<'a> {
         // if bi is an error return the Result here
         let base_interval = match bi {
             Err(e) => return Some(Err(e)),
-            Ok(p) => Rc::new(p),
+            Ok(p) => self.new_rc(p),
         };
+        self.base_line += 1;
+
+        let base_iterator = &self.base_iterator;
+        let base_chrom =
+            match self
+                .base_chrom
+                .resolve(base_interval.as_ref(), self.chromosome_order, || {
+                    base_iterator.name()
+                }) {
+                Ok(c) => c,
+                Err(e) => return Some(Err(e)),
+            };
 
-        if self.out_of_order(base_interval.clone()) {
-            let p = self
-                .previous_interval
-                .as_ref()
-                .expect("we know previous interval is_some from out_of_order");
-            let msg = format!(
-                "intervals from {} out of order {} should be before {}",
-                self.base_iterator.name(),
-                region_str(p.as_ref()),
-                region_str(base_interval.as_ref()),
-            );
-            return Some(Err(Error::new(ErrorKind::Other, msg)));
+        if let Some(v) = self.base_order.check(base_chrom, base_interval.as_ref()) {
+            return Some(Err(v.into_error(self.base_iterator.name(), self.base_line)));
         }
 
-        self.previous_interval = Some(base_interval.clone());
+        if let Some(previous) = self.previous_interval.replace(base_interval.clone()) {
+            self.release_rc(previous, None);
+        }
 
         // drop intervals from Q that are strictly before the base interval.
-        self.pop_front(base_interval.clone());
+        self.pop_front(base_chrom, base_interval.as_ref());
 
         // pull intervals through the min-heap until the base interval is strictly less than the
         // last pulled interval.
         // we want all intervals to pass through the min_heap so that they are ordered across files
-        if let Err(e) = self.pull_through_heap(base_interval.clone()) {
+        if let Err(e) = self.pull_through_heap(base_chrom, &base_interval) {
             return Some(Err(e));
         }
 
-        let mut overlapping_positions = Vec::new();
+        // allocate once; the dequeue holds all possible overlaps.
+        let mut overlapping_positions = Vec::with_capacity(self.dequeue.len());
         // de-Q contains all intervals that can overlap with the base interval.
         // de-Q is sorted.
         // We iterate through (again) and add those to overlapping positions.
         for o in self.dequeue.iter() {
             match cmp(
+                o.chrom_id,
                 o.interval.as_ref(),
+                base_chrom,
                 base_interval.as_ref(),
-                self.chromosome_order,
             ) {
                 Ordering::Less => continue,
                 Ordering::Greater => break,
@@ -176,47 +253,83 @@ impl<'a> Iterator for IntersectionIterator<'a> {
                     // we could avoid by by keeping entire intersection in Rc.
                     interval: Rc::clone(&o.interval),
                     id: o.id,
+                    line: o.line,
                 }),
             }
         }
 
         Some(Ok(Intersections {
             base_interval,
+            base_line: self.base_line,
             overlapping: overlapping_positions,
         }))
     }
 }
 
 /// Create a new IntersectionIterator given a query (base) and a vector of other positioned iterators.
-impl<'a> IntersectionIterator<'a> {
+impl<'a, I: PositionedIterator> IntersectionIterator<'a, I> {
     pub fn new(
-        base_iterator: Box<dyn PositionedIterator<Item = Box<dyn Positioned>>>,
-        other_iterators: Vec<Box<dyn PositionedIterator<Item = Box<dyn Positioned>>>>,
+        base_iterator: I,
+        other_iterators: Vec<I>,
         chromosome_order: &'a HashMap<String, usize>,
-    ) -> io::Result<Self> {
+    ) -> Result<Self> {
+        log::debug!(
+            "intersecting {} with [{}]",
+            base_iterator.name(),
+            other_iterators
+                .iter()
+                .map(|o| o.name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
         let min_heap = BinaryHeap::new();
-        let called = vec![false; other_iterators.len()];
+        let n = other_iterators.len();
+        let called = vec![false; n];
+        let other_chroms = other_iterators
+            .iter()
+            .map(|_| LastChrom::default())
+            .collect();
+        let lines = vec![0; other_iterators.len()];
         Ok(IntersectionIterator {
             base_iterator,
             other_iterators,
             min_heap,
             chromosome_order,
+            base_chrom: LastChrom::default(),
+            other_chroms,
             dequeue: VecDeque::new(),
             previous_interval: None,
+            base_order: OrderValidator::new(),
+            // the sweep does not depend on the order of stops in the other iterators,
+            // which are often sorted by start alone.
+            other_orders: (0..n)
+                .map(|_| OrderValidator::new().without_stop_order())
+                .collect(),
             called,
             heap_initialized: false,
+            base_line: 0,
+            lines,
+            rc_pool: Vec::new(),
         })
     }
 
-    fn init_heap(&mut self, base_interval: Rc<Box<dyn Positioned>>) -> io::Result<()> {
+    fn init_heap(&mut self, base_interval: &Rc<I::Item>) -> Result<()> {
         assert!(!self.heap_initialized);
         for (i, iter) in self.other_iterators.iter_mut().enumerate() {
             if let Some(positioned) = iter.next_position(Some(base_interval.as_ref())) {
                 let positioned = positioned?;
+                self.lines[i] += 1;
+                let chrom_id =
+                    self.other_chroms[i]
+                        .resolve(&positioned, self.chromosome_order, || iter.name())?;
+                if let Some(v) = self.other_orders[i].check(chrom_id, &positioned) {
+                    return Err(v.into_error(iter.name(), self.lines[i]));
+                }
                 self.min_heap.push(ReverseOrderPosition {
                     position: positioned,
-                    chromosome_order: self.chromosome_order,
+                    chrom_id,
                     id: i,
+                    line: self.lines[i],
                 });
             }
         }
@@ -225,33 +338,46 @@ impl<'a> IntersectionIterator<'a> {
     }
 
     /// drop intervals from Q that are strictly before the base interval.
-    fn pop_front(&mut self, base_interval: Rc<Box<dyn Positioned>>) {
+    fn pop_front(&mut self, base_chrom: usize, base_interval: &I::Item) {
         while !self.dequeue.is_empty()
             && Ordering::Less
                 == cmp(
+                    self.dequeue[0].chrom_id,
                     self.dequeue[0].interval.as_ref(),
-                    base_interval.as_ref(),
-                    self.chromosome_order,
+                    base_chrom,
+                    base_interval,
                 )
         {
-            _ = self.dequeue.pop_front();
+            let o = self.dequeue.pop_front().expect("dequeue is not empty");
+            self.release_rc(o.interval, Some(o.id as usize));
         }
     }
 
-    fn out_of_order(&self, interval: Rc<Box<dyn Positioned>>) -> bool {
-        return match &self.previous_interval {
-            None => false, // first interval in file.
-            Some(previous_interval) => {
-                let pci = self.chromosome_order[previous_interval.chrom()];
-                let ici = self.chromosome_order[interval.chrom()];
-                pci > ici
-                    || (pci == ici && previous_interval.start() > interval.start())
-                    || (pci == ici
-                        && previous_interval.start() == interval.start()
-                        && previous_interval.stop() > interval.stop())
+    /// wrap `p` in an Rc, reusing a pooled Rc if possible.
+    #[inline]
+    fn new_rc(&mut self, p: I::Item) -> Rc<I::Item> {
+        match self.rc_pool.pop() {
+            Some((mut rc, old_source)) => {
+                let slot = Rc::get_mut(&mut rc).expect("pooled Rc is unique");
+                let old = std::mem::replace(slot, p);
+                match old_source {
+                    None => self.base_iterator.recycle(old),
+                    Some(i) => self.other_iterators[i].recycle(old),
+                }
+                rc
             }
-        };
+            None => Rc::new(p),
+        }
+    }
+
+    /// keep `rc` for reuse if nothing else (e.g. a caller's Intersections) holds it.
+    #[inline]
+    fn release_rc(&mut self, mut rc: Rc<I::Item>, source: Option<usize>) {
+        if self.rc_pool.len() < MAX_RC_POOL && Rc::get_mut(&mut rc).is_some() {
+            self.rc_pool.push((rc, source));
+        }
     }
+
     // reset the array that tracks which iterators have been called with Some(Positioned)
     #[inline]
     fn zero_called(&mut self) {
@@ -259,23 +385,23 @@ impl<'a> IntersectionIterator<'a> {
         unsafe { ptr.write_bytes(0, self.called.len()) };
     }
 
-    fn pull_through_heap(&mut self, base_interval: Rc<Box<dyn Positioned>>) -> io::Result<()> {
+    fn pull_through_heap(&mut self, base_chrom: usize, base_interval: &Rc<I::Item>) -> Result<()> {
         self.zero_called();
         if !self.heap_initialized {
             // we wait til first iteration here to call init heap
             // because we need the base interval.
-            self.init_heap(Rc::clone(&base_interval))?;
+            self.init_heap(base_interval)?;
         }
-        let other_iterators = self.other_iterators.as_mut_slice();
-
         while let Some(ReverseOrderPosition {
             position,
+            chrom_id,
             id: file_index,
-            ..
+            line,
         }) = self.min_heap.pop()
         {
             // must always pull into the heap.
-            let f = other_iterators
+            let f = self
+                .other_iterators
                 .get_mut(file_index)
                 .expect("expected interval iterator at file index");
             // for a given base_interval, we make sure to call next_position with Some, only once.
@@ -288,42 +414,43 @@ impl<'a> IntersectionIterator<'a> {
             };
             if let Some(next_position) = f.next_position(arg) {
                 let next_position = next_position?;
+                self.lines[file_index] += 1;
+                let next_chrom = self.other_chroms[file_index].resolve(
+                    &next_position,
+                    self.chromosome_order,
+                    || f.name(),
+                )?;
 
                 // check that intervals within a file are in order.
-                if !(position.start() <= next_position.start()
-                    || self.chromosome_order[position.chrom()]
-                        < self.chromosome_order[next_position.chrom()])
-                {
-                    let msg = format!(
-                        "database intervals out of order ({} -> {}) in iterator: {}",
-                        region_str(&position),
-                        region_str(&next_position),
-                        other_iterators[file_index].name()
-                    );
-                    return Err(Error::new(ErrorKind::Other, msg));
+                if let Some(v) = self.other_orders[file_index].check(next_chrom, &next_position) {
+                    return Err(v.into_error(f.name(), self.lines[file_index]));
                 }
                 self.min_heap.push(ReverseOrderPosition {
                     position: next_position,
-                    chromosome_order: self.chromosome_order,
+                    chrom_id: next_chrom,
                     id: file_index,
+                    line: self.lines[file_index],
                 });
             }
 
             // and we must always add the position to the Q
-            let rc_pos = Rc::new(position);
-            let int = Intersection {
-                interval: rc_pos.clone(),
-                id: file_index as u32,
-            };
-            self.dequeue.push_back(int);
-
+            let rc_pos = self.new_rc(position);
             // if this position is after base_interval, we can stop pulling through heap.
-            if cmp(
+            // (cmp returns Less when base_interval is strictly before rc_pos).
+            let past_base = cmp(
+                base_chrom,
                 base_interval.as_ref(),
+                chrom_id,
                 rc_pos.as_ref(),
-                self.chromosome_order,
-            ) == Ordering::Greater
-            {
+            ) == Ordering::Less;
+            self.dequeue.push_back(Queued {
+                interval: rc_pos,
+                chrom_id,
+                id: file_index as u32,
+                line,
+            });
+
+            if past_base {
                 break;
             }
         }
@@ -358,17 +485,17 @@ mod tests {
         fn value(&self, b: Field) -> result::Result<Value, FieldError> {
             match b {
                 Field::Int(i) => match i {
-                    0 => Ok(Value::Strings(vec![self.chrom.clone()])),
-                    1 => Ok(Value::Ints(vec![self.start as i64])),
-                    2 => Ok(Value::Ints(vec![self.stop as i64])),
-                    3 => Ok(Value::Strings(vec![String::from("hello")])),
+                    0 => Ok(Value::Strings(vec![Some(self.chrom.clone())])),
+                    1 => Ok(Value::Ints(vec![Some(self.start as i64)])),
+                    2 => Ok(Value::Ints(vec![Some(self.stop as i64)])),
+                    3 => Ok(Value::Strings(vec![Some(String::from("hello"))])),
                     _ => Err(FieldError::InvalidFieldIndex(i)),
                 },
                 Field::String(s) => match s.as_str() {
-                    "chrom" => Ok(Value::Strings(vec![self.chrom.clone()])),
-                    "start" => Ok(Value::Ints(vec![self.start as i64])),
-                    "stop" => Ok(Value::Ints(vec![self.stop as i64])),
-                    "name" => Ok(Value::Strings(vec![String::from("hello")])),
+                    "chrom" => Ok(Value::Strings(vec![Some(self.chrom.clone())])),
+                    "start" => Ok(Value::Ints(vec![Some(self.start as i64)])),
+                    "stop" => Ok(Value::Ints(vec![Some(self.stop as i64)])),
+                    "name" => Ok(Value::Strings(vec![Some(String::from("hello"))])),
                     _ => Err(FieldError::InvalidFieldName(s)),
                 },
             }
@@ -390,10 +517,10 @@ mod tests {
         type Item = Box<dyn Positioned>;
 
         fn name(&self) -> String {
-            String::from(format!("{}:{}", self.name, self.i))
+            crate::string::to_string_type(format!("{}:{}", self.name, self.i))
         }
 
-        fn next_position(&mut self, _o: Option<&dyn Positioned>) -> Option<io::Result<Self::Item>> {
+        fn next_position(&mut self, _o: Option<&dyn Positioned>) -> Option<Result<Self::Item>> {
             if self.i >= self.ivs.len() {
                 return None;
             }
@@ -431,7 +558,7 @@ mod tests {
             start: n_intervals + 9,
             stop: n_intervals + 10,
         });
-        ivs.sort_by(|a, b| a.start.cmp(&b.start));
+        ivs.sort_by_key(|a| a.start);
 
         let b_ivs = Intervals::new(String::from("B"), ivs.clone());
         let mut iter =
@@ -510,6 +637,36 @@ mod tests {
         })
     }
 
+    #[test]
+    fn pull_past_earlier_intervals() {
+        // intervals before the base interval must not stop the pull through the heap, or
+        // the overlapping interval after them is missed.
+        let chrom_order = HashMap::from([(String::from("chr1"), 0)]);
+        let iv = |start, stop| Interval {
+            chrom: String::from("chr1"),
+            start,
+            stop,
+        };
+        let a_ivs = Intervals::new(String::from("A"), vec![iv(10, 20)]);
+        let b_ivs = Intervals::new(
+            String::from("B"),
+            vec![iv(1, 2), iv(3, 4), iv(12, 15), iv(30, 40)],
+        );
+        let overlaps: Vec<_> =
+            IntersectionIterator::new(Box::new(a_ivs), vec![Box::new(b_ivs)], &chrom_order)
+                .expect("error getting iterator")
+                .map(|intersection| {
+                    let intersection = intersection.expect("intersection");
+                    intersection
+                        .overlapping
+                        .iter()
+                        .map(|p| (p.interval.start(), p.interval.stop()))
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+        assert_eq!(overlaps, vec![vec![(12, 15)]]);
+    }
+
     #[test]
     fn ordering_error() {
         let chrom_order = HashMap::from([(String::from("chr1"), 0), (String::from("chr2"), 1)]);
@@ -534,7 +691,16 @@ mod tests {
         let e = iter.nth(1).expect("error getting next");
         assert!(e.is_err());
         let e = e.err().unwrap();
+        assert!(matches!(
+            e,
+            Error::OutOfOrder {
+                line: 2,
+                kind: crate::order::ViolationKind::Start,
+                ..
+            }
+        ));
         assert!(e.to_string().contains("out of order"));
+        assert!(e.to_string().contains("start"));
 
         // now repeat with database out of order.
         let a_ivs = Intervals::new(
@@ -575,9 +741,98 @@ mod tests {
         let e = iter.next().expect("error getting next");
         assert!(e.is_err());
         let e = e.err().unwrap();
+        assert!(matches!(e, Error::OutOfOrder { line: 2, .. }));
         assert!(e.to_string().contains("out of order"));
     }
 
+    #[test]
+    fn database_returns_to_earlier_chromosome() {
+        let chrom_order = HashMap::from([(String::from("chr1"), 0), (String::from("chr2"), 1)]);
+        let iv = |chrom: &str, start: u64| Interval {
+            chrom: String::from(chrom),
+            start,
+            stop: start + 10,
+        };
+        let a_ivs = Intervals::new(String::from("A"), vec![iv("chr1", 0), iv("chr2", 500)]);
+        // chr1 comes back after chr2 with a larger start.
+        let b_ivs = Intervals::new(
+            String::from("B"),
+            vec![iv("chr2", 5), iv("chr1", 100), iv("chr2", 500)],
+        );
+        let iter = IntersectionIterator::new(Box::new(a_ivs), vec![Box::new(b_ivs)], &chrom_order)
+            .expect("error getting iterator");
+        let e = iter
+            .filter_map(|r| r.err())
+            .next()
+            .expect("expected an error");
+        assert!(matches!(e, Error::OutOfOrder { .. }));
+    }
+
+    #[test]
+    fn chromosome_order_from_genome() {
+        // chr10 sorts before chr2 here, unlike lexicographic order.
+        let chrom_order = HashMap::from([(String::from("chr10"), 0), (String::from("chr2"), 1)]);
+        let iv = |chrom: &str, start: u64| Interval {
+            chrom: String::from(chrom),
+            start,
+            stop: start + 10,
+        };
+        let a_ivs = Intervals::new(String::from("A"), vec![iv("chr10", 5), iv("chr2", 5)]);
+        let b_ivs = Intervals::new(
+            String::from("B"),
+            vec![
+                iv("chr10", 0),
+                iv("chr10", 100),
+                iv("chr2", 0),
+                iv("chr2", 8),
+            ],
+        );
+        let iter = IntersectionIterator::new(Box::new(a_ivs), vec![Box::new(b_ivs)], &chrom_order)
+            .expect("error getting iterator");
+        let counts: Vec<_> = iter
+            .map(|intersection| {
+                let intersection = intersection.expect("error getting intersection");
+                assert!(intersection
+                    .overlapping
+                    .iter()
+                    .all(|o| o.interval.chrom() == intersection.base_interval.chrom()));
+                intersection.overlapping.len()
+            })
+            .collect();
+        assert_eq!(counts, vec![1, 2]);
+    }
+
+    #[test]
+    fn unknown_chromosome() {
+        let chrom_order = HashMap::from([(String::from("chr1"), 0)]);
+        let a_ivs = Intervals::new(
+            String::from("A"),
+            vec![Interval {
+                chrom: String::from("chr1"),
+                start: 1,
+                stop: 2,
+            }],
+        );
+        let b_ivs = Intervals::new(
+            String::from("B"),
+            vec![Interval {
+                chrom: String::from("chrUn"),
+                start: 1,
+                stop: 2,
+            }],
+        );
+        let mut iter =
+            IntersectionIterator::new(Box::new(a_ivs), vec![Box::new(b_ivs)], &chrom_order)
+                .expect("error getting iterator");
+        match iter.next().expect("error getting next") {
+            Err(Error::UnknownChromosome { name, chrom }) => {
+                assert_eq!(chrom, "chrUn");
+                assert!(name.starts_with('B'));
+            }
+            r => panic!("expected unknown chromosome error, got {:?}", r),
+        }
+    }
+
     #[test]
     fn multiple_sources() {
         let chrom_order = HashMap::from([(String::from("chr1"), 0), (String::from("chr2"), 1)]);
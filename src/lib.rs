@@ -3,9 +3,59 @@
 /// Intersection iterators and data structures.
 pub mod intersection;
 
+/// Summaries of values across overlapping intervals.
+pub mod aggregate;
+
+#[cfg(feature = "vcf")]
+/// Matching variants by allele rather than by position alone.
+pub mod allele;
+
+#[cfg(feature = "vcf")]
+/// Adding INFO fields to VCF records from overlapping intervals.
+pub mod annotate;
+
+#[cfg(feature = "vcf")]
+/// Genotype concordance between two VCFs.
+pub mod concordance;
+
+/// Regions of the genome not covered by intervals.
+pub mod complement;
+
+/// Depth of coverage as bedGraph segments and histograms.
+pub mod coverage;
+
+/// Error type used across bedder.
+pub mod error;
+
+/// Reading sequence from FASTA files with a `.fai` index.
+pub mod fasta;
+
+#[cfg(feature = "vcf")]
+/// Splitting multi-allelic variants and left-aligning indels.
+pub mod normalize;
+
+/// Merging many sorted iterators into one sorted stream.
+pub mod merge;
+
+/// Checking that the intervals of an iterator are sorted.
+pub mod order;
+
+/// Splitting intervals from many sources into segments with the same sources.
+pub mod partition;
+
 /// Position traits.
 pub mod position;
 
+/// Parsing genomic regions and restricting iterators to them.
+pub mod region;
+
+/// Sorting records in memory or, for large inputs, with bounded memory.
+pub mod sort;
+
+#[cfg(feature = "vcf")]
+/// Comparing structural variant callsets.
+pub mod sv;
+
 /// a std::String::String unless other string features are enabled.
 pub mod string;
 
@@ -20,3 +70,18 @@ pub mod bedder_bed;
 #[cfg(feature = "vcf")]
 /// Vcf parser implementing the PositionedIterator trait.
 pub mod bedder_vcf;
+
+#[cfg(all(feature = "bam", feature = "sam"))]
+/// SAM and BAM reader implementing the PositionedIterator trait.
+pub mod bedder_alignment;
+
+/// Fixed-size or fixed-count windows over regions of the genome.
+pub mod windows;
+
+#[cfg(all(feature = "bgzf", feature = "csi", feature = "tabix"))]
+/// Building and reading tabix and CSI indexes.
+pub mod index;
+
+#[cfg(all(feature = "bgzf", feature = "csi", feature = "tabix"))]
+/// BGZF output with tabix or CSI indexes built on the fly.
+pub mod writer;
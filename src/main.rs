@@ -1,12 +1,73 @@
 extern crate bedder;
+use bedder::aggregate::Aggregation;
+use bedder::allele;
+use bedder::annotate;
+use bedder::complement::Complement;
+use bedder::concordance::{Concordance, GenotypeClass};
+use bedder::coverage::{Coverage, Histogram};
+use bedder::fasta::IndexedFasta;
+use bedder::index::{self, IndexFormat};
+use bedder::intersection::{Intersection, IntersectionIterator};
+use bedder::merge::MergeIterator;
+use bedder::normalize::Normalizer;
+use bedder::order::OrderValidator;
+use bedder::partition::PartitionIterator;
+use bedder::position::{BoxPositioned, DynPositionedIterator, Positioned, PositionedIterator};
+use bedder::region::{self, Region, RegionFilter, Regions};
 use bedder::sniff;
-use clap::Parser;
+use bedder::sort::{InMemory, Sorter};
+use bedder::sv::{self, SvParams};
+use bedder::windows::{WindowSize, Windows};
+use bedder::writer::BgzfWriter;
+use clap::{Args, Parser, Subcommand};
+use noodles::{csi, vcf};
+use std::collections::HashMap;
 use std::env;
-use std::path::PathBuf;
+use std::ffi::OsString;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about=None)]
-struct Args {
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// report the intervals from other files that overlap each query interval (the default).
+    Intersect(IntersectArgs),
+    /// summarize values of overlapping intervals for each query interval (like bedtools map).
+    Map(MapArgs),
+    /// write the query VCF with INFO fields summarizing the overlapping intervals (like vcfanno).
+    Annotate(AnnotateArgs),
+    /// create a tabix or CSI index for a sorted, bgzipped BED, GFF, VCF or other tabular file.
+    Index(IndexArgs),
+    /// compare structural variant calls to a truth set and report precision and recall.
+    SvCompare(SvCompareArgs),
+    /// compare the genotypes of samples shared by a truth and a query VCF.
+    Concordance(ConcordanceArgs),
+    /// report the parts of the genome not covered by any interval (like bedtools complement).
+    Complement(ComplementArgs),
+    /// count the intervals of each file in windows across the genome.
+    BinCounts(BinCountsArgs),
+    /// report the depth of coverage of sorted intervals as bedGraph (like bedtools genomecov -bg).
+    Coverage(CoverageArgs),
+    /// split the intervals of many files into segments covered by the same files (like bedtools multiinter).
+    Partition(PartitionArgs),
+    /// merge sorted files (e.g. one per chromosome or sample) into a single sorted file.
+    Merge(MergeArgs),
+    /// sort a file of any size by the chromosome order of a genome file, then by position.
+    Sort(SortArgs),
+    /// check that files are sorted by the chromosome order of a genome file, then by position.
+    CheckSorted(CheckSortedArgs),
+}
+
+#[derive(Args, Debug)]
+struct InputArgs {
     #[arg(help = "input file", short = 'a')]
     query_path: PathBuf,
     #[arg(help = "other file", short = 'b', required = true)]
@@ -17,30 +78,1300 @@ struct Args {
         required = true
     )]
     genome_file: PathBuf,
+    #[arg(
+        help = "length of the longest interval in uncompressed BED inputs. when given, these skip ahead to far away queries by bisection",
+        long = "max-interval-length"
+    )]
+    max_interval_length: Option<u64>,
+    #[arg(
+        help = "only use intervals overlapping this region, e.g. chr1:1,000-2,000. may be repeated",
+        long = "region",
+        value_parser = parse_region,
+    )]
+    regions: Vec<Region>,
+    #[arg(
+        help = "only use intervals overlapping the regions in this BED file",
+        long = "regions-file"
+    )]
+    regions_file: Option<PathBuf>,
+    #[arg(
+        help = "split multi-allelic records of VCF inputs into one record per ALT",
+        long = "split-alleles"
+    )]
+    split_alleles: bool,
+    #[arg(
+        help = "left-align and trim indels of VCF inputs against this FASTA (with a .fai index). multi-allelic records are aligned only with --split-alleles",
+        long = "fasta"
+    )]
+    fasta: Option<PathBuf>,
+    #[arg(
+        help = "load other files smaller than this many megabytes into memory and sort them if they are out of order",
+        long = "sort-below"
+    )]
+    sort_below: Option<u64>,
 }
 
-pub fn main() -> Result<(), Box<dyn std::error::Error>> {
-    if env::var("RUST_LOG").is_err() {
-        env::set_var("RUST_LOG", "bedder=info");
+impl InputArgs {
+    /// the names of the query and other inputs, used in error messages.
+    fn names(&self) -> (std::string::String, Vec<std::string::String>) {
+        let name = |p: &PathBuf| p.to_string_lossy().into_owned();
+        (
+            name(&self.query_path),
+            self.other_paths.iter().map(name).collect(),
+        )
     }
-    env_logger::init();
-    log::info!("starting up");
-    let args = Args::parse();
 
-    let chrom_order = bedder::genome_file::parse_genome(std::fs::File::open(&args.genome_file)?)?;
+    /// the regions given by --region and --regions-file, if any.
+    fn regions(
+        &self,
+        chrom_order: &HashMap<bedder::string::String, usize>,
+    ) -> bedder::error::Result<Option<Rc<Regions>>> {
+        let mut regions = self.regions.clone();
+        if let Some(path) = &self.regions_file {
+            let f = std::fs::File::open(path)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+            regions.extend(region::read_bed(
+                io::BufReader::new(f),
+                &path.to_string_lossy(),
+            )?);
+        } else if regions.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Rc::new(Regions::new(regions, chrom_order)?)))
+    }
+
+    /// open `path`, normalizing VCF and BCF files if --split-alleles or --fasta was given.
+    fn open(&self, path: &Path) -> bedder::error::Result<DynPositionedIterator> {
+        if !self.split_alleles && self.fasta.is_none() {
+            return sniff::open_file_with_max_len(path, self.max_interval_length);
+        }
+        let mut reader = io::BufReader::new(std::fs::File::open(path)?);
+        match sniff::detect_file_format(&mut reader, path)?.0 {
+            sniff::FileFormat::VCF | sniff::FileFormat::BCF => {
+                self.open_vcf(path).map(|(_, iter)| iter)
+            }
+            _ => sniff::open_file_with_max_len(path, self.max_interval_length),
+        }
+    }
+
+    /// open the VCF or BCF `path` with a line for every record, normalized if
+    /// --split-alleles or --fasta was given.
+    fn open_vcf(&self, path: &Path) -> bedder::error::Result<(vcf::Header, DynPositionedIterator)> {
+        let v = sniff::open_vcf(path)?.with_lines();
+        let header = v.header().clone();
+        if !self.split_alleles && self.fasta.is_none() {
+            return Ok((header, Box::new(BoxPositioned::new(v))));
+        }
+        let fasta = self.fasta.as_ref().map(IndexedFasta::open).transpose()?;
+        let n = Normalizer::new(v, self.split_alleles, fasta);
+        Ok((header, Box::new(BoxPositioned::new(n))))
+    }
+
+    /// like `open` for an other file, which is sorted in memory if it is smaller than
+    /// --sort-below.
+    fn open_other(
+        &self,
+        path: &Path,
+        chrom_order: &HashMap<bedder::string::String, usize>,
+    ) -> bedder::error::Result<DynPositionedIterator> {
+        let iter = self.open(path)?;
+        let small = match self.sort_below {
+            // pipes can not be sized.
+            Some(mb) => std::fs::metadata(path).is_ok_and(|m| m.is_file() && m.len() < mb << 20),
+            None => false,
+        };
+        if !small {
+            return Ok(iter);
+        }
+        Ok(Box::new(InMemory::new(iter, path, chrom_order)?))
+    }
+}
+
+#[derive(Args, Debug)]
+struct OutputArgs {
+    #[arg(
+        help = "output file. written as BGZF if it ends with .gz [default: stdout]",
+        short = 'o',
+        long = "output"
+    )]
+    output: Option<PathBuf>,
+    #[arg(
+        help = "build an index of the .gz output: tbi or csi",
+        long = "index",
+        requires = "output",
+        value_parser = parse_index_format,
+    )]
+    index: Option<IndexFormat>,
+}
+
+#[derive(Args, Debug)]
+struct IntersectArgs {
+    #[command(flatten)]
+    inputs: InputArgs,
+    #[command(flatten)]
+    output: OutputArgs,
+    #[arg(
+        help = "write the original line of each query interval and each overlapping interval (like bedtools -wa -wb). a source index column is added when there are multiple -b files",
+        long = "raw"
+    )]
+    raw: bool,
+    #[arg(
+        help = "for VCF inputs, only report variants that share an ALT allele (after splitting multi-allelic records and trimming shared bases). a column of matched 1-based ALT numbers (query:other) is added",
+        long = "match-alleles",
+        requires = "raw"
+    )]
+    match_alleles: bool,
+}
+
+#[derive(Args, Debug)]
+struct MapArgs {
+    #[command(flatten)]
+    inputs: InputArgs,
+    #[command(flatten)]
+    output: OutputArgs,
+    #[arg(
+        help = "aggregation as source:field:op, e.g. 0:4:sum or 1:INFO.AF:max. source is the 0-based index of the -b file; integer fields are 0-based columns. ops: count, count_distinct, sum, mean, median, min, max, first, last, collapse, distinct",
+        short = 'c',
+        long = "column",
+        required = true,
+        value_parser = parse_aggregation,
+    )]
+    aggregations: Vec<Aggregation>,
+    #[arg(
+        help = "for VCF inputs, only summarize variants that share an ALT allele with the query variant. per-allele fields (e.g. INFO.AF) use the values of the matching alleles",
+        long = "match-alleles"
+    )]
+    match_alleles: bool,
+}
+
+#[derive(Args, Debug)]
+struct AnnotateArgs {
+    #[command(flatten)]
+    inputs: InputArgs,
+    #[command(flatten)]
+    output: OutputArgs,
+    #[arg(
+        help = "annotation config with one line per new INFO field: source:field:op KEY [overlap|alleles] [description], e.g. 0:INFO.AF:max gnomad_AF alleles. source is the 0-based index of the -b file; alleles uses only variants that share an ALT with the query",
+        short = 'c',
+        long = "config",
+        required = true
+    )]
+    config: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct SvCompareArgs {
+    #[arg(help = "truth VCF", short = 't', long = "truth")]
+    truth_path: PathBuf,
+    #[arg(help = "query VCF", short = 'q', long = "query")]
+    query_path: PathBuf,
+    #[arg(
+        help = "largest distance between the starts and between the ends of matching variants",
+        long = "max-distance",
+        default_value_t = 500
+    )]
+    max_distance: u64,
+    #[arg(
+        help = "smallest fraction of each variant covered by the other. not used for insertions and breakends",
+        long = "reciprocal-overlap",
+        default_value_t = 0.5
+    )]
+    reciprocal_overlap: f64,
+    #[arg(
+        help = "smallest ratio of the shorter to the longer SVLEN",
+        long = "size-ratio",
+        default_value_t = 0.7
+    )]
+    size_ratio: f64,
+    #[arg(help = "match variants of different SVTYPE", long = "ignore-type")]
+    ignore_type: bool,
+    #[arg(
+        help = "write the status (TP, FP or FN) and match of each truth and query variant to this file",
+        long = "variants"
+    )]
+    variants: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct ConcordanceArgs {
+    #[arg(help = "truth VCF", short = 't', long = "truth")]
+    truth_path: PathBuf,
+    #[arg(help = "query VCF", short = 'q', long = "query")]
+    query_path: PathBuf,
+    #[arg(
+        help = "genome file for chromosome ordering",
+        short = 'g',
+        required = true
+    )]
+    genome_file: PathBuf,
+    #[arg(
+        help = "write each site and sample with different genotype classes to this file",
+        long = "mismatches"
+    )]
+    mismatches: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct ComplementArgs {
+    #[arg(help = "sorted input file", short = 'a')]
+    query_path: PathBuf,
+    #[arg(
+        help = "genome file with chromosome lengths (e.g. a .fai) giving the chromosome order",
+        short = 'g',
+        required = true
+    )]
+    genome_file: PathBuf,
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+struct BinCountsArgs {
+    #[arg(help = "files to count", short = 'b', required = true)]
+    other_paths: Vec<PathBuf>,
+    #[arg(
+        help = "genome file with chromosome lengths (e.g. a .fai) giving the chromosome order",
+        short = 'g',
+        required = true
+    )]
+    genome_file: PathBuf,
+    #[arg(
+        help = "windows as size, size:step or nCOUNT for COUNT windows per chromosome or region, e.g. 1000, 1000:500 or n10",
+        short = 'w',
+        long = "window",
+        required = true
+    )]
+    window: WindowSize,
+    #[arg(
+        help = "make windows of the regions in this BED file instead of whole chromosomes",
+        long = "regions-file"
+    )]
+    regions_file: Option<PathBuf>,
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+struct CoverageArgs {
+    #[arg(help = "sorted BED, VCF, SAM or BAM file", short = 'a')]
+    query_path: PathBuf,
+    #[arg(
+        help = "genome file with chromosome lengths (e.g. a .fai) giving the chromosome order",
+        short = 'g',
+        required = true
+    )]
+    genome_file: PathBuf,
+    #[arg(
+        help = "also report uncovered bases with a depth of 0 (like bedtools genomecov -bga)",
+        long = "zeros"
+    )]
+    zeros: bool,
+    #[arg(
+        help = "write the number of bases at each depth per chromosome and genome-wide to this file",
+        long = "histogram"
+    )]
+    histogram: Option<PathBuf>,
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+struct PartitionArgs {
+    #[arg(help = "sorted input files", short = 'b', required = true)]
+    other_paths: Vec<PathBuf>,
+    #[arg(
+        help = "genome file for chromosome ordering",
+        short = 'g',
+        required = true
+    )]
+    genome_file: PathBuf,
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+struct MergeArgs {
+    #[arg(help = "sorted input files", short = 'b', required = true)]
+    other_paths: Vec<PathBuf>,
+    #[arg(
+        help = "genome file for chromosome ordering",
+        short = 'g',
+        required = true
+    )]
+    genome_file: PathBuf,
+    #[arg(
+        help = "write only the first of identical records (same position and line)",
+        long = "dedup"
+    )]
+    dedup: bool,
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+struct SortArgs {
+    #[arg(help = "input file in any order", short = 'a')]
+    query_path: PathBuf,
+    #[arg(
+        help = "genome file for chromosome ordering",
+        short = 'g',
+        required = true
+    )]
+    genome_file: PathBuf,
+    #[arg(
+        help = "megabytes of records to sort in memory before writing them to temporary files",
+        long = "max-memory",
+        default_value_t = bedder::sort::DEFAULT_MAX_MEMORY >> 20
+    )]
+    max_memory: usize,
+    #[arg(
+        help = "directory for temporary files [default: the system temporary directory]",
+        long = "tmp-dir"
+    )]
+    tmp_dir: Option<PathBuf>,
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(Args, Debug)]
+struct CheckSortedArgs {
+    #[arg(help = "files to check", short = 'b', required = true)]
+    other_paths: Vec<PathBuf>,
+    #[arg(
+        help = "genome file for chromosome ordering",
+        short = 'g',
+        required = true
+    )]
+    genome_file: PathBuf,
+    #[arg(
+        help = "report at most this many violations per file",
+        short = 'n',
+        long = "max-violations",
+        default_value_t = 10
+    )]
+    max_violations: usize,
+    #[arg(
+        help = "also require intervals with the same start to be sorted by stop, as the -a file of intersect must be. by default, files are checked like -b files",
+        long = "check-stop"
+    )]
+    check_stop: bool,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum Preset {
+    Bed,
+    Gff,
+    Vcf,
+}
 
-    let a_iter = sniff::open_file(&args.query_path)?;
-    let b_iters: Vec<_> = args
+#[derive(Args, Debug)]
+struct IndexArgs {
+    #[arg(help = "bgzipped file to index")]
+    path: PathBuf,
+    #[arg(
+        help = "column layout of the file [default: guessed from the extension, else bed]",
+        short = 'p',
+        long = "preset"
+    )]
+    preset: Option<Preset>,
+    #[arg(
+        help = "1-based column of the chromosome name",
+        short = 's',
+        long = "sequence"
+    )]
+    sequence: Option<usize>,
+    #[arg(
+        help = "1-based column of the start position",
+        short = 'b',
+        long = "begin"
+    )]
+    begin: Option<usize>,
+    #[arg(
+        help = "1-based column of the end position. 0 if there is no end column",
+        short = 'e',
+        long = "end"
+    )]
+    end: Option<usize>,
+    #[arg(
+        help = "positions are 0-based, half-open (as in BED) instead of 1-based, inclusive",
+        short = '0',
+        long = "zero-based"
+    )]
+    zero_based: bool,
+    #[arg(
+        help = "skip lines starting with this character",
+        short = 'c',
+        long = "comment"
+    )]
+    comment: Option<char>,
+    #[arg(
+        help = "skip this many lines at the start of the file",
+        short = 'S',
+        long = "skip-lines"
+    )]
+    skip_lines: Option<u32>,
+    #[arg(
+        help = "create a CSI index instead of tabix",
+        short = 'C',
+        long = "csi"
+    )]
+    csi: bool,
+    #[arg(
+        help = "min-shift of the CSI index (implies --csi). positions past 2^29 (512Mbp) need a CSI index",
+        short = 'm',
+        long = "min-shift",
+        value_parser = clap::value_parser!(u8).range(
+            i64::from(*index::MIN_SHIFT_RANGE.start())..=i64::from(*index::MIN_SHIFT_RANGE.end())
+        )
+    )]
+    min_shift: Option<u8>,
+}
+
+impl IndexArgs {
+    fn format(&self) -> IndexFormat {
+        match (self.csi, self.min_shift) {
+            (false, None) => IndexFormat::Tabix,
+            (_, min_shift) => IndexFormat::Csi {
+                min_shift: min_shift.unwrap_or(index::DEFAULT_MIN_SHIFT),
+            },
+        }
+    }
+
+    /// the index header from the preset with any column options applied.
+    fn header(&self) -> Result<csi::index::Header, Box<dyn std::error::Error>> {
+        use csi::index::header::{format::CoordinateSystem, Builder, Format};
+        let preset = self.preset.unwrap_or_else(|| {
+            let p = self.path.to_string_lossy();
+            let p = p.trim_end_matches(".gz").trim_end_matches(".bgz");
+            if p.ends_with(".gff") || p.ends_with(".gff3") || p.ends_with(".gtf") {
+                Preset::Gff
+            } else if p.ends_with(".vcf") {
+                Preset::Vcf
+            } else {
+                Preset::Bed
+            }
+        });
+        let mut builder = match preset {
+            Preset::Bed => Builder::bed(),
+            Preset::Gff => Builder::gff(),
+            Preset::Vcf => Builder::vcf(),
+        };
+        let column = |name: &str, c: usize| match c {
+            0 => Err(format!("--{} is a 1-based column", name)),
+            c => Ok(c - 1),
+        };
+        if let Some(c) = self.sequence {
+            builder = builder.set_reference_sequence_name_index(column("sequence", c)?);
+        }
+        if let Some(c) = self.begin {
+            builder = builder.set_start_position_index(column("begin", c)?);
+        }
+        if let Some(c) = self.end {
+            builder = builder.set_end_position_index(c.checked_sub(1));
+        }
+        if self.zero_based {
+            builder = builder.set_format(Format::Generic(CoordinateSystem::Bed));
+        }
+        if let Some(c) = self.comment {
+            let c = u8::try_from(c).map_err(|_| "--comment must be an ASCII character")?;
+            builder = builder.set_line_comment_prefix(c);
+        }
+        if let Some(n) = self.skip_lines {
+            builder = builder.set_line_skip_count(n);
+        }
+        Ok(builder.build())
+    }
+}
+
+fn parse_aggregation(s: &str) -> Result<Aggregation, String> {
+    s.parse::<Aggregation>().map_err(|e| e.to_string())
+}
+
+/// insert the `intersect` subcommand when the first argument is an option other
+/// than help or version, so that `bedder -a ... -b ... -g ...` keeps working.
+fn with_default_command(mut args: Vec<OsString>) -> Vec<OsString> {
+    if let Some(first) = args.get(1).and_then(|a| a.to_str()) {
+        if first.starts_with('-') && !matches!(first, "-h" | "--help" | "-V" | "--version") {
+            args.insert(1, OsString::from("intersect"));
+        }
+    }
+    args
+}
+
+fn parse_region(s: &str) -> Result<Region, String> {
+    s.parse::<Region>().map_err(|e| e.to_string())
+}
+
+fn parse_index_format(s: &str) -> Result<IndexFormat, String> {
+    s.parse::<IndexFormat>().map_err(|e| e.to_string())
+}
+
+/// Where results are written: stdout, a text file or a BGZF file with an optional index.
+enum Output {
+    Text(io::BufWriter<Box<dyn Write>>),
+    Bgzf {
+        writer: Box<BgzfWriter<std::fs::File>>,
+        path: PathBuf,
+    },
+}
+
+impl Output {
+    /// `query_path` determines the columns described by the index.
+    fn new(args: &OutputArgs, query_path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let header = match args.index {
+            Some(_) => {
+                let mut reader = io::BufReader::new(std::fs::File::open(query_path)?);
+                match sniff::detect_file_format(&mut reader, query_path)?.0 {
+                    sniff::FileFormat::VCF => csi::index::header::Builder::vcf(),
+                    _ => csi::index::header::Builder::bed(),
+                }
+            }
+            None => csi::index::header::Builder::bed(),
+        };
+        Output::with_index_header(args, header.build())
+    }
+
+    /// like `new` for output with the columns described by `header`.
+    fn with_index_header(
+        args: &OutputArgs,
+        header: csi::index::Header,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = match &args.output {
+            None => return Ok(Output::Text(io::BufWriter::new(Box::new(io::stdout())))),
+            Some(path) => path,
+        };
+        if !path.to_string_lossy().ends_with(".gz") {
+            if args.index.is_some() {
+                return Err("--index requires an output path ending in .gz".into());
+            }
+            let f = std::fs::File::create(path)?;
+            return Ok(Output::Text(io::BufWriter::new(Box::new(f))));
+        }
+        let mut writer = BgzfWriter::create(path)?;
+        if let Some(format) = args.index {
+            writer = writer.with_index(format, header);
+        }
+        Ok(Output::Bgzf {
+            writer: Box::new(writer),
+            path: path.clone(),
+        })
+    }
+
+    /// write header `text` which may hold many lines, each ending in a newline.
+    fn write_header(&mut self, text: &str) -> io::Result<()> {
+        match self {
+            Output::Text(w) => w.write_all(text.as_bytes()),
+            Output::Bgzf { writer, .. } => {
+                for line in text.lines() {
+                    writer.write_header_line(line)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// write `line` (a newline is added). `p` is the position used to index the line.
+    fn write_line(
+        &mut self,
+        p: &dyn Positioned,
+        line: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Output::Text(w) => writeln!(w, "{}", line)?,
+            Output::Bgzf { writer, .. } => writer.write_record(p, line)?,
+        }
+        Ok(())
+    }
+
+    /// flush the output and write the index, if any.
+    fn finish(self) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Output::Text(mut w) => w.flush()?,
+            Output::Bgzf { writer, path } => {
+                let format = writer.index_format();
+                if let (_, Some(index)) = writer.finish()? {
+                    let format = format.expect("an index has a format");
+                    format.write(format.index_path(&path), &index)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn intersection_iterator<'a>(
+    args: &InputArgs,
+    chrom_order: &'a HashMap<bedder::string::String, usize>,
+) -> bedder::error::Result<IntersectionIterator<'a>> {
+    let a_iter = args.open(&args.query_path)?;
+    intersection_iterator_with(args, a_iter, chrom_order)
+}
+
+/// like `intersection_iterator` for a query that is already open.
+fn intersection_iterator_with<'a>(
+    args: &InputArgs,
+    a_iter: DynPositionedIterator,
+    chrom_order: &'a HashMap<bedder::string::String, usize>,
+) -> bedder::error::Result<IntersectionIterator<'a>> {
+    let regions = args.regions(chrom_order)?;
+    let restrict = |iter: DynPositionedIterator| -> DynPositionedIterator {
+        match &regions {
+            Some(regions) => Box::new(RegionFilter::new(iter, Rc::clone(regions))),
+            None => iter,
+        }
+    };
+    let a_iter = restrict(a_iter);
+    let b_iters = args
         .other_paths
         .iter()
-        .map(|p| sniff::open_file(p).expect("error opening file"))
-        .collect();
+        .map(|path| args.open_other(path, chrom_order).map(restrict))
+        .collect::<bedder::error::Result<Vec<_>>>()?;
+
+    IntersectionIterator::new(a_iter, b_iters, chrom_order)
+}
+
+/// write the original line of `p` if available, otherwise its bed3 coordinates.
+fn write_positioned<W: std::fmt::Write>(w: &mut W, p: &dyn Positioned) -> std::fmt::Result {
+    match p.line() {
+        Some(line) => write!(w, "{}", line),
+        None => write!(w, "{}\t{}\t{}", p.chrom(), p.start(), p.stop()),
+    }
+}
+
+/// write the query and an overlapping interval (with its source index if there are several).
+fn write_overlap<W: std::fmt::Write>(
+    w: &mut W,
+    base: &dyn Positioned,
+    o: &Intersection<Box<dyn Positioned>>,
+    multiple_sources: bool,
+) -> std::fmt::Result {
+    write_positioned(w, base)?;
+    if multiple_sources {
+        write!(w, "\t{}", o.id)?;
+    }
+    w.write_char('\t')?;
+    write_positioned(w, o.interval.as_ref())
+}
 
-    let ii = bedder::intersection::IntersectionIterator::new(a_iter, b_iters, &chrom_order)?;
+fn intersect(args: IntersectArgs) -> Result<(), Box<dyn std::error::Error>> {
+    if args.output.index.is_some() && !args.raw {
+        return Err("--index requires --raw output".into());
+    }
+    let mut output = Output::new(&args.output, &args.inputs.query_path)?;
+    let chrom_order =
+        bedder::genome_file::parse_genome(std::fs::File::open(&args.inputs.genome_file)?)?;
+    let (query_name, names) = args.inputs.names();
+    let ii = intersection_iterator(&args.inputs, &chrom_order)?;
+    let multiple_sources = args.inputs.other_paths.len() > 1;
+    let mut line = String::new();
     // iterate over the intersections
-    ii.for_each(|intersection| {
-        let intersection = intersection.expect("error getting intersection");
-        println!("{:?}", intersection);
-    });
+    for intersection in ii {
+        let intersection = intersection?;
+        let base = intersection.base_interval.as_ref();
+        if !args.raw {
+            line.clear();
+            write!(line, "{:?}", intersection)?;
+            output.write_line(base, &line)?;
+            continue;
+        }
+        if args.match_alleles {
+            for m in allele::allele_matches(&intersection, &query_name, &names)? {
+                line.clear();
+                write_overlap(&mut line, base, m.intersection, multiple_sources)?;
+                for (i, (a, b)) in m.alts.iter().enumerate() {
+                    let sep = if i == 0 { '\t' } else { ',' };
+                    write!(line, "{}{}:{}", sep, a + 1, b + 1)?;
+                }
+                output.write_line(base, &line)?;
+            }
+            continue;
+        }
+        for o in intersection.overlapping.iter() {
+            line.clear();
+            write_overlap(&mut line, base, o, multiple_sources)?;
+            output.write_line(base, &line)?;
+        }
+    }
+    output.finish()
+}
+
+fn map(args: MapArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let mut output = Output::new(&args.output, &args.inputs.query_path)?;
+    let chrom_order =
+        bedder::genome_file::parse_genome(std::fs::File::open(&args.inputs.genome_file)?)?;
+    for a in args.aggregations.iter() {
+        if a.source as usize >= args.inputs.other_paths.len() {
+            return Err(format!("aggregation {} refers to a missing -b file", a).into());
+        }
+    }
+    let (query_name, names) = args.inputs.names();
+    let ii = intersection_iterator(&args.inputs, &chrom_order)?;
+    let mut line = String::new();
+    for intersection in ii {
+        let intersection = intersection?;
+        let base = intersection.base_interval.as_ref();
+        line.clear();
+        write_positioned(&mut line, base)?;
+        let matches = if args.match_alleles {
+            Some(allele::allele_matches(&intersection, &query_name, &names)?)
+        } else {
+            None
+        };
+        for a in args.aggregations.iter() {
+            let name = &names[a.source as usize];
+            let v = match &matches {
+                Some(matches) => a.apply_allele_matches(matches, name)?,
+                None => a.apply(&intersection, name)?,
+            };
+            match v {
+                Some(v) => write!(line, "\t{}", v)?,
+                None => line.push_str("\t."),
+            }
+        }
+        output.write_line(base, &line)?;
+    }
+    output.finish()
+}
+
+fn annotate(args: AnnotateArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let f = std::fs::File::open(&args.config)
+        .map_err(|e| format!("{}: {}", args.config.display(), e))?;
+    let annotations = annotate::read_config(
+        io::BufReader::new(f),
+        &args.config.to_string_lossy(),
+        args.inputs.other_paths.len(),
+    )?;
+    let mut sources = Vec::new();
+    for path in args.inputs.other_paths.iter() {
+        let mut reader = io::BufReader::new(std::fs::File::open(path)?);
+        let header = match sniff::detect_file_format(&mut reader, path)?.0 {
+            sniff::FileFormat::VCF | sniff::FileFormat::BCF => {
+                Some(sniff::open_vcf(path)?.header().clone())
+            }
+            _ => None,
+        };
+        sources.push(annotate::Source {
+            name: bedder::string::String::from(&*path.to_string_lossy()),
+            header,
+        });
+    }
+    let (header, query) = args.inputs.open_vcf(&args.inputs.query_path)?;
+    let annotator = annotate::VcfAnnotator::new(
+        header,
+        annotations,
+        &sources,
+        &args.inputs.query_path.to_string_lossy(),
+    )?;
+
+    let mut output = Output::new(&args.output, &args.inputs.query_path)?;
+    output.write_header(&annotator.header().to_string())?;
+    let chrom_order =
+        bedder::genome_file::parse_genome(std::fs::File::open(&args.inputs.genome_file)?)?;
+    let ii = intersection_iterator_with(&args.inputs, query, &chrom_order)?;
+    let mut line = String::new();
+    for intersection in ii {
+        let intersection = intersection?;
+        annotator.annotate(&intersection, &mut line)?;
+        output.write_line(intersection.base_interval.as_ref(), &line)?;
+    }
+    output.finish()
+}
+
+fn index(args: IndexArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let format = args.format();
+    let header = args.header()?;
+    let f =
+        std::fs::File::open(&args.path).map_err(|e| format!("{}: {}", args.path.display(), e))?;
+    let name = args.path.to_string_lossy();
+    let built = index::index_bgzf(io::BufReader::new(f), &name, format, header)?;
+    let index_path = format.index_path(&args.path);
+    format.write(&index_path, &built)?;
+
+    // validate by reading the index back and, for formats bedder can read, the records.
+    let read = format.read(&index_path)?;
+    if read.reference_sequences().len() != built.reference_sequences().len() {
+        return Err(format!("{}: index could not be read back", index_path.display()).into());
+    }
+    let mut reader = io::BufReader::new(std::fs::File::open(&args.path)?);
+    let detected = sniff::detect_file_format(&mut reader, &args.path)?.0;
+    if matches!(detected, sniff::FileFormat::BED | sniff::FileFormat::VCF) {
+        let mut records = sniff::open_file(&args.path)?;
+        let mut n = 0;
+        while let Some(r) = records.next_position(None) {
+            r?;
+            n += 1;
+        }
+        log::info!("read {} records from {}", n, args.path.display());
+    }
+    log::info!("wrote {}", index_path.display());
+    Ok(())
+}
+
+fn sv_compare(args: SvCompareArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let params = SvParams {
+        max_distance: args.max_distance,
+        min_reciprocal_overlap: args.reciprocal_overlap,
+        min_size_ratio: args.size_ratio,
+        match_type: !args.ignore_type,
+    };
+    let truth = sv::read_svs(
+        sniff::open_vcf(&args.truth_path)?,
+        &args.truth_path.to_string_lossy(),
+    )?;
+    let query = sv::read_svs(
+        sniff::open_vcf(&args.query_path)?,
+        &args.query_path.to_string_lossy(),
+    )?;
+    let comparison = sv::compare(&truth, &query, &params);
+
+    if let Some(path) = &args.variants {
+        let mut w = io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(w, "#set\tchrom\tstart\tstop\tsvtype\tsvlen\tstatus\tmatch")?;
+        let sets = [
+            ("truth", &truth, &query, &comparison.truth_matches, "FN"),
+            ("query", &query, &truth, &comparison.query_matches, "FP"),
+        ];
+        for (set, svs, others, matches, missing) in sets {
+            for (v, m) in svs.iter().zip(matches.iter()) {
+                let (status, other) = match m {
+                    Some(i) => {
+                        let o = &others[*i];
+                        ("TP", format!("{}:{}-{}", o.chrom, o.start + 1, o.stop))
+                    }
+                    None => (missing, String::from(".")),
+                };
+                writeln!(
+                    w,
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    set,
+                    v.chrom,
+                    v.start,
+                    v.stop,
+                    v.svtype.as_deref().unwrap_or("."),
+                    v.len,
+                    status,
+                    other
+                )?;
+            }
+        }
+        w.flush()?;
+    }
+
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "truth\t{}", truth.len())?;
+    writeln!(stdout, "query\t{}", query.len())?;
+    writeln!(stdout, "TP\t{}", comparison.true_positives())?;
+    writeln!(stdout, "FP\t{}", comparison.false_positives())?;
+    writeln!(stdout, "FN\t{}", comparison.false_negatives())?;
+    writeln!(stdout, "precision\t{:.4}", comparison.precision())?;
+    writeln!(stdout, "recall\t{:.4}", comparison.recall())?;
+    writeln!(stdout, "f1\t{:.4}", comparison.f1())?;
+    Ok(())
+}
+
+fn concordance(args: ConcordanceArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let chrom_order = bedder::genome_file::parse_genome(std::fs::File::open(&args.genome_file)?)?;
+    let open = |path: &Path| -> bedder::error::Result<_> {
+        let v = sniff::open_vcf(path)?.with_lines();
+        let header = v.header().clone();
+        let iter: DynPositionedIterator = Box::new(BoxPositioned::new(v));
+        Ok((iter, header))
+    };
+    let (truth, truth_header) = open(&args.truth_path)?;
+    let (query, query_header) = open(&args.query_path)?;
+    let mut c = Concordance::new(&truth_header, &query_header);
+    if c.samples().is_empty() {
+        return Err("no samples are shared by the truth and query VCFs".into());
+    }
+    let mut mismatches = match &args.mismatches {
+        Some(path) => {
+            let mut w = io::BufWriter::new(std::fs::File::create(path)?);
+            writeln!(w, "#chrom\tpos\tref\talt\tsample\ttruth\tquery")?;
+            Some(w)
+        }
+        None => None,
+    };
+    c.compare(truth, query, &chrom_order, |site, found| {
+        let w = match mismatches.as_mut() {
+            Some(w) => w,
+            None => return Ok(()),
+        };
+        let line = site.line().unwrap_or_default();
+        let columns: Vec<&str> = line.split('\t').collect();
+        for m in found {
+            writeln!(
+                w,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                site.chrom(),
+                site.start() + 1,
+                columns.get(3).unwrap_or(&"."),
+                columns.get(4).unwrap_or(&"."),
+                m.sample,
+                m.truth,
+                m.query
+            )?;
+        }
+        Ok(())
+    })?;
+    if let Some(mut w) = mismatches {
+        w.flush()?;
+    }
+
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+    write!(stdout, "#sample\tcalled\tconcordance\tnon_ref_discordance")?;
+    for t in GenotypeClass::ALL {
+        for q in GenotypeClass::ALL {
+            write!(stdout, "\t{}/{}", t, q)?;
+        }
+    }
+    writeln!(stdout)?;
+    let total = c.total();
+    let rows = c
+        .samples()
+        .iter()
+        .map(|s| s.as_str())
+        .zip(c.tables().iter())
+        .chain(std::iter::once(("ALL", &total)));
+    for (sample, table) in rows {
+        write!(
+            stdout,
+            "{}\t{}\t{:.4}\t{:.4}",
+            sample,
+            table.called(),
+            table.concordance(),
+            table.non_reference_discordance()
+        )?;
+        for t in GenotypeClass::ALL {
+            for q in GenotypeClass::ALL {
+                write!(stdout, "\t{}", table.get(t, q))?;
+            }
+        }
+        writeln!(stdout)?;
+    }
+    stdout.flush()?;
     Ok(())
 }
+
+fn complement(args: ComplementArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let genome =
+        bedder::genome_file::parse_genome_lengths(std::fs::File::open(&args.genome_file)?)?;
+    let mut gaps = Complement::new(sniff::open_file(&args.query_path)?, genome);
+    let mut output =
+        Output::with_index_header(&args.output, csi::index::header::Builder::bed().build())?;
+    let mut line = String::new();
+    while let Some(gap) = gaps.next_position(None) {
+        let gap = gap?;
+        line.clear();
+        write_positioned(&mut line, &gap)?;
+        output.write_line(&gap, &line)?;
+    }
+    output.finish()
+}
+
+fn bin_counts(args: BinCountsArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let genome =
+        bedder::genome_file::parse_genome_lengths(std::fs::File::open(&args.genome_file)?)?;
+    let chrom_order: HashMap<bedder::string::String, usize> = genome
+        .iter()
+        .enumerate()
+        .map(|(i, (chrom, _))| (chrom.clone(), i))
+        .collect();
+    let windows = match &args.regions_file {
+        Some(path) => {
+            let f = std::fs::File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            let regions = region::read_bed(io::BufReader::new(f), &path.to_string_lossy())?;
+            let regions = Regions::new(regions, &chrom_order)?;
+            Windows::new(regions.iter().cloned().collect(), args.window)
+        }
+        None => Windows::genome(&genome, args.window),
+    };
+    let windows: DynPositionedIterator = Box::new(BoxPositioned::new(windows));
+    let b_iters = args
+        .other_paths
+        .iter()
+        .map(|path| sniff::open_file(path))
+        .collect::<bedder::error::Result<Vec<_>>>()?;
+    let ii = IntersectionIterator::new(windows, b_iters, &chrom_order)?;
+
+    let mut output =
+        Output::with_index_header(&args.output, csi::index::header::Builder::bed().build())?;
+    let mut header = String::from("#chrom\tstart\tstop");
+    for path in args.other_paths.iter() {
+        write!(header, "\t{}", path.display())?;
+    }
+    header.push('\n');
+    output.write_header(&header)?;
+    let mut counts = vec![0u64; args.other_paths.len()];
+    let mut line = String::new();
+    for intersection in ii {
+        let intersection = intersection?;
+        counts.iter_mut().for_each(|c| *c = 0);
+        for o in intersection.overlapping.iter() {
+            counts[o.id as usize] += 1;
+        }
+        line.clear();
+        let window = intersection.base_interval.as_ref().as_ref();
+        write_positioned(&mut line, window)?;
+        for c in counts.iter() {
+            write!(line, "\t{}", c)?;
+        }
+        output.write_line(window, &line)?;
+    }
+    output.finish()
+}
+
+fn coverage(args: CoverageArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let genome =
+        bedder::genome_file::parse_genome_lengths(std::fs::File::open(&args.genome_file)?)?;
+    let mut segments = Coverage::with_genome(sniff::open_file(&args.query_path)?, genome);
+    let mut output =
+        Output::with_index_header(&args.output, csi::index::header::Builder::bed().build())?;
+    let mut histogram = Histogram::default();
+    let mut line = String::new();
+    while let Some(segment) = segments.next_position(None) {
+        let segment = segment?;
+        histogram.add(&segment);
+        if segment.depth == 0 && !args.zeros {
+            continue;
+        }
+        line.clear();
+        write!(
+            line,
+            "{}\t{}\t{}\t{}",
+            segment.chrom, segment.start, segment.stop, segment.depth
+        )?;
+        output.write_line(&segment, &line)?;
+    }
+    output.finish()?;
+    if let Some(path) = &args.histogram {
+        let mut w = io::BufWriter::new(std::fs::File::create(path)?);
+        histogram.write(&mut w)?;
+        w.flush()?;
+    }
+    Ok(())
+}
+
+fn partition(args: PartitionArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let chrom_order = bedder::genome_file::parse_genome(std::fs::File::open(&args.genome_file)?)?;
+    let iterators = args
+        .other_paths
+        .iter()
+        .map(|path| sniff::open_file(path))
+        .collect::<bedder::error::Result<Vec<_>>>()?;
+    let mut output =
+        Output::with_index_header(&args.output, csi::index::header::Builder::bed().build())?;
+    // name the sources before the column names.
+    let mut header = String::new();
+    for (i, path) in args.other_paths.iter().enumerate() {
+        writeln!(header, "#{}\t{}", i, path.display())?;
+    }
+    header.push_str("#chrom\tstart\tstop\tcount\tsources\n");
+    output.write_header(&header)?;
+    let mut line = String::new();
+    for partition in PartitionIterator::new(iterators, &chrom_order) {
+        let partition = partition?;
+        line.clear();
+        write!(
+            line,
+            "{}\t{}\t{}\t{}\t",
+            partition.chrom,
+            partition.start,
+            partition.stop,
+            partition.sources.len()
+        )?;
+        for (i, s) in partition.sources.iter().enumerate() {
+            if i > 0 {
+                line.push(',');
+            }
+            write!(line, "{}", s)?;
+        }
+        output.write_line(&partition, &line)?;
+    }
+    output.finish()
+}
+
+/// open `path` so that every record has a line, along with the header of a VCF or BCF.
+fn open_with_lines(
+    path: &Path,
+) -> Result<(DynPositionedIterator, Option<String>), Box<dyn std::error::Error>> {
+    let mut reader = io::BufReader::new(std::fs::File::open(path)?);
+    match sniff::detect_file_format(&mut reader, path)?.0 {
+        sniff::FileFormat::VCF | sniff::FileFormat::BCF => {
+            // BCF records are written as VCF text.
+            let vcf = sniff::open_vcf(path)?.with_lines();
+            let header = vcf.header().to_string();
+            Ok((Box::new(BoxPositioned::new(vcf)), Some(header)))
+        }
+        _ => Ok((sniff::open_file(path)?, None)),
+    }
+}
+
+/// the columns described by an index of VCF or BED output.
+fn index_header(vcf: bool) -> csi::index::Header {
+    if vcf {
+        csi::index::header::Builder::vcf().build()
+    } else {
+        csi::index::header::Builder::bed().build()
+    }
+}
+
+fn sort(args: SortArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let chrom_order = bedder::genome_file::parse_genome(std::fs::File::open(&args.genome_file)?)?;
+    let (iterator, header) = open_with_lines(&args.query_path)?;
+    let mut sorter = Sorter::new(&chrom_order).with_max_memory(args.max_memory << 20);
+    if let Some(dir) = &args.tmp_dir {
+        sorter = sorter.with_tmp_dir(dir);
+    }
+    let mut sorted = sorter.sort(iterator)?;
+    let mut output = Output::with_index_header(&args.output, index_header(header.is_some()))?;
+    if let Some(header) = header {
+        output.write_header(&header)?;
+    }
+    while let Some(record) = sorted.next_position(None) {
+        let record = record?;
+        output.write_line(&record, &record.line)?;
+    }
+    output.finish()
+}
+
+fn merge(args: MergeArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let chrom_order = bedder::genome_file::parse_genome(std::fs::File::open(&args.genome_file)?)?;
+    let mut iterators = Vec::new();
+    let mut header = None;
+    for (i, path) in args.other_paths.iter().enumerate() {
+        let (iterator, vcf_header) = open_with_lines(path)?;
+        iterators.push(iterator);
+        // the header of the first file describes the merged VCF.
+        if i == 0 {
+            header = vcf_header;
+        }
+    }
+    let mut output = Output::with_index_header(&args.output, index_header(header.is_some()))?;
+    if let Some(header) = header {
+        output.write_header(&header)?;
+    }
+    let mut merged = MergeIterator::new(iterators, &chrom_order);
+    if args.dedup {
+        merged = merged.with_dedup();
+    }
+    let mut line = String::new();
+    while let Some(p) = merged.next_position(None) {
+        let p = p?;
+        line.clear();
+        write_positioned(&mut line, &p)?;
+        output.write_line(&p, &line)?;
+        merged.recycle(p);
+    }
+    output.finish()
+}
+
+fn check_sorted(args: CheckSortedArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let chrom_order = bedder::genome_file::parse_genome(std::fs::File::open(&args.genome_file)?)?;
+    let mut stdout = io::BufWriter::new(io::stdout());
+    let mut unsorted = 0;
+    for path in args.other_paths.iter() {
+        let mut iter = sniff::open_file(path)?;
+        let mut order = if args.check_stop {
+            OrderValidator::new()
+        } else {
+            OrderValidator::new().without_stop_order()
+        };
+        let mut violations = 0;
+        while let Some(p) = iter.next_position(None) {
+            let p = p?;
+            let violation = match chrom_order.get(p.chrom()) {
+                None => format!("unknown chromosome: {}", p.chrom()),
+                Some(chrom_id) => match order.check(*chrom_id, &p) {
+                    None => continue,
+                    Some(v) => v.to_string(),
+                },
+            };
+            violations += 1;
+            if violations <= args.max_violations {
+                writeln!(stdout, "{}: {}", iter.name(), violation)?;
+            }
+        }
+        if violations == 0 {
+            writeln!(stdout, "{}: sorted", path.display())?;
+        } else {
+            unsorted += 1;
+            writeln!(stdout, "{}: {} violations", path.display(), violations)?;
+        }
+    }
+    stdout.flush()?;
+    if unsorted > 0 {
+        return Err(format!(
+            "{} of {} files are not sorted",
+            unsorted,
+            args.other_paths.len()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+pub fn main() {
+    if env::var("RUST_LOG").is_err() {
+        env::set_var("RUST_LOG", "bedder=info");
+    }
+    env_logger::init();
+    log::info!("starting up");
+    let cli = Cli::parse_from(with_default_command(env::args_os().collect()));
+
+    let result = match cli.command {
+        Commands::Intersect(args) => intersect(args),
+        Commands::Map(args) => map(args),
+        Commands::Annotate(args) => annotate(args),
+        Commands::Index(args) => index(args),
+        Commands::SvCompare(args) => sv_compare(args),
+        Commands::Concordance(args) => concordance(args),
+        Commands::Complement(args) => complement(args),
+        Commands::BinCounts(args) => bin_counts(args),
+        Commands::Coverage(args) => coverage(args),
+        Commands::Partition(args) => partition(args),
+        Commands::Merge(args) => merge(args),
+        Commands::Sort(args) => sort(args),
+        Commands::CheckSorted(args) => check_sorted(args),
+    };
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        let args = args.iter().map(OsString::from).collect();
+        Cli::try_parse_from(with_default_command(args))
+    }
+
+    #[test]
+    fn test_default_command() {
+        for args in [
+            vec!["bedder", "-a", "a.bed", "-b", "b.bed", "-g", "g.txt"],
+            vec![
+                "bedder",
+                "intersect",
+                "-a",
+                "a.bed",
+                "-b",
+                "b.bed",
+                "-g",
+                "g.txt",
+            ],
+        ] {
+            let Commands::Intersect(a) = parse(&args).unwrap().command else {
+                panic!("expected intersect");
+            };
+            assert_eq!(a.inputs.query_path, PathBuf::from("a.bed"));
+            assert_eq!(a.inputs.other_paths, vec![PathBuf::from("b.bed")]);
+        }
+        assert_eq!(
+            parse(&["bedder", "--help"]).unwrap_err().kind(),
+            clap::error::ErrorKind::DisplayHelp
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use crate::error::Result;
+use crate::intersection::{LastChrom, ReverseOrderPosition};
+use crate::order::OrderValidator;
+use crate::position::{
+    DynPositionedIterator, Field, FieldError, Positioned, PositionedIterator, Value,
+};
+use crate::string::{to_string_type, String};
+use std::collections::{BinaryHeap, HashMap};
+use std::result;
+
+/// An interval from one of the iterators of a `MergeIterator`.
+#[derive(Debug)]
+pub struct Merged<P> {
+    pub position: P,
+    /// the index of the iterator that the interval came from.
+    pub id: u32,
+}
+
+impl<P: Positioned> Positioned for Merged<P> {
+    fn chrom(&self) -> &str {
+        self.position.chrom()
+    }
+
+    fn start(&self) -> u64 {
+        self.position.start()
+    }
+
+    fn stop(&self) -> u64 {
+        self.position.stop()
+    }
+
+    fn value(&self, f: Field) -> result::Result<Value, FieldError> {
+        self.position.value(f)
+    }
+
+    fn line(&self) -> Option<&str> {
+        self.position.line()
+    }
+}
+
+/// An iterator that merges the intervals of many sorted iterators into a single sorted
+/// stream, e.g. to combine per-chromosome or per-sample files without sorting them again.
+/// Identical intervals are returned in the order of their iterators.
+pub struct MergeIterator<'a, I = DynPositionedIterator>
+where
+    I: PositionedIterator,
+{
+    iterators: Vec<I>,
+    chroms: Vec<LastChrom>,
+    // check that the intervals of each iterator are sorted.
+    orders: Vec<OrderValidator>,
+    // the number of intervals read from each iterator, to locate errors.
+    lines: Vec<u64>,
+    min_heap: BinaryHeap<ReverseOrderPosition<I::Item>>,
+    chromosome_order: &'a HashMap<String, usize>,
+    heap_initialized: bool,
+    dedup: bool,
+    // the position of the last returned interval and the lines returned at that position.
+    dedup_position: Option<(usize, u64, u64)>,
+    dedup_lines: Vec<Option<std::string::String>>,
+}
+
+impl<'a, I: PositionedIterator> MergeIterator<'a, I> {
+    pub fn new(iterators: Vec<I>, chromosome_order: &'a HashMap<String, usize>) -> Self {
+        let n = iterators.len();
+        MergeIterator {
+            iterators,
+            chroms: (0..n).map(|_| LastChrom::default()).collect(),
+            orders: (0..n)
+                .map(|_| OrderValidator::new().without_stop_order())
+                .collect(),
+            lines: vec![0; n],
+            min_heap: BinaryHeap::new(),
+            chromosome_order,
+            heap_initialized: false,
+            dedup: false,
+            dedup_position: None,
+            dedup_lines: Vec::new(),
+        }
+    }
+
+    /// Skip intervals identical to one that was already returned: those with the same
+    /// position and, for text formats, the same line. Only the first is kept.
+    pub fn with_dedup(mut self) -> Self {
+        self.dedup = true;
+        self
+    }
+
+    /// add the next interval from iterator `i` to the heap.
+    fn pull(&mut self, i: usize) -> Result<()> {
+        let p = match self.iterators[i].next_position(None) {
+            None => return Ok(()),
+            Some(p) => p?,
+        };
+        self.lines[i] += 1;
+        let iterator = &self.iterators[i];
+        let chrom_id = self.chroms[i].resolve(&p, self.chromosome_order, || iterator.name())?;
+        if let Some(v) = self.orders[i].check(chrom_id, &p) {
+            return Err(v.into_error(iterator.name(), self.lines[i]));
+        }
+        self.min_heap.push(ReverseOrderPosition {
+            position: p,
+            chrom_id,
+            id: i,
+            line: self.lines[i],
+        });
+        Ok(())
+    }
+
+    /// true if an interval identical to `p` was already returned.
+    fn is_duplicate(&mut self, chrom_id: usize, p: &I::Item) -> bool {
+        let position = Some((chrom_id, p.start(), p.stop()));
+        if self.dedup_position != position {
+            self.dedup_position = position;
+            self.dedup_lines.clear();
+        }
+        let line = p.line();
+        if self.dedup_lines.iter().any(|l| l.as_deref() == line) {
+            return true;
+        }
+        self.dedup_lines.push(line.map(|l| l.to_string()));
+        false
+    }
+
+    fn next_merged(&mut self) -> Result<Option<Merged<I::Item>>> {
+        if !self.heap_initialized {
+            for i in 0..self.iterators.len() {
+                self.pull(i)?;
+            }
+            self.heap_initialized = true;
+        }
+        while let Some(top) = self.min_heap.pop() {
+            self.pull(top.id)?;
+            if self.dedup && self.is_duplicate(top.chrom_id, &top.position) {
+                self.iterators[top.id].recycle(top.position);
+                continue;
+            }
+            return Ok(Some(Merged {
+                position: top.position,
+                id: top.id as u32,
+            }));
+        }
+        Ok(None)
+    }
+}
+
+impl<'a, I: PositionedIterator> PositionedIterator for MergeIterator<'a, I> {
+    type Item = Merged<I::Item>;
+
+    fn next_position(&mut self, _q: Option<&dyn Positioned>) -> Option<Result<Self::Item>> {
+        self.next_merged().transpose()
+    }
+
+    fn name(&self) -> String {
+        let names: Vec<_> = self.iterators.iter().map(|it| it.name()).collect();
+        to_string_type(format!("merge of {}", names.join(", ")))
+    }
+
+    fn recycle(&mut self, p: Self::Item) {
+        self.iterators[p.id as usize].recycle(p.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bedder_bed::BedderBed;
+    use crate::error::Error;
+    use std::io::Cursor;
+
+    fn merge(beds: &[&str], dedup: bool) -> Result<Vec<(u32, std::string::String)>> {
+        let order = HashMap::from([(String::from("chr1"), 0), (String::from("chr2"), 1)]);
+        let iterators = beds
+            .iter()
+            .map(|s| BedderBed::new(Cursor::new(s.to_string()), "t.bed"))
+            .collect();
+        let mut m = MergeIterator::new(iterators, &order);
+        if dedup {
+            m = m.with_dedup();
+        }
+        let mut v = Vec::new();
+        while let Some(p) = m.next_position(None) {
+            let p = p?;
+            v.push((p.id, p.line().unwrap().to_string()));
+            m.recycle(p);
+        }
+        Ok(v)
+    }
+
+    #[test]
+    fn test_merge() {
+        let a = "chr1\t10\t20\tx\nchr2\t5\t10\tx\n";
+        let b = "chr1\t5\t15\ty\nchr1\t10\t20\tx\nchr2\t0\t10\ty\n";
+        let c = "chr1\t10\t20\tz\n";
+        let expected: Vec<_> = [
+            (1, "chr1\t5\t15\ty"),
+            (0, "chr1\t10\t20\tx"),
+            (1, "chr1\t10\t20\tx"),
+            (2, "chr1\t10\t20\tz"),
+            (1, "chr2\t0\t10\ty"),
+            (0, "chr2\t5\t10\tx"),
+        ]
+        .into_iter()
+        .map(|(id, l)| (id, l.to_string()))
+        .collect();
+        assert_eq!(merge(&[a, b, c], false).unwrap(), expected);
+
+        // only the first of the identical intervals is kept; z differs in its line.
+        let mut deduped = expected.clone();
+        deduped.remove(2);
+        assert_eq!(merge(&[a, b, c], true).unwrap(), deduped);
+    }
+
+    #[test]
+    fn test_merge_errors() {
+        let e = merge(&["chr1\t20\t30\nchr1\t10\t15\n"], false).unwrap_err();
+        assert!(matches!(e, Error::OutOfOrder { .. }));
+        let e = merge(&["chr2\t20\t30\nchr1\t10\t15\n"], false).unwrap_err();
+        assert!(matches!(e, Error::OutOfOrder { .. }));
+        let e = merge(&["chrX\t20\t30\n"], false).unwrap_err();
+        assert!(matches!(e, Error::UnknownChromosome { .. }));
+    }
+}
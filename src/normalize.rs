@@ -0,0 +1,405 @@
+use crate::bedder_vcf::{format_number, info_number, BedderVCF, VcfRecord};
+use crate::error::{Error, Result};
+use crate::fasta::IndexedFasta;
+use crate::position::{Positioned, PositionedIterator};
+use crate::string::{to_string_type, String};
+use noodles::vcf::{self, header::Number};
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, Read, Seek};
+
+/// indels are moved left by at most this many bases. Records are held back for this
+/// distance so that the normalized records stay sorted.
+pub const MAX_SHIFT: u64 = 1000;
+
+/// keep the elements of a comma-separated value that belong to ALT `k` (1-based) of
+/// `n_alts`. Values without the expected number of elements are kept whole.
+fn pick_allele(
+    value: &str,
+    number: Option<Number>,
+    k: usize,
+    n_alts: usize,
+) -> std::string::String {
+    let v: Vec<&str> = value.split(',').collect();
+    let n_alleles = n_alts + 1;
+    // genotype order for diploid calls: (a, b) with a <= b is at b * (b + 1) / 2 + a.
+    let idx: Vec<usize> = match number {
+        Some(Number::A) if v.len() == n_alts => vec![k - 1],
+        Some(Number::R) if v.len() == n_alleles => vec![0, k],
+        Some(Number::G) if v.len() == n_alleles * (n_alleles + 1) / 2 => {
+            vec![0, k * (k + 1) / 2, k * (k + 1) / 2 + k]
+        }
+        Some(Number::G) if v.len() == n_alleles => vec![0, k],
+        _ => return value.to_string(),
+    };
+    idx.iter().map(|&i| v[i]).collect::<Vec<_>>().join(",")
+}
+
+/// rewrite a `GT` for ALT `k`: `k` becomes 1 and other ALTs become 0 (reference).
+fn pick_genotype(gt: &str, k: usize) -> std::string::String {
+    let mut out = std::string::String::with_capacity(gt.len());
+    let mut rest = gt;
+    loop {
+        let end = rest.find(['/', '|']).unwrap_or(rest.len());
+        match rest[..end].parse::<usize>() {
+            Ok(a) if a == k => out.push('1'),
+            Ok(_) => out.push('0'),
+            Err(_) => out.push_str(&rest[..end]),
+        }
+        if end == rest.len() {
+            return out;
+        }
+        // keep the separator to keep the phasing.
+        out.push_str(&rest[end..end + 1]);
+        rest = &rest[end + 1..];
+    }
+}
+
+/// split a multi-allelic VCF line into one line per ALT. INFO and FORMAT fields with
+/// Number=A, R or G keep only the values of that ALT, and genotypes are recoded so the ALT
+/// is 1. Lines with a single ALT are returned as they are.
+pub fn split_alleles(line: &str, header: &vcf::Header) -> Vec<std::string::String> {
+    let columns: Vec<&str> = line.split('\t').collect();
+    if columns.len() < 8 {
+        return vec![line.to_string()];
+    }
+    let alts: Vec<&str> = columns[4].split(',').collect();
+    if alts.len() < 2 {
+        return vec![line.to_string()];
+    }
+    let keys: Vec<&str> = columns
+        .get(8)
+        .map(|f| f.split(':').collect())
+        .unwrap_or_default();
+    let n_alts = alts.len();
+    let mut lines = Vec::with_capacity(n_alts);
+    for (i, alt) in alts.iter().enumerate() {
+        let k = i + 1;
+        let mut out: Vec<std::string::String> = columns.iter().map(|c| c.to_string()).collect();
+        out[4] = alt.to_string();
+        if columns[7] != "." {
+            out[7] = columns[7]
+                .split(';')
+                .map(|field| match field.split_once('=') {
+                    Some((key, value)) => format!(
+                        "{}={}",
+                        key,
+                        pick_allele(value, info_number(header, key), k, n_alts)
+                    ),
+                    None => field.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(";");
+        }
+        for sample in out.iter_mut().skip(9) {
+            *sample = sample
+                .split(':')
+                .zip(keys.iter())
+                .map(|(value, key)| match *key {
+                    "GT" => pick_genotype(value, k),
+                    key => pick_allele(value, format_number(header, key), k, n_alts),
+                })
+                .collect::<Vec<_>>()
+                .join(":");
+        }
+        lines.push(out.join("\t"));
+    }
+    lines
+}
+
+/// left-align and trim a REF/ALT pair starting at 0-based `position` using `reference`,
+/// the bases of the chromosome from `reference_start` up to at least the end of REF.
+/// Alleles are kept at least one base long by adding the preceding base when they would
+/// become empty. Returns None if REF does not match the reference.
+pub fn left_align(
+    position: u64,
+    reference_allele: &str,
+    alternate_allele: &str,
+    reference: &[u8],
+    reference_start: u64,
+) -> Option<(u64, std::string::String, std::string::String)> {
+    let offset = (position - reference_start) as usize;
+    let expected = reference.get(offset..offset + reference_allele.len())?;
+    if !expected.eq_ignore_ascii_case(reference_allele.as_bytes()) {
+        return None;
+    }
+    let mut r = reference_allele.to_ascii_uppercase().into_bytes();
+    let mut a = alternate_allele.to_ascii_uppercase().into_bytes();
+    let mut p = position;
+    loop {
+        // a shared last base can be removed if an allele that becomes empty can be extended.
+        if r.last() == a.last() && ((r.len() > 1 && a.len() > 1) || p > reference_start) {
+            r.pop();
+            a.pop();
+            if r.is_empty() || a.is_empty() {
+                p -= 1;
+                let base = reference[(p - reference_start) as usize].to_ascii_uppercase();
+                r.insert(0, base);
+                a.insert(0, base);
+            }
+        } else {
+            break;
+        }
+    }
+    while r.len() > 1 && a.len() > 1 && r[0] == a[0] {
+        r.remove(0);
+        a.remove(0);
+        p += 1;
+    }
+    // the bases were checked against the reference so they are ascii.
+    let text = |v: Vec<u8>| std::string::String::from_utf8(v).unwrap_or_default();
+    Some((p, text(r), text(a)))
+}
+
+fn is_sequence(allele: &str) -> bool {
+    !allele.is_empty() && allele.bytes().all(|b| b.is_ascii_alphabetic())
+}
+
+/// A VCF reader that splits multi-allelic records into one record per ALT and, given a
+/// FASTA, left-aligns and trims indels so that variants have the same position regardless
+/// of how a caller represented them. Only records with a single ALT are aligned, so
+/// alignment is usually used along with splitting.
+pub struct Normalizer<'a, R = io::BufReader<fs::File>> {
+    inner: BedderVCF<'a>,
+    fasta: Option<IndexedFasta<R>>,
+    split: bool,
+    // normalized records sorted by position.
+    buffer: VecDeque<VcfRecord>,
+    // position of the last record read from `inner`.
+    chrom: String,
+    start: u64,
+    done: bool,
+}
+
+impl<'a, R: Read + Seek> Normalizer<'a, R> {
+    pub fn new(inner: BedderVCF<'a>, split: bool, fasta: Option<IndexedFasta<R>>) -> Self {
+        Normalizer {
+            inner: inner.with_lines(),
+            fasta,
+            split,
+            buffer: VecDeque::new(),
+            chrom: String::default(),
+            start: 0,
+            done: false,
+        }
+    }
+
+    pub fn header(&self) -> &vcf::Header {
+        self.inner.header()
+    }
+
+    /// left-align `line` if it has a single sequence ALT. Returns None if it is unchanged.
+    fn align(&mut self, line: &str) -> Result<Option<std::string::String>> {
+        let fasta = match self.fasta.as_mut() {
+            Some(fasta) => fasta,
+            None => return Ok(None),
+        };
+        let mut columns: Vec<&str> = line.splitn(6, '\t').collect();
+        if columns.len() < 6 || !is_sequence(columns[3]) || !is_sequence(columns[4]) {
+            return Ok(None);
+        }
+        let position = match columns[1].parse::<u64>() {
+            Ok(p) if p > 0 => p - 1,
+            _ => return Ok(None),
+        };
+        let window = position.saturating_sub(MAX_SHIFT);
+        let reference = fasta.fetch(columns[0], window, position + columns[3].len() as u64)?;
+        let (p, r, a) = left_align(position, columns[3], columns[4], &reference, window)
+            .ok_or_else(|| Error::Parse {
+                name: self.inner.path().clone(),
+                line: self.inner.record_number(),
+                region: Some(to_string_type(format!("{}:{}", columns[0], columns[1]))),
+                message: to_string_type(format!("REF {} does not match the reference", columns[3])),
+            })?;
+        if p == position && r == columns[3] && a == columns[4] {
+            return Ok(None);
+        }
+        let p = (p + 1).to_string();
+        columns[1] = &p;
+        columns[3] = &r;
+        columns[4] = &a;
+        Ok(Some(columns.join("\t")))
+    }
+
+    fn normalize(&mut self, record: VcfRecord) -> Result<()> {
+        // records always have a line as the reader was made `with_lines`.
+        let original = record.line().unwrap_or_default().to_string();
+        let lines = if self.split {
+            split_alleles(&original, self.inner.header())
+        } else {
+            vec![original.clone()]
+        };
+        let unsplit = lines.len() == 1;
+        let mut record = Some(record);
+        for line in lines {
+            let normalized = match self.align(&line)? {
+                Some(aligned) => VcfRecord::parse(aligned, self.inner.shared_header()),
+                // the original record can be kept if it was neither split nor aligned.
+                None if unsplit => Ok(record.take().expect("a single line")),
+                None => VcfRecord::parse(line, self.inner.shared_header()),
+            }
+            .map_err(|e| {
+                let region: Vec<&str> = original.splitn(3, '\t').take(2).collect();
+                Error::Parse {
+                    name: self.inner.path().clone(),
+                    line: self.inner.record_number(),
+                    region: Some(to_string_type(region.join(":"))),
+                    message: to_string_type(e.to_string()),
+                }
+            })?;
+            let pos = self.buffer.partition_point(|b| {
+                b.chrom() != normalized.chrom() || b.start() <= normalized.start()
+            });
+            self.buffer.insert(pos, normalized);
+        }
+        if let Some(record) = record {
+            self.inner.recycle(record);
+        }
+        Ok(())
+    }
+}
+
+impl<'a, R: Read + Seek> PositionedIterator for Normalizer<'a, R> {
+    type Item = VcfRecord;
+
+    fn next_position(&mut self, _q: Option<&dyn Positioned>) -> Option<Result<Self::Item>> {
+        loop {
+            match self.buffer.front() {
+                // later records can only move back MAX_SHIFT bases before their start.
+                Some(b)
+                    if self.done
+                        || b.chrom() != self.chrom
+                        || b.start() + MAX_SHIFT < self.start =>
+                {
+                    return self.buffer.pop_front().map(Ok)
+                }
+                None if self.done => return None,
+                _ => {}
+            }
+            match self.inner.next_position(None) {
+                None => self.done = true,
+                Some(Err(e)) => return Some(Err(e)),
+                Some(Ok(record)) => {
+                    if record.chrom() != self.chrom {
+                        self.chrom = String::from(record.chrom());
+                    }
+                    self.start = record.start();
+                    if let Err(e) = self.normalize(record) {
+                        return Some(Err(e));
+                    }
+                }
+            }
+        }
+    }
+
+    fn recycle(&mut self, p: Self::Item) {
+        self.inner.recycle(p)
+    }
+
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bedder_vcf::vcf_from_str;
+    use crate::fasta::read_fai;
+    use crate::position::{Field, Value};
+    use std::io::Cursor;
+
+    const HEADER: &str = "##fileformat=VCFv4.3\n##INFO=<ID=AF,Number=A,Type=Float,Description=\"AF\">\n##INFO=<ID=DP,Number=1,Type=Integer,Description=\"DP\">\n##FORMAT=<ID=GT,Number=1,Type=String,Description=\"GT\">\n##FORMAT=<ID=AD,Number=R,Type=Integer,Description=\"AD\">\n##FORMAT=<ID=PL,Number=G,Type=Integer,Description=\"PL\">\n#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\ts1\n";
+
+    fn vcf(records: &str) -> BedderVCF<'static> {
+        vcf_from_str(&format!("{}{}", HEADER, records), "t.vcf")
+    }
+
+    fn fasta(seq: &str) -> IndexedFasta<Cursor<std::string::String>> {
+        let data = format!(">chr1\n{}\n", seq);
+        let fai = format!("chr1\t{}\t6\t{}\t{}\n", seq.len(), seq.len(), seq.len() + 1);
+        let index = read_fai(fai.as_bytes(), "t.fa.fai").unwrap();
+        IndexedFasta::new(Cursor::new(data), index, "t.fa")
+    }
+
+    fn lines<R: Read + Seek>(mut n: Normalizer<R>) -> Vec<std::string::String> {
+        let mut lines = Vec::new();
+        while let Some(r) = n.next_position(None) {
+            lines.push(r.unwrap().line().unwrap().to_string());
+        }
+        lines
+    }
+
+    #[test]
+    fn test_split_alleles() {
+        let v = vcf("");
+        let split = split_alleles(
+            "chr1\t10\t.\tA\tC,T\t.\t.\tAF=0.1,0.2;DP=10\tGT:AD:PL\t1/2:1,2,3:0,1,2,3,4,5",
+            v.header(),
+        );
+        assert_eq!(
+            split,
+            vec![
+                "chr1\t10\t.\tA\tC\t.\t.\tAF=0.1;DP=10\tGT:AD:PL\t1/0:1,2:0,1,2",
+                "chr1\t10\t.\tA\tT\t.\t.\tAF=0.2;DP=10\tGT:AD:PL\t0/1:1,3:0,3,5",
+            ]
+        );
+        assert_eq!(pick_genotype("0|2", 2), "0|1");
+        assert_eq!(pick_genotype("./.", 1), "./.");
+    }
+
+    #[test]
+    fn test_left_align() {
+        //              0123456789
+        let reference = b"GGCACACAGT";
+        // deleting the last CA of the repeat is the same as deleting the first.
+        assert_eq!(
+            left_align(5, "ACA", "A", reference, 0),
+            Some((1, String::from("GCA"), String::from("G")))
+        );
+        // an insertion written with extra shared bases.
+        assert_eq!(
+            left_align(7, "AG", "ACAG", reference, 0),
+            Some((1, String::from("G"), String::from("GCA")))
+        );
+        // SNPs are only trimmed.
+        assert_eq!(
+            left_align(2, "CAC", "CTC", reference, 0),
+            Some((3, String::from("A"), String::from("T")))
+        );
+        assert_eq!(left_align(2, "T", "A", reference, 0), None);
+    }
+
+    #[test]
+    fn test_normalizer() {
+        let records = "chr1\t4\t.\tA\tC,ACA\t.\t.\tAF=0.1,0.2\tGT\t1/2\nchr1\t6\t.\tACA\tA\t.\t.\tAF=0.3\tGT\t0/1\n";
+        let n = Normalizer::new(vcf(records), true, Some(fasta("GGCACACAGT")));
+        assert_eq!(
+            lines(n),
+            vec![
+                "chr1\t2\t.\tG\tGCA\t.\t.\tAF=0.2\tGT\t0/1",
+                "chr1\t2\t.\tGCA\tG\t.\t.\tAF=0.3\tGT\t0/1",
+                "chr1\t4\t.\tA\tC\t.\t.\tAF=0.1\tGT\t1/0",
+            ]
+        );
+
+        let mut n = Normalizer::new(vcf(records), true, None::<IndexedFasta<Cursor<Vec<u8>>>>);
+        let first = n.next_position(None).unwrap().unwrap();
+        assert_eq!(
+            first.value(Field::String(String::from("INFO.AF"))).unwrap(),
+            Value::Floats(vec![Some(0.1f32 as f64)])
+        );
+        assert_eq!(n.next_position(None).unwrap().unwrap().start(), 3);
+        assert_eq!(n.next_position(None).unwrap().unwrap().start(), 5);
+        assert!(n.next_position(None).is_none());
+
+        // REF of the second record does not match the reference.
+        let mut n = Normalizer::new(
+            vcf("chr1\t3\t.\tC\tA\t.\t.\t.\tGT\t0/1\nchr1\t4\t.\tT\tC\t.\t.\t.\tGT\t0/1\n"),
+            false,
+            Some(fasta("GGCACACAGT")),
+        );
+        let e = n.next_position(None).unwrap().unwrap_err();
+        assert!(matches!(e, Error::Parse { line: 2, .. }), "{:?}", e);
+    }
+}
@@ -0,0 +1,212 @@
+use crate::error::Error;
+use crate::intersection::region_str;
+use crate::position::Positioned;
+use crate::string::{to_string_type, String};
+use std::collections::HashSet;
+use std::fmt;
+
+/// The ways in which an interval can be out of order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// the interval is on a chromosome that was already finished.
+    ChromosomeRevisited,
+    /// the interval starts a chromosome that comes before the previous one in the genome file.
+    ChromosomeOrder,
+    /// the interval starts before the previous interval.
+    Start,
+    /// the interval has the start of the previous interval but ends before it.
+    Stop,
+}
+
+impl fmt::Display for ViolationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ViolationKind::ChromosomeRevisited => write!(f, "chromosome revisited"),
+            ViolationKind::ChromosomeOrder => write!(f, "chromosome out of genome order"),
+            ViolationKind::Start => write!(f, "start before previous start"),
+            ViolationKind::Stop => write!(f, "stop before previous stop"),
+        }
+    }
+}
+
+/// An interval that is out of order with the interval before it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub kind: ViolationKind,
+    /// the region of the previous interval, e.g. chr1:11-20.
+    pub previous: std::string::String,
+    /// the region of the interval that is out of order.
+    pub current: std::string::String,
+}
+
+impl Violation {
+    /// the error for this violation of the `line`th interval of the iterator called `name`.
+    pub fn into_error(self, name: String, line: u64) -> Error {
+        Error::OutOfOrder {
+            name,
+            line,
+            kind: self.kind,
+            previous: to_string_type(self.previous),
+            current: to_string_type(self.current),
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} came before {}",
+            self.kind, self.previous, self.current
+        )
+    }
+}
+
+/// Checks that the intervals of a single iterator are sorted by the chromosome order of a
+/// genome file, then by start and stop. Chromosomes must be contiguous.
+#[derive(Debug)]
+pub struct OrderValidator {
+    check_stop: bool,
+    // chromosome index, start and stop of the previous interval.
+    previous: Option<(usize, u64, u64)>,
+    previous_chrom: String,
+    // chromosomes that were left for a later one.
+    finished: HashSet<usize>,
+}
+
+impl Default for OrderValidator {
+    fn default() -> Self {
+        OrderValidator::new()
+    }
+}
+
+impl OrderValidator {
+    pub fn new() -> Self {
+        OrderValidator {
+            check_stop: true,
+            previous: None,
+            previous_chrom: String::default(),
+            finished: HashSet::new(),
+        }
+    }
+
+    /// Allow intervals with the same start in any order of their stops.
+    pub fn without_stop_order(mut self) -> Self {
+        self.check_stop = false;
+        self
+    }
+
+    /// check that `p`, on the chromosome with index `chrom_id` in the genome order, is not
+    /// before the previous interval. `p` becomes the previous interval even if it is out of
+    /// order, so each violation is reported once.
+    #[inline]
+    pub fn check<P: Positioned>(&mut self, chrom_id: usize, p: &P) -> Option<Violation> {
+        let (previous_chrom_id, start, stop) = match self.previous {
+            Some(previous) => previous,
+            None => {
+                self.previous = Some((chrom_id, p.start(), p.stop()));
+                self.previous_chrom = String::from(p.chrom());
+                return None;
+            }
+        };
+        let kind = if chrom_id == previous_chrom_id {
+            if p.start() < start {
+                Some(ViolationKind::Start)
+            } else if self.check_stop && p.start() == start && p.stop() < stop {
+                Some(ViolationKind::Stop)
+            } else {
+                None
+            }
+        } else if self.finished.contains(&chrom_id) {
+            Some(ViolationKind::ChromosomeRevisited)
+        } else if chrom_id < previous_chrom_id {
+            Some(ViolationKind::ChromosomeOrder)
+        } else {
+            None
+        };
+        let violation = kind.map(|kind| Violation {
+            kind,
+            previous: format!("{}:{}-{}", self.previous_chrom, start + 1, stop),
+            current: region_str(p),
+        });
+        if chrom_id != previous_chrom_id {
+            self.finished.insert(previous_chrom_id);
+            self.previous_chrom = String::from(p.chrom());
+        }
+        self.previous = Some((chrom_id, p.start(), p.stop()));
+        violation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region::Region;
+
+    fn kinds(intervals: &[(&str, u64, u64)], check_stop: bool) -> Vec<Option<ViolationKind>> {
+        let order = ["chr1", "chr2", "chr3"];
+        let mut v = OrderValidator::new();
+        if !check_stop {
+            v = v.without_stop_order();
+        }
+        intervals
+            .iter()
+            .map(|(chrom, start, stop)| {
+                let r = Region {
+                    chrom: String::from(*chrom),
+                    start: *start,
+                    stop: *stop,
+                };
+                let chrom_id = order.iter().position(|c| c == chrom).unwrap();
+                v.check(chrom_id, &r).map(|v| v.kind)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_order_validator() {
+        use ViolationKind::*;
+        let intervals = [
+            ("chr1", 10, 20),
+            ("chr1", 10, 20),
+            ("chr1", 10, 15),
+            ("chr1", 5, 50),
+            ("chr2", 0, 10),
+            // an earlier chromosome with a larger start.
+            ("chr1", 100, 200),
+            ("chr3", 0, 10),
+            ("chr2", 50, 60),
+        ];
+        assert_eq!(
+            kinds(&intervals, true),
+            vec![
+                None,
+                None,
+                Some(Stop),
+                Some(Start),
+                None,
+                Some(ChromosomeRevisited),
+                None,
+                Some(ChromosomeRevisited)
+            ]
+        );
+        assert_eq!(kinds(&intervals, false)[2], None);
+        assert_eq!(
+            kinds(&[("chr2", 0, 10), ("chr1", 0, 10)], true),
+            vec![None, Some(ChromosomeOrder)]
+        );
+
+        let mut v = OrderValidator::new();
+        let r = |chrom: &str, start, stop| Region {
+            chrom: String::from(chrom),
+            start,
+            stop,
+        };
+        v.check(1, &r("chr2", 10, 20));
+        let violation = v.check(0, &r("chr1", 30, 40)).unwrap();
+        assert_eq!(
+            violation.to_string(),
+            "chromosome out of genome order: chr2:11-20 came before chr1:31-40"
+        );
+    }
+}
@@ -0,0 +1,241 @@
+use crate::error::Result;
+use crate::merge::{MergeIterator, Merged};
+use crate::position::{
+    DynPositionedIterator, Field, FieldError, Positioned, PositionedIterator, Value,
+};
+use crate::string::String;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::result;
+
+/// A segment of the genome covered by the same set of sources.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Partition {
+    pub chrom: String,
+    pub start: u64,
+    pub stop: u64,
+    /// the indexes of the iterators with an interval covering the segment, in order.
+    pub sources: Vec<u32>,
+}
+
+impl Positioned for Partition {
+    fn chrom(&self) -> &str {
+        &self.chrom
+    }
+
+    fn start(&self) -> u64 {
+        self.start
+    }
+
+    fn stop(&self) -> u64 {
+        self.stop
+    }
+
+    fn value(&self, f: Field) -> result::Result<Value, FieldError> {
+        match f {
+            Field::Int(0) => Ok(Value::Strings(vec![Some(self.chrom.clone())])),
+            Field::Int(1) => Ok(Value::Ints(vec![Some(self.start as i64)])),
+            Field::Int(2) => Ok(Value::Ints(vec![Some(self.stop as i64)])),
+            Field::Int(3) => Ok(Value::Ints(vec![Some(self.sources.len() as i64)])),
+            Field::Int(4) => Ok(Value::Ints(
+                self.sources.iter().map(|s| Some(*s as i64)).collect(),
+            )),
+            Field::Int(i) => Err(FieldError::InvalidFieldIndex(i)),
+            Field::String(s) => Err(FieldError::InvalidFieldName(s)),
+        }
+    }
+}
+
+/// An iterator that splits the intervals of many sorted iterators into maximal segments
+/// covered by the same set of iterators (like bedtools multiinter). Intervals from all
+/// iterators are combined by a `MergeIterator`; uncovered segments are skipped.
+pub struct PartitionIterator<'a, I = DynPositionedIterator>
+where
+    I: PositionedIterator,
+{
+    merge: MergeIterator<'a, I>,
+    // the next interval from `merge`, if it was read but not yet used.
+    next: Option<Merged<I::Item>>,
+    // the current chromosome.
+    chrom: Option<String>,
+    // segments are known up to here.
+    pos: u64,
+    // ends of the intervals covering `pos` and the iterators they came from.
+    ends: BinaryHeap<Reverse<(u64, u32)>>,
+    // the number of intervals from each iterator that cover `pos`.
+    counts: Vec<u32>,
+    // a partition that may be extended by the next one.
+    pending: Option<Partition>,
+}
+
+impl<'a, I: PositionedIterator> PartitionIterator<'a, I> {
+    pub fn new(iterators: Vec<I>, chromosome_order: &'a HashMap<String, usize>) -> Self {
+        let n = iterators.len();
+        PartitionIterator {
+            merge: MergeIterator::new(iterators, chromosome_order),
+            next: None,
+            chrom: None,
+            pos: 0,
+            ends: BinaryHeap::new(),
+            counts: vec![0; n],
+            pending: None,
+        }
+    }
+
+    /// the next interval from `merge` without consuming it.
+    fn peek(&mut self) -> Result<Option<&Merged<I::Item>>> {
+        if self.next.is_none() {
+            self.next = self.merge.next_position(None).transpose()?;
+        }
+        Ok(self.next.as_ref())
+    }
+
+    /// the next partition, which may have the same sources as the last.
+    fn next_partition(&mut self) -> Result<Option<Partition>> {
+        loop {
+            let chrom = match &self.chrom {
+                Some(c) => c.clone(),
+                None => match self.peek()? {
+                    None => return Ok(None),
+                    Some(top) => {
+                        let (chrom, start) = (String::from(top.chrom()), top.start());
+                        self.pos = start;
+                        self.chrom = Some(chrom);
+                        continue;
+                    }
+                },
+            };
+            let next_start = match self.peek()? {
+                Some(top) if top.chrom() == chrom.as_str() => Some(top.start()),
+                _ => None,
+            };
+            if next_start.is_some_and(|s| s <= self.pos) {
+                let top = self.next.take().expect("the merge has a next start");
+                if top.stop() > top.start() {
+                    self.ends.push(Reverse((top.stop(), top.id)));
+                    self.counts[top.id as usize] += 1;
+                }
+                self.merge.recycle(top);
+                continue;
+            }
+            if let Some(Reverse((end, id))) = self.ends.peek() {
+                if *end <= self.pos {
+                    self.counts[*id as usize] -= 1;
+                    self.ends.pop();
+                    continue;
+                }
+            }
+            let end = self.ends.peek().map(|Reverse((end, _))| *end);
+            let event = match (next_start, end) {
+                (Some(s), Some(e)) => s.min(e),
+                (None, Some(e)) => e,
+                // nothing covers pos so skip to the next interval.
+                (Some(s), None) => {
+                    self.pos = s;
+                    continue;
+                }
+                // the chromosome is finished.
+                (None, None) => {
+                    self.chrom = None;
+                    continue;
+                }
+            };
+            let partition = Partition {
+                chrom,
+                start: self.pos,
+                stop: event,
+                sources: (0..self.counts.len() as u32)
+                    .filter(|i| self.counts[*i as usize] > 0)
+                    .collect(),
+            };
+            self.pos = event;
+            return Ok(Some(partition));
+        }
+    }
+}
+
+impl<'a, I: PositionedIterator> Iterator for PartitionIterator<'a, I> {
+    type Item = Result<Partition>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.next_partition() {
+                Err(e) => return Some(Err(e)),
+                Ok(None) => return self.pending.take().map(Ok),
+                Ok(Some(p)) => match &mut self.pending {
+                    Some(last)
+                        if last.chrom == p.chrom
+                            && last.stop == p.start
+                            && last.sources == p.sources =>
+                    {
+                        last.stop = p.stop
+                    }
+                    _ => {
+                        if let Some(last) = self.pending.replace(p) {
+                            return Some(Ok(last));
+                        }
+                    }
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bedder_bed::BedderBed;
+    use crate::error::Error;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_partition() {
+        let a = "chr1\t10\t50\nchr1\t60\t70\nchr2\t0\t10\n";
+        let b = "chr1\t20\t30\nchr1\t30\t40\nchr1\t65\t80\n";
+        let c = "chr1\t25\t35\nchr2\t5\t10\n";
+        let order = HashMap::from([(String::from("chr1"), 0), (String::from("chr2"), 1)]);
+        let iterators = [a, b, c]
+            .iter()
+            .map(|s| BedderBed::new(Cursor::new(s.to_string()), "t.bed"))
+            .collect();
+        let parts: Vec<(std::string::String, u64, u64, Vec<u32>)> =
+            PartitionIterator::new(iterators, &order)
+                .map(|p| {
+                    let p = p.unwrap();
+                    (p.chrom.to_string(), p.start, p.stop, p.sources)
+                })
+                .collect();
+        let part = |chrom: &str, start, stop, sources: &[u32]| {
+            (chrom.to_string(), start, stop, sources.to_vec())
+        };
+        assert_eq!(
+            parts,
+            vec![
+                part("chr1", 10, 20, &[0]),
+                part("chr1", 20, 25, &[0, 1]),
+                // the two intervals of b are joined as the sources do not change at 30.
+                part("chr1", 25, 35, &[0, 1, 2]),
+                part("chr1", 35, 40, &[0, 1]),
+                part("chr1", 40, 50, &[0]),
+                part("chr1", 60, 65, &[0]),
+                part("chr1", 65, 70, &[0, 1]),
+                part("chr1", 70, 80, &[1]),
+                part("chr2", 0, 5, &[0]),
+                part("chr2", 5, 10, &[0, 2]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_partition_out_of_order() {
+        let order = HashMap::from([(String::from("chr1"), 0)]);
+        let iterators = vec![BedderBed::new(
+            Cursor::new("chr1\t20\t30\nchr1\t10\t15\n"),
+            "t.bed",
+        )];
+        let e = PartitionIterator::new(iterators, &order)
+            .find_map(|p| p.err())
+            .unwrap();
+        assert!(matches!(e, Error::OutOfOrder { line: 2, .. }));
+    }
+}
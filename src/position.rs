@@ -1,30 +1,75 @@
 use crate::string::String;
+use std::any::Any;
 use std::fmt::{self, Debug};
-use std::io;
 use std::result;
 
-/// A Value is a vector of integers, floats, or strings.
+/// A Value is a vector of integers, floats, or strings, or a flag.
 /// Often this will be a single value.
-#[derive(Debug)]
+/// Elements are `None` when missing (e.g. `.` in a VCF array) so that
+/// multi-valued fields (e.g. per-allele values) keep their alignment.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Value {
-    Ints(Vec<i64>),
-    Floats(Vec<f64>),
-    Strings(Vec<String>),
+    Ints(Vec<Option<i64>>),
+    Floats(Vec<Option<f64>>),
+    Strings(Vec<Option<String>>),
+    /// A boolean, e.g. a VCF INFO Flag.
+    Flag(bool),
+    /// The field is present but has no value.
+    Missing,
+}
+
+/// Values are written as comma-separated lists with missing elements as `.`.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn join<T: fmt::Display>(f: &mut fmt::Formatter, vals: &[Option<T>]) -> fmt::Result {
+            for (i, v) in vals.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ",")?;
+                }
+                match v {
+                    Some(v) => write!(f, "{}", v)?,
+                    None => write!(f, ".")?,
+                }
+            }
+            Ok(())
+        }
+        match self {
+            Value::Ints(v) => join(f, v),
+            Value::Floats(v) => join(f, v),
+            Value::Strings(v) => join(f, v),
+            Value::Flag(b) => write!(f, "{}", b),
+            Value::Missing => write!(f, "."),
+        }
+    }
 }
 
 /// Field is either an integer, as in a bed column
 /// or a string, as in a vcf info field.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Field {
     String(String),
     Int(usize),
 }
 
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Field::String(s) => write!(f, "{}", s),
+            Field::Int(i) => write!(f, "{}", i),
+        }
+    }
+}
+
 /// Error returned when a field is not found.
 #[derive(Debug)]
 pub enum FieldError {
     InvalidFieldIndex(usize),
     InvalidFieldName(String),
+    /// A value could not be used as the type required by an operation.
+    TypeMismatch {
+        field: String,
+        value: String,
+    },
 }
 
 impl fmt::Display for FieldError {
@@ -32,6 +77,9 @@ impl fmt::Display for FieldError {
         match self {
             FieldError::InvalidFieldIndex(i) => write!(f, "invalid column index: {}", i),
             FieldError::InvalidFieldName(s) => write!(f, "invalid column name: {}", s),
+            FieldError::TypeMismatch { field, value } => {
+                write!(f, "expected numeric value for {}, got: {}", field, value)
+            }
         }
     }
 }
@@ -50,12 +98,27 @@ pub trait Positioned: Debug {
     // extract a value from the Positioned object Col
     fn value(&self, b: Field) -> result::Result<Value, FieldError>;
 
-    // get back the original line?
-    //fn line(&self) -> &'a str;
+    /// The original text of the record (without the newline) for text-based formats.
+    /// This allows lossless output of columns that are not parsed.
+    fn line(&self) -> Option<&str> {
+        None
+    }
+
+    /// Convert a boxed Positioned to `Any` so that a reader can take back its own records
+    /// (see `PositionedIterator::recycle`). Returns None if this is not supported.
+    fn into_any(self: Box<Self>) -> Option<Box<dyn Any>> {
+        None
+    }
+
+    /// Borrow this as `Any` so that format-specific code can reach the concrete record
+    /// (e.g. the header of a VCF record). Returns None if this is not supported.
+    fn as_any(&self) -> Option<&dyn Any> {
+        None
+    }
 }
 
-// Delegate the boxed version of this trait object to the inner object.
-impl Positioned for Box<dyn Positioned> {
+// Delegate boxed Positioneds (including trait objects) to the inner object.
+impl<T: Positioned + ?Sized> Positioned for Box<T> {
     fn chrom(&self) -> &str {
         self.as_ref().chrom()
     }
@@ -71,6 +134,18 @@ impl Positioned for Box<dyn Positioned> {
     fn value(&self, b: Field) -> result::Result<Value, FieldError> {
         self.as_ref().value(b)
     }
+
+    fn line(&self) -> Option<&str> {
+        self.as_ref().line()
+    }
+
+    fn into_any(self: Box<Self>) -> Option<Box<dyn Any>> {
+        (*self).into_any()
+    }
+
+    fn as_any(&self) -> Option<&dyn Any> {
+        self.as_ref().as_any()
+    }
 }
 
 impl PartialEq for dyn Positioned {
@@ -101,5 +176,99 @@ pub trait PositionedIterator {
     fn next_position(
         &mut self,
         q: Option<&dyn Positioned>,
-    ) -> Option<std::result::Result<Self::Item, io::Error>>;
+    ) -> Option<crate::error::Result<Self::Item>>;
+
+    /// Give back a Positioned from this iterator that is no longer used.
+    /// Implementers may keep it and reuse its allocations for later records.
+    fn recycle(&mut self, _p: Self::Item) {}
+}
+
+// Delegate boxed iterators (including trait objects) to the inner iterator.
+impl<I: PositionedIterator + ?Sized> PositionedIterator for Box<I> {
+    type Item = I::Item;
+
+    fn name(&self) -> String {
+        self.as_ref().name()
+    }
+
+    fn next_position(
+        &mut self,
+        q: Option<&dyn Positioned>,
+    ) -> Option<crate::error::Result<Self::Item>> {
+        self.as_mut().next_position(q)
+    }
+
+    fn recycle(&mut self, p: Self::Item) {
+        self.as_mut().recycle(p)
+    }
+}
+
+/// A PositionedIterator over any type of file.
+pub type DynPositionedIterator = Box<dyn PositionedIterator<Item = Box<dyn Positioned>>>;
+
+/// at most this many boxes are kept for reuse by `BoxPositioned`.
+const MAX_FREE_BOXES: usize = 1024;
+
+/// BoxPositioned adapts an iterator over a concrete Positioned type to one over `Box<dyn Positioned>`
+/// so that it can be intersected with iterators over other types.
+/// Recycled boxes are kept and their contents are given back to the wrapped iterator.
+pub struct BoxPositioned<I>
+where
+    I: PositionedIterator,
+{
+    inner: I,
+    // the boxes themselves are what we reuse here.
+    #[allow(clippy::vec_box)]
+    free: Vec<Box<I::Item>>,
+}
+
+impl<I> BoxPositioned<I>
+where
+    I: PositionedIterator,
+{
+    pub fn new(inner: I) -> Self {
+        BoxPositioned {
+            inner,
+            free: Vec::new(),
+        }
+    }
+}
+
+impl<I> PositionedIterator for BoxPositioned<I>
+where
+    I: PositionedIterator,
+    I::Item: Default + 'static,
+{
+    type Item = Box<dyn Positioned>;
+
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+
+    fn next_position(
+        &mut self,
+        q: Option<&dyn Positioned>,
+    ) -> Option<crate::error::Result<Self::Item>> {
+        let p = match self.inner.next_position(q)? {
+            Ok(p) => p,
+            Err(e) => return Some(Err(e)),
+        };
+        let b: Box<dyn Positioned> = match self.free.pop() {
+            Some(mut b) => {
+                *b = p;
+                b
+            }
+            None => Box::new(p),
+        };
+        Some(Ok(b))
+    }
+
+    fn recycle(&mut self, p: Self::Item) {
+        if let Some(Ok(mut b)) = p.into_any().map(|a| a.downcast::<I::Item>()) {
+            self.inner.recycle(std::mem::take(&mut *b));
+            if self.free.len() < MAX_FREE_BOXES {
+                self.free.push(b);
+            }
+        }
+    }
 }
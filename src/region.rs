@@ -0,0 +1,419 @@
+use crate::error::{Error, Result};
+use crate::position::{Field, FieldError, Positioned, PositionedIterator, Value};
+use crate::string::{to_string_type, String};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::BufRead;
+use std::rc::Rc;
+use std::result;
+use std::str::FromStr;
+
+/// A genomic region with 0-based, half-open coordinates.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Region {
+    pub chrom: String,
+    pub start: u64,
+    pub stop: u64,
+}
+
+impl Positioned for Region {
+    fn chrom(&self) -> &str {
+        &self.chrom
+    }
+
+    fn start(&self) -> u64 {
+        self.start
+    }
+
+    fn stop(&self) -> u64 {
+        self.stop
+    }
+
+    fn value(&self, f: Field) -> result::Result<Value, FieldError> {
+        match f {
+            Field::Int(0) => Ok(Value::Strings(vec![Some(self.chrom.clone())])),
+            Field::Int(1) => Ok(Value::Ints(vec![Some(self.start as i64)])),
+            Field::Int(2) => Ok(Value::Ints(vec![Some(self.stop as i64)])),
+            Field::Int(i) => Err(FieldError::InvalidFieldIndex(i)),
+            Field::String(s) => Err(FieldError::InvalidFieldName(s)),
+        }
+    }
+}
+
+/// Parse regions as `chr1`, `chr1:1000` (to the end of the chromosome) or `chr1:1,000-2,000`.
+/// Positions are 1-based and inclusive as in samtools and tabix; commas are ignored.
+/// Chromosome names containing `:` (e.g. `HLA-A*01:01:01G`) are kept whole when the part after
+/// the last `:` is not a range.
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(String::from("empty region"));
+        }
+        let whole = || Region {
+            chrom: String::from(s),
+            start: 0,
+            stop: u64::MAX,
+        };
+        let (chrom, range) = match s.rsplit_once(':') {
+            None => return Ok(whole()),
+            Some((chrom, range)) => (chrom, range.replace(',', "")),
+        };
+        let (start, stop) = match range.split_once('-') {
+            None => (range.as_str(), ""),
+            Some((start, stop)) => (start, stop),
+        };
+        if !start.bytes().all(|b| b.is_ascii_digit()) || !stop.bytes().all(|b| b.is_ascii_digit()) {
+            return Ok(whole());
+        }
+        let invalid = || to_string_type(format!("invalid region: {}", s));
+        let start = match start {
+            "" => 1,
+            start => start.parse::<u64>().map_err(|_| invalid())?,
+        };
+        let stop = match stop {
+            "" => u64::MAX,
+            stop => stop.parse::<u64>().map_err(|_| invalid())?,
+        };
+        if start == 0 || stop < start || chrom.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Region {
+            chrom: String::from(chrom),
+            start: start - 1,
+            stop,
+        })
+    }
+}
+
+/// Regions are written 1-based, inclusive so that they parse back to the same region.
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.start, self.stop) {
+            (0, u64::MAX) => write!(f, "{}", self.chrom),
+            (start, u64::MAX) => write!(f, "{}:{}", self.chrom, start + 1),
+            (start, stop) => write!(f, "{}:{}-{}", self.chrom, start + 1, stop),
+        }
+    }
+}
+
+/// read regions from a BED file. comment, `track` and `browser` lines are skipped.
+/// `name` is used in error messages.
+pub fn read_bed<R: BufRead>(reader: R, name: &str) -> Result<Vec<Region>> {
+    let mut regions = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty()
+            || line.starts_with('#')
+            || line.starts_with("track")
+            || line.starts_with("browser")
+        {
+            continue;
+        }
+        let parse_error = |message: std::string::String| Error::Parse {
+            name: String::from(name),
+            line: i as u64 + 1,
+            region: None,
+            message: to_string_type(message),
+        };
+        let mut fields = line.split('\t');
+        let chrom = fields.next().unwrap_or_default();
+        let mut position = |column: &str| {
+            fields
+                .next()
+                .ok_or_else(|| parse_error(format!("missing {}", column)))?
+                .trim()
+                .parse::<u64>()
+                .map_err(|e| parse_error(format!("invalid {}: {}", column, e)))
+        };
+        let start = position("start")?;
+        let stop = position("end")?;
+        regions.push(Region {
+            chrom: String::from(chrom),
+            start,
+            stop,
+        });
+    }
+    Ok(regions)
+}
+
+/// A set of regions sorted by the chromosome order of a genome file with overlapping and
+/// adjacent regions merged.
+#[derive(Debug)]
+pub struct Regions {
+    // regions and their chromosome index.
+    regions: Vec<(Region, usize)>,
+    chrom_order: HashMap<String, usize>,
+}
+
+impl Regions {
+    pub fn new(regions: Vec<Region>, chrom_order: &HashMap<String, usize>) -> Result<Self> {
+        let mut sorted = Vec::with_capacity(regions.len());
+        for r in regions {
+            let id = *chrom_order
+                .get(&r.chrom)
+                .ok_or_else(|| Error::UnknownChromosome {
+                    name: String::from("regions"),
+                    chrom: r.chrom.clone(),
+                })?;
+            sorted.push((r, id));
+        }
+        sorted.sort_by_key(|(r, id)| (*id, r.start, r.stop));
+        let mut merged: Vec<(Region, usize)> = Vec::with_capacity(sorted.len());
+        for (r, id) in sorted {
+            match merged.last_mut() {
+                Some((last, last_id)) if *last_id == id && r.start <= last.stop => {
+                    last.stop = last.stop.max(r.stop);
+                }
+                _ => merged.push((r, id)),
+            }
+        }
+        Ok(Regions {
+            regions: merged,
+            chrom_order: chrom_order.clone(),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Region> {
+        self.regions.iter().map(|(r, _)| r)
+    }
+}
+
+/// RegionFilter yields only the intervals of the wrapped iterator that overlap a region.
+/// The current region is passed to the wrapped iterator as the query when the filter moves
+/// to it, so iterators that can seek (e.g. indexed BGZF files) skip the data between regions;
+/// others are read and filtered. Intervals with no length are treated as covering one base.
+pub struct RegionFilter<I: PositionedIterator> {
+    inner: I,
+    // shared by the filters of all inputs.
+    regions: Rc<Regions>,
+    // index of the current region; earlier regions are finished.
+    i: usize,
+    // the last chromosome seen and its index.
+    chrom: Option<(String, usize)>,
+    // index of the region last passed to the wrapped iterator as a query.
+    queried: Option<usize>,
+}
+
+impl<I: PositionedIterator> RegionFilter<I> {
+    pub fn new(inner: I, regions: Rc<Regions>) -> Self {
+        RegionFilter {
+            inner,
+            regions,
+            i: 0,
+            chrom: None,
+            queried: None,
+        }
+    }
+
+    fn chrom_id(&mut self, chrom: &str) -> Result<usize> {
+        match &self.chrom {
+            Some((name, id)) if name == chrom => Ok(*id),
+            _ => {
+                let id = *self.regions.chrom_order.get(chrom).ok_or_else(|| {
+                    Error::UnknownChromosome {
+                        name: self.inner.name(),
+                        chrom: String::from(chrom),
+                    }
+                })?;
+                self.chrom = Some((String::from(chrom), id));
+                Ok(id)
+            }
+        }
+    }
+}
+
+impl<I: PositionedIterator> PositionedIterator for RegionFilter<I> {
+    type Item = I::Item;
+
+    fn next_position(&mut self, q: Option<&dyn Positioned>) -> Option<Result<Self::Item>> {
+        let regions = Rc::clone(&self.regions);
+        let mut q = q;
+        loop {
+            let (region, region_id) = regions.regions.get(self.i)?;
+            // like the query, a hint is given only once: for a new query (seeking to the
+            // later of the query and the current region) or when a new region is reached.
+            let hint: Option<&dyn Positioned> = match q.take() {
+                Some(q) => match regions.chrom_order.get(q.chrom()) {
+                    Some(id) if (*id, q.start()) > (*region_id, region.start) => Some(q),
+                    _ => Some(region),
+                },
+                None if self.queried != Some(self.i) => Some(region),
+                None => None,
+            };
+            self.queried = Some(self.i);
+            let p = match self.inner.next_position(hint)? {
+                Err(e) => return Some(Err(e)),
+                Ok(p) => p,
+            };
+            let id = match self.chrom_id(p.chrom()) {
+                Err(e) => return Some(Err(e)),
+                Ok(id) => id,
+            };
+            let stop = p.stop().max(p.start() + 1);
+            // later intervals start at or after this one so can not overlap earlier regions.
+            while let Some((r, rid)) = regions.regions.get(self.i) {
+                if *rid < id || (*rid == id && r.stop <= p.start()) {
+                    self.i += 1;
+                } else {
+                    break;
+                }
+            }
+            match regions.regions.get(self.i) {
+                Some((r, rid)) if *rid == id && r.start < stop => return Some(Ok(p)),
+                Some(_) => self.inner.recycle(p),
+                None => {
+                    self.inner.recycle(p);
+                    return None;
+                }
+            }
+        }
+    }
+
+    fn recycle(&mut self, p: Self::Item) {
+        self.inner.recycle(p)
+    }
+
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bedder_bed::BedderBed;
+    use crate::intersection::IntersectionIterator;
+    use std::io::Cursor;
+
+    fn region(chrom: &str, start: u64, stop: u64) -> Region {
+        Region {
+            chrom: String::from(chrom),
+            start,
+            stop,
+        }
+    }
+
+    #[test]
+    fn test_parse_region() {
+        assert_eq!("chr1".parse(), Ok(region("chr1", 0, u64::MAX)));
+        assert_eq!("chr1:1,000-2,000".parse(), Ok(region("chr1", 999, 2000)));
+        assert_eq!("chr1:1000".parse(), Ok(region("chr1", 999, u64::MAX)));
+        assert_eq!("chr1:1000-".parse(), Ok(region("chr1", 999, u64::MAX)));
+        assert_eq!("chr1:-2000".parse(), Ok(region("chr1", 0, 2000)));
+        assert_eq!(
+            "HLA-A*01:01:01G".parse(),
+            Ok(region("HLA-A*01:01:01G", 0, u64::MAX))
+        );
+        assert!("chr1:0-10".parse::<Region>().is_err());
+        assert!("chr1:20-10".parse::<Region>().is_err());
+        assert!(":1-10".parse::<Region>().is_err());
+        assert!("".parse::<Region>().is_err());
+        for s in ["chr1", "chr1:5", "chr1:5-10"] {
+            assert_eq!(s.parse::<Region>().unwrap().to_string(), s);
+        }
+    }
+
+    #[test]
+    fn test_regions_merge() {
+        let order = HashMap::from([(String::from("chr1"), 0), (String::from("chr2"), 1)]);
+        let bed = "track name=x\nchr2\t5\t10\nchr1\t20\t30\nchr1\t10\t20\nchr1\t25\t26\n";
+        let regions = read_bed(Cursor::new(bed), "r.bed").unwrap();
+        let regions = Regions::new(regions, &order).unwrap();
+        let got: Vec<_> = regions.iter().cloned().collect();
+        assert_eq!(got, vec![region("chr1", 10, 30), region("chr2", 5, 10)]);
+
+        let r = Regions::new(vec![region("chr3", 0, 1)], &order);
+        assert!(matches!(r, Err(Error::UnknownChromosome { .. })));
+        let r = read_bed(Cursor::new("chr1\t10\n"), "r.bed");
+        assert!(matches!(r, Err(Error::Parse { line: 1, .. })));
+    }
+
+    /// counts the calls with a query.
+    struct CountQueries<I> {
+        inner: I,
+        queries: usize,
+    }
+
+    impl<I: PositionedIterator> PositionedIterator for CountQueries<I> {
+        type Item = I::Item;
+
+        fn next_position(&mut self, q: Option<&dyn Positioned>) -> Option<Result<Self::Item>> {
+            if q.is_some() {
+                self.queries += 1;
+            }
+            self.inner.next_position(q)
+        }
+
+        fn name(&self) -> String {
+            self.inner.name()
+        }
+    }
+
+    #[test]
+    fn test_region_filter_queries_once_per_region() {
+        let order = HashMap::from([(String::from("chr1"), 0), (String::from("chr2"), 1)]);
+        let regions = Regions::new(
+            vec![region("chr1", 100, 200), region("chr2", 0, 10)],
+            &order,
+        )
+        .unwrap();
+        let bed = "chr1\t100\t110\nchr1\t120\t130\nchr1\t140\t150\nchr2\t1\t2\nchr2\t3\t4\n";
+        let inner = CountQueries {
+            inner: BedderBed::new(Cursor::new(bed), "a.bed"),
+            queries: 0,
+        };
+        let mut f = RegionFilter::new(inner, Rc::new(regions));
+        let mut n = 0;
+        while let Some(p) = f.next_position(None) {
+            p.unwrap();
+            n += 1;
+        }
+        assert_eq!(n, 5);
+        assert_eq!(f.inner.queries, 2);
+    }
+
+    #[test]
+    fn test_region_filter() {
+        let order = HashMap::from([(String::from("chr1"), 0), (String::from("chr2"), 1)]);
+        let regions = Regions::new(
+            vec![region("chr1", 100, 200), region("chr2", 0, 10)],
+            &order,
+        )
+        .unwrap();
+        let regions = Rc::new(regions);
+        let a = "chr1\t0\t50\nchr1\t50\t150\nchr1\t150\t160\nchr1\t200\t300\nchr2\t5\t5\nchr2\t20\t30\n";
+        let b = "chr1\t0\t1000\nchr2\t0\t1000\n";
+        let a = RegionFilter::new(BedderBed::new(Cursor::new(a), "a.bed"), regions.clone());
+        let b = RegionFilter::new(BedderBed::new(Cursor::new(b), "b.bed"), regions);
+        let it = IntersectionIterator::new(a, vec![b], &order).unwrap();
+        let got: Vec<_> = it
+            .map(|i| {
+                let i = i.unwrap();
+                (
+                    i.base_interval.chrom().to_string(),
+                    i.base_interval.start(),
+                    i.overlapping.len(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            got,
+            vec![
+                ("chr1".to_string(), 50, 1),
+                ("chr1".to_string(), 150, 1),
+                ("chr2".to_string(), 5, 1),
+            ]
+        );
+    }
+}
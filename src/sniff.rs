@@ -2,11 +2,12 @@ use flate2::read::GzDecoder;
 use std::io::{BufRead, Read};
 use std::path::Path;
 
+use crate::bedder_alignment::BedderAlignments;
 use crate::bedder_bed::BedderBed;
-use crate::bedder_vcf::BedderVCF;
-use crate::position::{Positioned, PositionedIterator};
-use noodles::bgzf;
-use noodles::vcf;
+use crate::bedder_vcf::{BedderVCF, LineVCFReader};
+use crate::error::{Error, Result};
+use crate::position::{BoxPositioned, DynPositionedIterator};
+use noodles::{bam, bgzf, sam};
 
 /// File formats supported by this file detector.
 #[derive(Debug, PartialEq)]
@@ -30,63 +31,192 @@ pub enum Compression {
     RAZF,
 }
 
-pub fn open_file<P>(
-    path: P,
-) -> std::io::Result<Box<dyn PositionedIterator<Item = Box<dyn Positioned>>>>
+pub fn open_file<P>(path: P) -> Result<DynPositionedIterator>
 where
     P: AsRef<Path>,
 {
-    let file = std::fs::File::open(&path)?;
-    let r = open_reader(file, path);
-    r
+    open_file_with_max_len(path, None)
 }
 
-pub fn open_reader<R, P>(
-    reader: R,
-    path: P,
-) -> std::io::Result<Box<dyn PositionedIterator<Item = Box<dyn Positioned>>>>
+/// Like `open_file`, but an uncompressed BED file skips ahead to far away queries by
+/// bisection if `max_len`, the length of its longest interval, is given. Indexed, bgzipped
+/// BED files seek in either case.
+pub fn open_file_with_max_len<P>(path: P, max_len: Option<u64>) -> Result<DynPositionedIterator>
+where
+    P: AsRef<Path>,
+{
+    let file = std::fs::File::open(&path).map_err(|e| {
+        std::io::Error::new(e.kind(), format!("{}: {}", path.as_ref().display(), e))
+    })?;
+    // pipes (e.g. /dev/stdin) can not seek.
+    let regular_file = file.metadata().map(|m| m.is_file()).unwrap_or(false);
+    let mut reader = std::io::BufReader::new(file);
+    let (format, compression) = detect(&mut reader, &path)?;
+    if let Some(max_len) = max_len.filter(|_| regular_file) {
+        if format == FileFormat::BED && compression == Compression::None {
+            // uncompressed BED files can skip ahead by seeking.
+            let bed = BedderBed::seekable(reader, &path, max_len)?;
+            return Ok(Box::new(BoxPositioned::new(bed)));
+        }
+    }
+    #[cfg(all(feature = "bgzf", feature = "csi", feature = "tabix"))]
+    if regular_file && format == FileFormat::BED && compression == Compression::BGZF {
+        // bgzipped BED files with a tabix or CSI index seek to queries that are far ahead.
+        if let Some(index) = read_index(&path)? {
+            let bed = BedderBed::indexed(bgzf::Reader::new(reader), index, &path);
+            return Ok(Box::new(BoxPositioned::new(bed)));
+        }
+    }
+    open_detected(reader, path, format, compression)
+}
+
+/// read the tabix (`.tbi`) or CSI (`.csi`) index next to `path`, if there is one.
+#[cfg(all(feature = "bgzf", feature = "csi", feature = "tabix"))]
+fn read_index<P: AsRef<Path>>(path: P) -> std::io::Result<Option<noodles::csi::Index>> {
+    use crate::index::{IndexFormat, DEFAULT_MIN_SHIFT};
+    let formats = [
+        IndexFormat::Tabix,
+        IndexFormat::Csi {
+            min_shift: DEFAULT_MIN_SHIFT,
+        },
+    ];
+    for format in formats {
+        let index_path = format.index_path(&path);
+        if index_path.is_file() {
+            log::info!("using index {:?}", index_path);
+            return format.read(index_path).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+pub fn open_reader<R, P>(reader: R, path: P) -> Result<DynPositionedIterator>
 where
     R: Read + 'static,
     P: AsRef<Path>,
 {
     let mut reader = std::io::BufReader::new(reader);
-    let (format, compression) = detect_file_format(&mut reader, &path)?;
+    let (format, compression) = detect(&mut reader, &path)?;
+    open_detected(reader, path, format, compression)
+}
+
+fn detect<R: BufRead, P: AsRef<Path>>(
+    reader: &mut R,
+    path: P,
+) -> std::io::Result<(FileFormat, Compression)> {
+    let (format, compression) = detect_file_format(reader, &path)?;
     log::info!(
         "path: {:?}, format: {:?} compression: {:?}",
         path.as_ref(),
         format,
         compression
     );
-    let br: Box<dyn BufRead> = match compression {
-        Compression::None => Box::new(reader),
-        Compression::GZ => Box::new(std::io::BufReader::new(GzDecoder::new(reader))),
-        Compression::BGZF => match format {
-            // BCF|BAM will appear as bgzf so we don't want to do this outside
-            FileFormat::BCF | FileFormat::BAM => Box::new(reader),
-            _ => Box::new(bgzf::Reader::new(reader)),
-        },
-        Compression::RAZF => unimplemented!(),
-    };
+    Ok((format, compression))
+}
+
+fn open_detected<R, P>(
+    reader: std::io::BufReader<R>,
+    path: P,
+    format: FileFormat,
+    compression: Compression,
+) -> Result<DynPositionedIterator>
+where
+    R: Read + 'static,
+    P: AsRef<Path>,
+{
+    match format {
+        FileFormat::VCF | FileFormat::BCF => {
+            let vcf = open_detected_vcf(reader, path, format, compression)?;
+            Ok(Box::new(BoxPositioned::new(vcf)))
+        }
+        FileFormat::BED => {
+            let br = decompress(reader, &path, &format, compression)?;
+            let reader = BedderBed::new(br, &path);
+            Ok(Box::new(BoxPositioned::new(reader)))
+        }
+        FileFormat::BAM => {
+            let mut bam = bam::Reader::new(decompress(reader, &path, &format, compression)?);
+            let header = bam.read_header()?;
+            let reader = BedderAlignments::new(Box::new(bam), header, &path);
+            Ok(Box::new(BoxPositioned::new(reader)))
+        }
+        FileFormat::SAM => {
+            let mut sam = sam::Reader::new(decompress(reader, &path, &format, compression)?);
+            let header = sam.read_header()?;
+            let reader = BedderAlignments::new(Box::new(sam), header, &path);
+            Ok(Box::new(BoxPositioned::new(reader)))
+        }
+        _ => Err(Error::UnsupportedFormat {
+            name: crate::string::String::from(&*path.as_ref().to_string_lossy()),
+            format: crate::string::to_string_type(format!("{:?}", format)),
+        }),
+    }
+}
+
+/// Open a VCF or BCF file as a `BedderVCF`, which gives access to its header.
+pub fn open_vcf<P: AsRef<Path>>(path: P) -> Result<BedderVCF<'static>> {
+    let file = std::fs::File::open(&path).map_err(|e| {
+        std::io::Error::new(e.kind(), format!("{}: {}", path.as_ref().display(), e))
+    })?;
+    let mut reader = std::io::BufReader::new(file);
+    let (format, compression) = detect(&mut reader, &path)?;
+    open_detected_vcf(reader, path, format, compression)
+}
+
+fn open_detected_vcf<R, P>(
+    reader: std::io::BufReader<R>,
+    path: P,
+    format: FileFormat,
+    compression: Compression,
+) -> Result<BedderVCF<'static>>
+where
+    R: Read + 'static,
+    P: AsRef<Path>,
+{
+    let br = decompress(reader, &path, &format, compression)?;
     match format {
         FileFormat::VCF => {
-            let mut vcf = vcf::reader::Builder.build_from_reader(br)?;
+            let mut vcf = LineVCFReader::new(br);
             let hdr = vcf.read_header()?;
-            let bed_vcf = BedderVCF::new(Box::new(vcf), hdr)?;
-            Ok(Box::new(bed_vcf))
+            BedderVCF::new(Box::new(vcf), hdr, &path)
         }
         FileFormat::BCF => {
             let mut bcf = noodles::bcf::Reader::new(br);
             let hdr = bcf.read_header()?;
-            let bed_vcf = BedderVCF::new(Box::new(bcf), hdr)?;
-            Ok(Box::new(bed_vcf))
+            BedderVCF::new(Box::new(bcf), hdr, &path)
         }
+        _ => Err(Error::UnsupportedFormat {
+            name: crate::string::String::from(&*path.as_ref().to_string_lossy()),
+            format: crate::string::to_string_type(format!("{:?} (expected VCF or BCF)", format)),
+        }),
+    }
+}
 
-        FileFormat::BED => {
-            let reader = BedderBed::new(br);
-            Ok(Box::new(reader))
+fn decompress<R, P>(
+    reader: std::io::BufReader<R>,
+    path: P,
+    format: &FileFormat,
+    compression: Compression,
+) -> Result<Box<dyn BufRead>>
+where
+    R: Read + 'static,
+    P: AsRef<Path>,
+{
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::GZ => Box::new(std::io::BufReader::new(GzDecoder::new(reader))),
+        Compression::BGZF => match format {
+            // BCF|BAM will appear as bgzf so we don't want to do this outside
+            FileFormat::BCF | FileFormat::BAM => Box::new(reader),
+            _ => Box::new(bgzf::Reader::new(reader)),
+        },
+        Compression::RAZF => {
+            return Err(Error::UnsupportedFormat {
+                name: crate::string::String::from(&*path.as_ref().to_string_lossy()),
+                format: crate::string::String::from("RAZF compression"),
+            })
         }
-        _ => unimplemented!("{format:?} not yet supported"),
-    }
+    })
 }
 
 /// detect the file format of a reader.
@@ -146,7 +276,7 @@ pub fn detect_file_format<R: BufRead, P: AsRef<Path>>(
     {
         FileFormat::SAM
     } else {
-        let p = path.as_ref();
+        let p = path.as_ref().to_string_lossy();
         if p.ends_with(".bed") || p.ends_with(".bed.gz") || p.ends_with(".bed.bgz") {
             FileFormat::BED
         } else {
@@ -193,8 +323,6 @@ fn is_bed_line(s: &str) -> bool {
 mod tests {
 
     use super::*;
-    use noodles::bam;
-    use noodles::sam;
 
     #[test]
     fn test_detect_format_bam() {
@@ -230,6 +358,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_open_alignments() {
+        for path in ["tests/test.bam", "tests/test.sam"] {
+            let mut it = open_file(path).unwrap();
+            let mut starts = Vec::new();
+            while let Some(p) = it.next_position(None) {
+                let p = p.unwrap();
+                assert_eq!(p.chrom(), "chr1");
+                assert_eq!(p.stop() - p.start(), 100);
+                starts.push(p.start());
+            }
+            assert_eq!(starts, vec![999900, 999913], "{}", path);
+        }
+    }
+
     #[test]
     fn test_is_bed_line() {
         // Test valid BED line
@@ -252,4 +395,16 @@ mod tests {
         let valid_bed_line = "chr1\t100\t200";
         assert!(is_bed_line(valid_bed_line));
     }
+
+    #[test]
+    fn test_detect_format_bed_suffix() {
+        // a header line alone does not look like BED, so only the suffix can tell.
+        let mut rdr = std::io::Cursor::new(b"track name=a\n".to_vec());
+        let (format, _) = detect_file_format(&mut rdr, "dir/a.bed").unwrap();
+        assert_eq!(format, FileFormat::BED);
+
+        let mut rdr = std::io::Cursor::new(b"track name=a\n".to_vec());
+        let (format, _) = detect_file_format(&mut rdr, "dir/a.txt").unwrap();
+        assert_eq!(format, FileFormat::Unknown);
+    }
 }
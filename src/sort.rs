@@ -0,0 +1,470 @@
+use crate::error::{Error, Result};
+use crate::intersection::{LastChrom, ReverseOrderPosition};
+use crate::merge::{MergeIterator, Merged};
+use crate::position::{Field, FieldError, Positioned, PositionedIterator, Value};
+use crate::string::{to_string_type, String};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::result;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// by default, records are spilled to disk when those in memory take more than this many bytes.
+pub const DEFAULT_MAX_MEMORY: usize = 256 << 20;
+
+/// by default, at most this many sorted runs (and open files) are merged at once.
+pub const DEFAULT_MAX_FAN_IN: usize = 128;
+
+/// A whole record and its position, as returned by `Sorter::sort`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SortedRecord {
+    pub chrom: String,
+    pub start: u64,
+    pub stop: u64,
+    /// the original line of the record.
+    pub line: std::string::String,
+}
+
+impl SortedRecord {
+    fn from_positioned<P: Positioned>(p: &P) -> Self {
+        let line = match p.line() {
+            Some(line) => line.to_string(),
+            None => format!("{}\t{}\t{}", p.chrom(), p.start(), p.stop()),
+        };
+        SortedRecord {
+            chrom: String::from(p.chrom()),
+            start: p.start(),
+            stop: p.stop(),
+            line,
+        }
+    }
+
+    /// the approximate number of bytes used by the record.
+    fn size(&self) -> usize {
+        std::mem::size_of::<(usize, SortedRecord)>() + self.chrom.len() + self.line.len()
+    }
+}
+
+impl Positioned for SortedRecord {
+    fn chrom(&self) -> &str {
+        &self.chrom
+    }
+
+    fn start(&self) -> u64 {
+        self.start
+    }
+
+    fn stop(&self) -> u64 {
+        self.stop
+    }
+
+    fn value(&self, f: Field) -> result::Result<Value, FieldError> {
+        match f {
+            Field::Int(0) => Ok(Value::Strings(vec![Some(self.chrom.clone())])),
+            Field::Int(1) => Ok(Value::Ints(vec![Some(self.start as i64)])),
+            Field::Int(2) => Ok(Value::Ints(vec![Some(self.stop as i64)])),
+            Field::Int(i) => Err(FieldError::InvalidFieldIndex(i)),
+            Field::String(s) => Err(FieldError::InvalidFieldName(s)),
+        }
+    }
+
+    fn line(&self) -> Option<&str> {
+        Some(&self.line)
+    }
+}
+
+// numbers the temporary files of all sorts in this process.
+static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+/// A temporary file that is removed when dropped.
+struct TempPath(PathBuf);
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.0) {
+            log::warn!("could not remove {:?}: {}", self.0, e);
+        }
+    }
+}
+
+/// A sorted run of records, either still in memory or spilled to a temporary file
+/// with the chromosome, start and stop before each line.
+struct Run {
+    source: RunSource,
+    name: String,
+    line_number: u64,
+}
+
+enum RunSource {
+    Memory(std::vec::IntoIter<(usize, SortedRecord)>),
+    File {
+        // opened on the first read so that runs waiting to be merged do not hold a file.
+        reader: Option<BufReader<File>>,
+        buf: std::string::String,
+        // the file is removed when the run is dropped.
+        path: TempPath,
+    },
+}
+
+impl Run {
+    /// sort `records` by position, keeping the input order of records at the same position.
+    fn sort(mut records: Vec<(usize, SortedRecord)>) -> Vec<(usize, SortedRecord)> {
+        records.sort_by_key(|(chrom_id, r)| (*chrom_id, r.start, r.stop));
+        records
+    }
+
+    fn memory(records: Vec<(usize, SortedRecord)>) -> Self {
+        Run {
+            source: RunSource::Memory(Run::sort(records).into_iter()),
+            name: String::from("sort buffer"),
+            line_number: 0,
+        }
+    }
+
+    /// sort `records` and write them to a new file in `dir`.
+    fn spill(records: Vec<(usize, SortedRecord)>, dir: &Path) -> Result<Self> {
+        log::info!("spilling {} sorted records", records.len());
+        Run::write(Run::sort(records).into_iter().map(|(_, r)| Ok(r)), dir)
+    }
+
+    /// merge sorted `runs` into a single run written to a new file in `dir`.
+    fn merge(
+        runs: Vec<Run>,
+        chromosome_order: &HashMap<String, usize>,
+        dir: &Path,
+    ) -> Result<Self> {
+        log::info!("merging {} sorted runs", runs.len());
+        let mut merge = MergeIterator::new(runs, chromosome_order);
+        let records = std::iter::from_fn(|| merge.next_position(None));
+        Run::write(records.map(|r| r.map(|m| m.position)), dir)
+    }
+
+    /// write sorted `records` to a new file in `dir`.
+    fn write<I: Iterator<Item = Result<SortedRecord>>>(records: I, dir: &Path) -> Result<Self> {
+        let n = RUNS.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!("bedder-sort-{}-{}.tmp", std::process::id(), n));
+        log::info!("writing sorted run to {:?}", path);
+        let file = File::create(&path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        let path = TempPath(path);
+        let mut writer = BufWriter::new(file);
+        for r in records {
+            let r = r?;
+            writeln!(writer, "{}\t{}\t{}\t{}", r.chrom, r.start, r.stop, r.line)?;
+        }
+        writer.into_inner().map_err(|e| e.into_error())?;
+        Ok(Run {
+            name: String::from(&*path.0.to_string_lossy()),
+            source: RunSource::File {
+                reader: None,
+                buf: std::string::String::new(),
+                path,
+            },
+            line_number: 0,
+        })
+    }
+
+    fn parse_error(&self, message: &str) -> Error {
+        Error::Parse {
+            name: self.name.clone(),
+            line: self.line_number,
+            region: None,
+            message: String::from(message),
+        }
+    }
+}
+
+impl PositionedIterator for Run {
+    type Item = SortedRecord;
+
+    fn next_position(&mut self, _q: Option<&dyn Positioned>) -> Option<Result<Self::Item>> {
+        let (reader, buf) = match &mut self.source {
+            RunSource::Memory(records) => return records.next().map(|(_, r)| Ok(r)),
+            RunSource::File { reader, buf, path } => {
+                if reader.is_none() {
+                    match File::open(&path.0) {
+                        Ok(f) => *reader = Some(BufReader::new(f)),
+                        Err(e) => return Some(Err(e.into())),
+                    }
+                }
+                (reader.as_mut().expect("the run was opened"), buf)
+            }
+        };
+        buf.clear();
+        match reader.read_line(buf) {
+            Ok(0) => return None,
+            Ok(_) => self.line_number += 1,
+            Err(e) => return Some(Err(e.into())),
+        }
+        let mut fields = buf.trim_end_matches(['\r', '\n']).splitn(4, '\t');
+        let (chrom, start, stop, line) = match (
+            fields.next(),
+            fields.next().and_then(|s| s.parse::<u64>().ok()),
+            fields.next().and_then(|s| s.parse::<u64>().ok()),
+            fields.next(),
+        ) {
+            (Some(chrom), Some(start), Some(stop), Some(line)) => (chrom, start, stop, line),
+            _ => return Some(Err(self.parse_error("invalid record in sorted run"))),
+        };
+        Some(Ok(SortedRecord {
+            chrom: String::from(chrom),
+            start,
+            stop,
+            line: line.to_string(),
+        }))
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+/// Sorts the records of an iterator by the order of the chromosomes in a genome file,
+/// then by start and stop. Records are sorted in memory until they take more than
+/// `max_memory` bytes; sorted runs are then written to temporary files and merged,
+/// at most `max_fan_in` at a time.
+pub struct Sorter<'a> {
+    chromosome_order: &'a HashMap<String, usize>,
+    max_memory: usize,
+    max_fan_in: usize,
+    tmp_dir: PathBuf,
+}
+
+impl<'a> Sorter<'a> {
+    pub fn new(chromosome_order: &'a HashMap<String, usize>) -> Self {
+        Sorter {
+            chromosome_order,
+            max_memory: DEFAULT_MAX_MEMORY,
+            max_fan_in: DEFAULT_MAX_FAN_IN,
+            tmp_dir: std::env::temp_dir(),
+        }
+    }
+
+    /// keep at most about `bytes` of records in memory.
+    pub fn with_max_memory(mut self, bytes: usize) -> Self {
+        self.max_memory = bytes;
+        self
+    }
+
+    /// merge at most `n` (and at least 2) sorted runs at once. With more runs, groups of
+    /// `n` runs are first merged into larger runs.
+    pub fn with_max_fan_in(mut self, n: usize) -> Self {
+        self.max_fan_in = n.max(2);
+        self
+    }
+
+    /// write sorted runs to `dir` instead of the system temporary directory.
+    pub fn with_tmp_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.tmp_dir = dir.as_ref().to_path_buf();
+        self
+    }
+
+    /// read all records from `it`, which may be in any order, and return them sorted.
+    /// Records keep their original line; those without a line become BED3.
+    pub fn sort<I: PositionedIterator>(&self, mut it: I) -> Result<Sorted<'a>> {
+        let mut chrom = LastChrom::default();
+        let mut records = Vec::new();
+        let mut size = 0;
+        let mut runs = Vec::new();
+        while let Some(p) = it.next_position(None) {
+            let p = p?;
+            let chrom_id = chrom.resolve(&p, self.chromosome_order, || it.name())?;
+            let record = SortedRecord::from_positioned(&p);
+            it.recycle(p);
+            size += record.size();
+            records.push((chrom_id, record));
+            if size > self.max_memory {
+                runs.push(Run::spill(std::mem::take(&mut records), &self.tmp_dir)?);
+                size = 0;
+            }
+        }
+        if !records.is_empty() || runs.is_empty() {
+            runs.push(Run::memory(records));
+        }
+        while runs.len() > self.max_fan_in {
+            let mut merged = Vec::new();
+            let mut rest = runs.into_iter();
+            loop {
+                let group: Vec<Run> = rest.by_ref().take(self.max_fan_in).collect();
+                if group.is_empty() {
+                    break;
+                }
+                merged.push(Run::merge(group, self.chromosome_order, &self.tmp_dir)?);
+            }
+            runs = merged;
+        }
+        if runs.len() > 1 {
+            log::info!("merging {} sorted runs", runs.len());
+        }
+        Ok(Sorted {
+            merge: MergeIterator::new(runs, self.chromosome_order),
+        })
+    }
+}
+
+/// The sorted records of a `Sorter`. Temporary files are removed once they are read or
+/// when this is dropped.
+pub struct Sorted<'a> {
+    merge: MergeIterator<'a, Run>,
+}
+
+impl<'a> PositionedIterator for Sorted<'a> {
+    type Item = SortedRecord;
+
+    fn next_position(&mut self, _q: Option<&dyn Positioned>) -> Option<Result<Self::Item>> {
+        self.merge
+            .next_position(None)
+            .map(|r| r.map(|Merged { position, .. }| position))
+    }
+
+    fn name(&self) -> String {
+        String::from("sorted records")
+    }
+}
+
+/// The records of a small iterator, loaded into memory and sorted if they were out of
+/// order, so that an unsorted file need not be sorted on disk first.
+pub struct InMemory<P> {
+    // each record with its 1-based number in the input.
+    records: std::vec::IntoIter<(P, u64)>,
+    path: String,
+    // the input number of the record last returned.
+    record_number: u64,
+}
+
+impl<P: Positioned> InMemory<P> {
+    /// read all records from `it`, which reads the file at `path`. Records are sorted in
+    /// the order of the intersection heap; those at the same position keep their input order.
+    pub fn new<I: PositionedIterator<Item = P>, Q: AsRef<Path>>(
+        mut it: I,
+        path: Q,
+        chromosome_order: &HashMap<String, usize>,
+    ) -> Result<Self> {
+        let mut chrom = LastChrom::default();
+        let mut records: Vec<ReverseOrderPosition<P>> = Vec::new();
+        // the name of the iterator at the first record that is out of order.
+        let mut unsorted = None;
+        while let Some(p) = it.next_position(None) {
+            let p = p?;
+            let chrom_id = chrom.resolve(&p, chromosome_order, || it.name())?;
+            let record = ReverseOrderPosition {
+                position: p,
+                chrom_id,
+                id: records.len(),
+                line: records.len() as u64 + 1,
+            };
+            // the order is reversed so that a max-heap returns the first record.
+            if unsorted.is_none() && records.last().is_some_and(|last| *last < record) {
+                unsorted = Some(it.name());
+            }
+            records.push(record);
+        }
+        if let Some(name) = unsorted {
+            log::warn!(
+                "intervals from {} out of order; sorting {} records in memory",
+                name,
+                records.len()
+            );
+            records.sort_by(|a, b| b.cmp(a));
+        }
+        Ok(InMemory {
+            records: records
+                .into_iter()
+                .map(|r| (r.position, r.line))
+                .collect::<Vec<_>>()
+                .into_iter(),
+            path: String::from(&*path.as_ref().to_string_lossy()),
+            record_number: 0,
+        })
+    }
+}
+
+impl<P: Positioned> PositionedIterator for InMemory<P> {
+    type Item = P;
+
+    fn next_position(&mut self, _q: Option<&dyn Positioned>) -> Option<Result<Self::Item>> {
+        let (p, record_number) = self.records.next()?;
+        self.record_number = record_number;
+        Some(Ok(p))
+    }
+
+    /// the path and the input number (counting records, not lines) of the record last
+    /// returned.
+    fn name(&self) -> String {
+        to_string_type(format!("{}:{}", self.path, self.record_number))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bedder_bed::BedderBed;
+    use std::io::Cursor;
+
+    fn sort(bed: &str, max_memory: usize) -> Result<Vec<std::string::String>> {
+        sort_with_fan_in(bed, max_memory, DEFAULT_MAX_FAN_IN)
+    }
+
+    fn sort_with_fan_in(
+        bed: &str,
+        max_memory: usize,
+        max_fan_in: usize,
+    ) -> Result<Vec<std::string::String>> {
+        let order = HashMap::from([(String::from("chr1"), 0), (String::from("chr2"), 1)]);
+        let bed = BedderBed::new(Cursor::new(bed.to_string()), "t.bed");
+        let mut sorted = Sorter::new(&order)
+            .with_max_memory(max_memory)
+            .with_max_fan_in(max_fan_in)
+            .sort(bed)?;
+        let mut lines = Vec::new();
+        while let Some(r) = sorted.next_position(None) {
+            lines.push(r?.line);
+        }
+        Ok(lines)
+    }
+
+    #[test]
+    fn test_sort() {
+        let bed = "chr2\t5\t10\ta\nchr1\t30\t40\tb\nchr1\t10\t20\tc\nchr1\t10\t15\td\nchr1\t10\t15\te\nchr2\t0\t10\tf\n";
+        let expected = vec![
+            "chr1\t10\t15\td",
+            "chr1\t10\t15\te",
+            "chr1\t10\t20\tc",
+            "chr1\t30\t40\tb",
+            "chr2\t0\t10\tf",
+            "chr2\t5\t10\ta",
+        ];
+        assert_eq!(sort(bed, DEFAULT_MAX_MEMORY).unwrap(), expected);
+        // spill every record to its own run; identical positions keep the input order.
+        assert_eq!(sort(bed, 0).unwrap(), expected);
+        assert!(sort("", 0).unwrap().is_empty());
+        // 6 runs merged 2 at a time in 3 passes.
+        assert_eq!(sort_with_fan_in(bed, 0, 2).unwrap(), expected);
+
+        let e = sort("chrX\t1\t2\n", 0).unwrap_err();
+        assert!(matches!(e, Error::UnknownChromosome { .. }));
+    }
+
+    #[test]
+    fn test_in_memory() {
+        let order = HashMap::from([(String::from("chr1"), 0), (String::from("chr2"), 1)]);
+        let bed =
+            "chr2\t5\t10\ta\nchr1\t30\t40\tb\nchr1\t10\t20\tc\nchr1\t10\t15\td\nchr1\t10\t15\te\n";
+        let bed = BedderBed::new(Cursor::new(bed.to_string()), "t.bed");
+        let mut sorted = InMemory::new(bed, "t.bed", &order).unwrap();
+        let mut names = Vec::new();
+        let mut record_names = Vec::new();
+        while let Some(r) = sorted.next_position(None) {
+            let r = r.unwrap();
+            names.push(r.line().unwrap().rsplit('\t').next().unwrap().to_string());
+            record_names.push(sorted.name());
+        }
+        assert_eq!(names, vec!["d", "e", "c", "b", "a"]);
+        // errors name the replayed record by its number in the input.
+        assert_eq!(
+            record_names,
+            vec!["t.bed:4", "t.bed:5", "t.bed:3", "t.bed:2", "t.bed:1"]
+        );
+    }
+}
@@ -17,3 +17,11 @@ pub use kstring::KString as String;
     not(feature = "kstring"),
 ))]
 pub use std::string::String;
+
+/// Convert a std String, e.g. from `format!` or `to_string()`, to [`String`].
+/// This is a no-op unless one of the alternative string features is enabled.
+#[inline]
+#[allow(clippy::useless_conversion)]
+pub fn to_string_type(s: std::string::String) -> String {
+    s.into()
+}
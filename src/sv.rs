@@ -0,0 +1,354 @@
+use crate::error::{Error, Result};
+use crate::position::{Field, FieldError, Positioned, PositionedIterator, Value};
+use crate::string::String;
+use std::collections::HashMap;
+use std::result;
+
+/// A structural variant reduced to what is needed to compare it to others.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sv {
+    pub chrom: String,
+    /// 0-based position of the VCF POS.
+    pub start: u64,
+    /// exclusive end (the VCF END for symbolic alleles).
+    pub stop: u64,
+    /// SVTYPE without any subtype (e.g. `DUP` for `DUP:TANDEM`), if known.
+    pub svtype: Option<String>,
+    /// absolute SVLEN, or the length implied by the alleles or END.
+    pub len: u64,
+}
+
+impl Sv {
+    /// get an Sv from a VCF record using `INFO.SVTYPE` and `INFO.SVLEN` when present, and the
+    /// ALT allele (e.g. `<DEL>` or a sequence) otherwise.
+    pub fn from_positioned<P: Positioned + ?Sized>(p: &P) -> result::Result<Sv, FieldError> {
+        let first_string = |name: &str| -> result::Result<Option<String>, FieldError> {
+            match p.value(Field::String(String::from(name))) {
+                Ok(Value::Strings(v)) => Ok(v.into_iter().flatten().next()),
+                Ok(_) | Err(FieldError::InvalidFieldName(_)) => Ok(None),
+                Err(e) => Err(e),
+            }
+        };
+        let reference = first_string("REF")?.unwrap_or_default();
+        let alt = first_string("ALT")?.unwrap_or_default();
+
+        let svtype = match first_string("INFO.SVTYPE")? {
+            Some(t) => Some(t),
+            None if alt.starts_with('<') => Some(String::from(alt.trim_matches(['<', '>']))),
+            None if alt.contains(['[', ']']) => Some(String::from("BND")),
+            None if alt.len() > reference.len() => Some(String::from("INS")),
+            None if alt.len() < reference.len() => Some(String::from("DEL")),
+            None => None,
+        }
+        .map(|t| String::from(t.split(':').next().unwrap_or_default()));
+
+        let svlen = match p.value(Field::String(String::from("INFO.SVLEN"))) {
+            Ok(Value::Ints(v)) => v.into_iter().flatten().next().map(|l| l.unsigned_abs()),
+            Ok(Value::Strings(v)) => v
+                .into_iter()
+                .flatten()
+                .next()
+                .and_then(|l| l.parse::<i64>().ok())
+                .map(|l| l.unsigned_abs()),
+            Ok(_) | Err(FieldError::InvalidFieldName(_)) => None,
+            Err(e) => return Err(e),
+        };
+        let len = match svlen {
+            Some(l) => l,
+            None if alt.bytes().all(|b| b.is_ascii_alphabetic()) && !alt.is_empty() => {
+                alt.len().abs_diff(reference.len()) as u64
+            }
+            // symbolic alleles span from after the padding base at POS to END.
+            None => p.stop().saturating_sub(p.start() + 1),
+        };
+        Ok(Sv {
+            chrom: String::from(p.chrom()),
+            start: p.start(),
+            stop: p.stop(),
+            svtype,
+            len,
+        })
+    }
+
+    /// events without a span that can be compared by reciprocal overlap.
+    fn is_point(&self) -> bool {
+        matches!(self.svtype.as_deref(), Some("INS") | Some("BND")) || self.stop <= self.start + 1
+    }
+}
+
+/// Thresholds used to decide whether two structural variants are the same event.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SvParams {
+    /// the largest distance allowed between the starts and between the ends.
+    pub max_distance: u64,
+    /// the smallest fraction of each variant that must be covered by the other.
+    /// not used for insertions and breakends.
+    pub min_reciprocal_overlap: f64,
+    /// the smallest ratio of the shorter to the longer SV length.
+    pub min_size_ratio: f64,
+    /// require the same SVTYPE (when both are known).
+    pub match_type: bool,
+}
+
+impl Default for SvParams {
+    fn default() -> Self {
+        SvParams {
+            max_distance: 500,
+            min_reciprocal_overlap: 0.5,
+            min_size_ratio: 0.7,
+            match_type: true,
+        }
+    }
+}
+
+/// How closely two structural variants agree.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SvMatch {
+    /// the larger of the distances between the starts and between the ends.
+    pub distance: u64,
+    /// the smaller of the fractions of each variant covered by the other (1 for point events).
+    pub reciprocal_overlap: f64,
+    /// the shorter SV length divided by the longer.
+    pub size_ratio: f64,
+}
+
+impl SvParams {
+    /// compare `a` and `b`, returning None if they do not match.
+    pub fn compare(&self, a: &Sv, b: &Sv) -> Option<SvMatch> {
+        if a.chrom != b.chrom {
+            return None;
+        }
+        if self.match_type {
+            if let (Some(at), Some(bt)) = (&a.svtype, &b.svtype) {
+                if at != bt {
+                    return None;
+                }
+            }
+        }
+        let distance = a.start.abs_diff(b.start).max(a.stop.abs_diff(b.stop));
+        if distance > self.max_distance {
+            return None;
+        }
+        let size_ratio = match a.len.max(b.len) {
+            0 => 1.0,
+            longest => a.len.min(b.len) as f64 / longest as f64,
+        };
+        if size_ratio < self.min_size_ratio {
+            return None;
+        }
+        let reciprocal_overlap = if a.is_point() || b.is_point() {
+            1.0
+        } else {
+            let overlap = a.stop.min(b.stop).saturating_sub(a.start.max(b.start));
+            let longest = (a.stop - a.start).max(b.stop - b.start);
+            overlap as f64 / longest as f64
+        };
+        if reciprocal_overlap < self.min_reciprocal_overlap {
+            return None;
+        }
+        Some(SvMatch {
+            distance,
+            reciprocal_overlap,
+            size_ratio,
+        })
+    }
+}
+
+/// read all records of `it` as structural variants. `name` (e.g. the path) is used in
+/// error messages.
+pub fn read_svs<I: PositionedIterator>(mut it: I, name: &str) -> Result<Vec<Sv>> {
+    let mut svs = Vec::new();
+    while let Some(p) = it.next_position(None) {
+        let p = p?;
+        let sv =
+            Sv::from_positioned(&p).map_err(|e| Error::field(name, svs.len() as u64 + 1, &p, e))?;
+        svs.push(sv);
+        it.recycle(p);
+    }
+    Ok(svs)
+}
+
+/// The result of comparing a query callset to a truth set. Each variant is matched to at
+/// most one variant from the other set.
+#[derive(Debug)]
+pub struct Comparison {
+    /// for each truth variant, the index of the matching query variant.
+    pub truth_matches: Vec<Option<usize>>,
+    /// for each query variant, the index of the matching truth variant.
+    pub query_matches: Vec<Option<usize>>,
+}
+
+impl Comparison {
+    /// matched truth variants (true positives by recall).
+    pub fn true_positives(&self) -> usize {
+        self.truth_matches.iter().flatten().count()
+    }
+
+    /// query variants without a match.
+    pub fn false_positives(&self) -> usize {
+        self.query_matches.iter().filter(|m| m.is_none()).count()
+    }
+
+    /// truth variants without a match.
+    pub fn false_negatives(&self) -> usize {
+        self.truth_matches.iter().filter(|m| m.is_none()).count()
+    }
+
+    /// the fraction of query variants that match a truth variant.
+    pub fn precision(&self) -> f64 {
+        ratio(
+            self.query_matches.iter().flatten().count(),
+            self.query_matches.len(),
+        )
+    }
+
+    /// the fraction of truth variants that match a query variant.
+    pub fn recall(&self) -> f64 {
+        ratio(self.true_positives(), self.truth_matches.len())
+    }
+
+    pub fn f1(&self) -> f64 {
+        let (p, r) = (self.precision(), self.recall());
+        if p + r == 0.0 {
+            0.0
+        } else {
+            2.0 * p * r / (p + r)
+        }
+    }
+}
+
+fn ratio(n: usize, d: usize) -> f64 {
+    if d == 0 {
+        0.0
+    } else {
+        n as f64 / d as f64
+    }
+}
+
+/// compare `query` to `truth`. Candidate pairs are found per chromosome by start position
+/// and then matched one-to-one, closest (by breakpoint distance, then size) first.
+pub fn compare(truth: &[Sv], query: &[Sv], params: &SvParams) -> Comparison {
+    let mut by_chrom: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, t) in truth.iter().enumerate() {
+        by_chrom.entry(t.chrom.as_str()).or_default().push(i);
+    }
+    for idxs in by_chrom.values_mut() {
+        idxs.sort_by_key(|&i| truth[i].start);
+    }
+
+    let mut candidates = Vec::new();
+    for (qi, q) in query.iter().enumerate() {
+        let idxs = match by_chrom.get(q.chrom.as_str()) {
+            Some(idxs) => idxs,
+            None => continue,
+        };
+        // truth variants with a start within max_distance of the query start.
+        let first = idxs.partition_point(|&i| truth[i].start + params.max_distance < q.start);
+        for &ti in idxs[first..].iter() {
+            if truth[ti].start > q.start + params.max_distance {
+                break;
+            }
+            if let Some(m) = params.compare(&truth[ti], q) {
+                candidates.push((m, ti, qi));
+            }
+        }
+    }
+    candidates.sort_by(|(a, ai, aq), (b, bi, bq)| {
+        a.distance
+            .cmp(&b.distance)
+            .then(b.size_ratio.total_cmp(&a.size_ratio))
+            .then((ai, aq).cmp(&(bi, bq)))
+    });
+
+    let mut truth_matches = vec![None; truth.len()];
+    let mut query_matches = vec![None; query.len()];
+    for (_, ti, qi) in candidates {
+        if truth_matches[ti].is_none() && query_matches[qi].is_none() {
+            truth_matches[ti] = Some(qi);
+            query_matches[qi] = Some(ti);
+        }
+    }
+    Comparison {
+        truth_matches,
+        query_matches,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bedder_vcf::vcf_from_str;
+
+    const HEADER: &str = "##fileformat=VCFv4.3\n##INFO=<ID=SVTYPE,Number=1,Type=String,Description=\"type\">\n##INFO=<ID=SVLEN,Number=.,Type=Integer,Description=\"length\">\n##INFO=<ID=END,Number=1,Type=Integer,Description=\"end\">\n#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n";
+
+    fn svs(records: &str) -> Vec<Sv> {
+        let data = format!("{}{}", HEADER, records);
+        read_svs(vcf_from_str(&data, "t.vcf"), "t.vcf").unwrap()
+    }
+
+    fn sv(start: u64, stop: u64, svtype: &str, len: u64) -> Sv {
+        Sv {
+            chrom: String::from("chr1"),
+            start,
+            stop,
+            svtype: Some(String::from(svtype)),
+            len,
+        }
+    }
+
+    #[test]
+    fn test_sv_from_record() {
+        let v = svs("chr1\t100\t.\tN\t<DEL>\t.\t.\tSVTYPE=DEL;SVLEN=-1000;END=1100\nchr1\t200\t.\tA\tACGTACGT\t.\t.\t.\nchr1\t300\t.\tN\t<DUP:TANDEM>\t.\t.\tEND=400\n");
+        assert_eq!(v[0], sv(99, 1100, "DEL", 1000));
+        assert_eq!(v[1], sv(199, 200, "INS", 7));
+        assert_eq!(v[2], sv(299, 400, "DUP", 100));
+    }
+
+    #[test]
+    fn test_sv_params() {
+        let p = SvParams::default();
+        let a = sv(1000, 2000, "DEL", 1000);
+        let m = p.compare(&a, &sv(1100, 2050, "DEL", 950)).unwrap();
+        assert_eq!(m.distance, 100);
+        assert!((m.reciprocal_overlap - 0.9).abs() < 1e-9);
+        assert!((m.size_ratio - 0.95).abs() < 1e-9);
+        // wrong type, too far, too small.
+        assert!(p.compare(&a, &sv(1000, 2000, "DUP", 1000)).is_none());
+        assert!(p.compare(&a, &sv(1600, 2000, "DEL", 400)).is_none());
+        assert!(p.compare(&a, &sv(1000, 1600, "DEL", 600)).is_none());
+        let p = SvParams {
+            match_type: false,
+            ..p
+        };
+        assert!(p.compare(&a, &sv(1000, 2000, "DUP", 1000)).is_some());
+        // insertions are compared by position and size only.
+        let p = SvParams::default();
+        assert!(p
+            .compare(&sv(100, 101, "INS", 300), &sv(400, 401, "INS", 250))
+            .is_some());
+    }
+
+    #[test]
+    fn test_compare() {
+        let truth = vec![
+            sv(1000, 2000, "DEL", 1000),
+            sv(5000, 5001, "INS", 100),
+            sv(9000, 9500, "DUP", 500),
+        ];
+        let query = vec![
+            sv(1010, 2000, "DEL", 990),
+            // a second call of the same deletion is not matched again.
+            sv(1050, 2000, "DEL", 950),
+            sv(5100, 5101, "INS", 90),
+            sv(20000, 21000, "DEL", 1000),
+        ];
+        let c = compare(&truth, &query, &SvParams::default());
+        assert_eq!(c.truth_matches, vec![Some(0), Some(2), None]);
+        assert_eq!(c.query_matches, vec![Some(0), None, Some(1), None]);
+        assert_eq!(c.true_positives(), 2);
+        assert_eq!(c.false_positives(), 2);
+        assert_eq!(c.false_negatives(), 1);
+        assert!((c.precision() - 0.5).abs() < 1e-9);
+        assert!((c.recall() - 2.0 / 3.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,202 @@
+use crate::error::Result;
+use crate::position::{Positioned, PositionedIterator};
+use crate::region::Region;
+use crate::string::{to_string_type, String};
+use std::fmt;
+use std::str::FromStr;
+
+/// How each region is divided into windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowSize {
+    /// windows of `size` bases starting every `step` bases. The last window of a region
+    /// may be shorter.
+    Fixed { size: u64, step: u64 },
+    /// this many windows of (nearly) equal size per region.
+    Count(u64),
+}
+
+impl fmt::Display for WindowSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WindowSize::Fixed { size, step } if size == step => write!(f, "{}", size),
+            WindowSize::Fixed { size, step } => write!(f, "{}:{}", size, step),
+            WindowSize::Count(n) => write!(f, "n{}", n),
+        }
+    }
+}
+
+/// parse `size`, `size:step` or `nCOUNT`, e.g. `1000`, `1000:500` or `n10`.
+impl FromStr for WindowSize {
+    type Err = std::string::String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let number = |n: &str| -> std::result::Result<u64, Self::Err> {
+            match n.parse::<u64>() {
+                Ok(0) | Err(_) => Err(format!("invalid window size: {}", s)),
+                Ok(n) => Ok(n),
+            }
+        };
+        if let Some(n) = s.strip_prefix('n') {
+            return Ok(WindowSize::Count(number(n)?));
+        }
+        match s.split_once(':') {
+            Some((size, step)) => Ok(WindowSize::Fixed {
+                size: number(size)?,
+                step: number(step)?,
+            }),
+            None => {
+                let size = number(s)?;
+                Ok(WindowSize::Fixed { size, step: size })
+            }
+        }
+    }
+}
+
+/// An iterator over windows of regions (like bedtools makewindows), e.g. to use as the
+/// base iterator of an `IntersectionIterator` to count intervals per bin.
+pub struct Windows {
+    regions: Vec<Region>,
+    size: WindowSize,
+    // the current region and the index of the next window in it.
+    i: usize,
+    window: u64,
+}
+
+impl Windows {
+    /// windows of `regions`, which must be sorted if the windows are intersected.
+    pub fn new(regions: Vec<Region>, size: WindowSize) -> Self {
+        Windows {
+            regions,
+            size,
+            i: 0,
+            window: 0,
+        }
+    }
+
+    /// windows of whole chromosomes from `genome` (as read by
+    /// `genome_file::parse_genome_lengths`).
+    pub fn genome(genome: &[(String, u64)], size: WindowSize) -> Self {
+        let regions = genome
+            .iter()
+            .map(|(chrom, length)| Region {
+                chrom: chrom.clone(),
+                start: 0,
+                stop: *length,
+            })
+            .collect();
+        Windows::new(regions, size)
+    }
+
+    /// the bounds of window `w` of `region`, or of the next non-empty window after it,
+    /// along with the index of that window.
+    fn window(&self, region: &Region, w: u64) -> Option<(u64, u64, u64)> {
+        let length = region.stop.saturating_sub(region.start);
+        let window = match self.size {
+            WindowSize::Fixed { size, step } => {
+                let start = w * step;
+                // a window that reached the end would contain all later windows.
+                if start >= length || (w > 0 && (w - 1) * step + size >= length) {
+                    return None;
+                }
+                Some((w, start, (start + size).min(length)))
+            }
+            // skip empty windows when the region is shorter than n.
+            WindowSize::Count(n) => (w..n)
+                .map(|w| (w, length * w / n, length * (w + 1) / n))
+                .find(|(_, start, stop)| start < stop),
+        };
+        window.map(|(w, start, stop)| (w, region.start + start, region.start + stop))
+    }
+}
+
+impl PositionedIterator for Windows {
+    type Item = Region;
+
+    fn next_position(&mut self, _q: Option<&dyn Positioned>) -> Option<Result<Self::Item>> {
+        loop {
+            let region = self.regions.get(self.i)?;
+            match self.window(region, self.window) {
+                Some((w, start, stop)) => {
+                    self.window = w + 1;
+                    return Some(Ok(Region {
+                        chrom: region.chrom.clone(),
+                        start,
+                        stop,
+                    }));
+                }
+                None => {
+                    self.i += 1;
+                    self.window = 0;
+                }
+            }
+        }
+    }
+
+    fn name(&self) -> String {
+        to_string_type(format!("windows({})", self.size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn windows(length: u64, size: &str) -> Vec<(u64, u64)> {
+        let genome = vec![(String::from("chr1"), length)];
+        let mut w = Windows::genome(&genome, size.parse().unwrap());
+        let mut v = Vec::new();
+        while let Some(r) = w.next_position(None) {
+            let r = r.unwrap();
+            v.push((r.start, r.stop));
+        }
+        v
+    }
+
+    #[test]
+    fn test_window_size() {
+        assert_eq!(
+            "100".parse::<WindowSize>().unwrap(),
+            WindowSize::Fixed {
+                size: 100,
+                step: 100
+            }
+        );
+        assert_eq!(
+            "100:50".parse::<WindowSize>().unwrap().to_string(),
+            "100:50"
+        );
+        assert_eq!("n4".parse::<WindowSize>().unwrap(), WindowSize::Count(4));
+        assert!("0".parse::<WindowSize>().is_err());
+        assert!("x".parse::<WindowSize>().is_err());
+    }
+
+    #[test]
+    fn test_windows() {
+        assert_eq!(windows(250, "100"), vec![(0, 100), (100, 200), (200, 250)]);
+        assert_eq!(
+            windows(250, "100:50"),
+            vec![(0, 100), (50, 150), (100, 200), (150, 250)]
+        );
+        assert_eq!(windows(10, "n4"), vec![(0, 2), (2, 5), (5, 7), (7, 10)]);
+        assert_eq!(windows(2, "n4"), vec![(0, 1), (1, 2)]);
+
+        let regions = vec![
+            Region {
+                chrom: String::from("chr1"),
+                start: 10,
+                stop: 30,
+            },
+            Region {
+                chrom: String::from("chr2"),
+                start: 0,
+                stop: 5,
+            },
+        ];
+        let mut w = Windows::new(regions, WindowSize::Count(2));
+        let mut v = Vec::new();
+        while let Some(r) = w.next_position(None) {
+            v.push(r.unwrap().to_string());
+        }
+        assert_eq!(v, vec!["chr1:11-20", "chr1:21-30", "chr2:1-2", "chr2:3-5"]);
+    }
+}
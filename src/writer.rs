@@ -0,0 +1,170 @@
+use crate::error::Result;
+use crate::index::Indexer;
+pub use crate::index::{IndexFormat, DEFAULT_MIN_SHIFT};
+use crate::position::Positioned;
+use crate::string::String;
+use noodles::bgzf;
+use noodles::csi::{self, index::reference_sequence::bin::Chunk};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// BgzfWriter writes BGZF-compressed text (e.g. BED or VCF lines) and optionally builds a tabix
+/// or CSI index on the fly so the output can be used as indexed input without `bgzip` and `tabix`.
+/// Indexed records must be written sorted with each chromosome in a single block.
+pub struct BgzfWriter<W: Write> {
+    writer: bgzf::Writer<W>,
+    indexer: Option<Indexer>,
+    // used in error messages.
+    name: String,
+}
+
+impl<W: Write> BgzfWriter<W> {
+    /// Create a writer with no index. `name` (e.g. the output path) is used in error messages.
+    pub fn new(inner: W, name: &str) -> Self {
+        BgzfWriter {
+            writer: bgzf::Writer::new(inner),
+            indexer: None,
+            name: String::from(name),
+        }
+    }
+
+    /// Build an index of the given format. `header` describes the columns of the output
+    /// (e.g. `csi::index::header::Builder::bed().build()`); chromosome names are filled in as
+    /// records are written.
+    pub fn with_index(mut self, format: IndexFormat, header: csi::index::Header) -> Self {
+        self.indexer = Some(Indexer::new(format, header, &self.name));
+        self
+    }
+
+    /// write a line that is not indexed, such as a header or comment. a newline is added.
+    pub fn write_header_line(&mut self, line: &str) -> io::Result<()> {
+        self.writer.write_all(line.as_bytes())?;
+        self.writer.write_all(b"\n")
+    }
+
+    /// write `line` (a newline is added), indexing it with the position of `p`.
+    pub fn write_record<P: Positioned + ?Sized>(&mut self, p: &P, line: &str) -> Result<()> {
+        let start = self.writer.virtual_position();
+        self.writer.write_all(line.as_bytes())?;
+        self.writer.write_all(b"\n")?;
+        let end = self.writer.virtual_position();
+
+        let indexer = match &mut self.indexer {
+            None => return Ok(()),
+            Some(indexer) => indexer,
+        };
+        indexer.add_record(p.chrom(), p.start(), p.stop(), Chunk::new(start, end))
+    }
+
+    /// Finish the BGZF stream and return the inner writer along with the index, if any.
+    pub fn finish(self) -> io::Result<(W, Option<csi::Index>)> {
+        let inner = self.writer.finish()?;
+        let index = self.indexer.map(Indexer::build);
+        Ok((inner, index))
+    }
+
+    /// the index format, if an index is being built.
+    pub fn index_format(&self) -> Option<IndexFormat> {
+        self.indexer.as_ref().map(Indexer::format)
+    }
+}
+
+impl BgzfWriter<std::fs::File> {
+    /// Create a BGZF file at `path`, optionally indexed (see `with_index`).
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let f = std::fs::File::create(&path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.as_ref().display(), e)))?;
+        Ok(BgzfWriter::new(f, &path.as_ref().to_string_lossy()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bedder_bed::BedRecord;
+    use crate::error::Error;
+    use std::io::Read;
+
+    fn bed_lines() -> Vec<std::string::String> {
+        let mut lines = Vec::new();
+        for chrom in ["chr1", "chr2"] {
+            for i in 0..1000 {
+                lines.push(format!("{}\t{}\t{}\tx{}", chrom, i * 100, i * 100 + 50, i));
+            }
+        }
+        lines
+    }
+
+    fn write_bed(format: Option<IndexFormat>) -> (Vec<u8>, Option<csi::Index>) {
+        let mut w = BgzfWriter::new(Vec::new(), "test.bed.gz");
+        if let Some(format) = format {
+            w = w.with_index(format, csi::index::header::Builder::bed().build());
+        }
+        w.write_header_line("#chrom\tstart\tend\tname").unwrap();
+        for line in bed_lines() {
+            let r = BedRecord::from_line(&line).unwrap();
+            w.write_record(&r, &line).unwrap();
+        }
+        w.finish().unwrap()
+    }
+
+    #[test]
+    fn test_bgzf_round_trip() {
+        let (data, index) = write_bed(None);
+        assert!(index.is_none());
+        let mut text = std::string::String::new();
+        bgzf::Reader::new(&data[..])
+            .read_to_string(&mut text)
+            .unwrap();
+        assert!(text.starts_with("#chrom\tstart\tend\tname\nchr1\t0\t50\tx0\n"));
+        assert_eq!(text.lines().count(), 2001);
+    }
+
+    #[test]
+    fn test_index() {
+        for format in [
+            IndexFormat::Tabix,
+            IndexFormat::Csi {
+                min_shift: DEFAULT_MIN_SHIFT,
+            },
+        ] {
+            let (data, index) = write_bed(Some(format));
+            let index = index.expect("expected an index");
+            let header = index.header().expect("expected a header");
+            let names: Vec<_> = header.reference_sequence_names().iter().collect();
+            assert_eq!(names, vec!["chr1", "chr2"]);
+            assert_eq!(index.reference_sequences().len(), 2);
+
+            // write and read back the index, then use it to find records by region.
+            let mut buf = Vec::new();
+            format.write_to(&mut buf, &index).unwrap();
+            let index = format.read_from(&buf[..]).unwrap();
+            let region = "chr2:50001-50100".parse().unwrap();
+            let mut reader = csi::io::IndexedReader::new(io::Cursor::new(data), index);
+            let lines: Vec<_> = reader
+                .query(&region)
+                .unwrap()
+                .map(|r| r.unwrap().as_ref().to_string())
+                .collect();
+            assert_eq!(lines, vec!["chr2\t50000\t50050\tx500"], "{}", format);
+        }
+    }
+
+    #[test]
+    fn test_unsorted() {
+        let mut w = BgzfWriter::new(Vec::new(), "test.bed.gz").with_index(
+            IndexFormat::Tabix,
+            csi::index::header::Builder::bed().build(),
+        );
+        for line in ["chr1\t10\t20", "chr2\t10\t20", "chr1\t30\t40"] {
+            let r = BedRecord::from_line(line).unwrap();
+            match w.write_record(&r, line) {
+                Ok(_) => assert_ne!(line, "chr1\t30\t40"),
+                Err(e) => {
+                    assert_eq!(line, "chr1\t30\t40");
+                    assert!(matches!(e, Error::OutOfOrder { .. }));
+                }
+            }
+        }
+    }
+}